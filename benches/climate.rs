@@ -0,0 +1,53 @@
+//! Benchmarks of biome lookups in the vanilla overworld parameter list.
+//! Run with `cargo bench --bench climate`.
+
+use criterion::{ black_box, criterion_group, criterion_main, Criterion };
+use serde_json::Value;
+use deepslate_rs::climate::{ self, LastResult, ParameterList, SearchMode, TargetPoint };
+use deepslate_rs::random::{ RandomSource, XoroshiroRandomSource };
+use deepslate_rs::{ dimension, grid, vanilla };
+
+fn overworld() -> ParameterList {
+	let biomes = Value::Array(vanilla::Version::latest().overworld_biomes());
+	ParameterList::from_json(&biomes).unwrap().0
+}
+
+/// Targets spread over the range that the overworld's climate noises reach.
+fn targets(count: usize) -> Vec<[f64; 6]> {
+	let mut random = XoroshiroRandomSource::from(0);
+	(0..count).map(|_| [0; 6].map(|_| random.next_double() * 2.4 - 1.2)).collect()
+}
+
+fn target(values: &[f64; 6]) -> TargetPoint {
+	climate::target(values[0], values[1], values[2], values[3], values[4], values[5])
+}
+
+fn search(c: &mut Criterion) {
+	let tree = overworld();
+	let linear = tree.clone().with_search_mode(SearchMode::Linear);
+	let targets = targets(1024);
+
+	c.bench_function("build overworld", |b| b.iter(overworld));
+	c.bench_function("find 1024 targets", |b| b.iter(|| {
+		targets.iter().map(|t| tree.find(black_box(target(t)))).sum::<i32>()
+	}));
+	c.bench_function("find 1024 targets from last result", |b| b.iter(|| {
+		let mut last = LastResult::default();
+		targets.iter().map(|t| tree.find_from(black_box(target(t)), &mut last)).sum::<i32>()
+	}));
+	c.bench_function("find 1024 targets linearly", |b| b.iter(|| {
+		targets.iter().map(|t| linear.find(black_box(target(t)))).sum::<i32>()
+	}));
+}
+
+fn render(c: &mut Criterion) {
+	let registry = vanilla::Version::latest().registry();
+	let world = dimension::Dimension::load(&registry, "overworld", 1).unwrap();
+	let grid = grid::Grid::flat(grid::Axis::new(-1024.0, 16.0, 128), 64.0, grid::Axis::new(-1024.0, 16.0, 128));
+	c.bench_function("biome map 128x128", |b| b.iter(|| {
+		grid.map(|x, y, z| world.biome(x as i32, y as i32, z as i32))
+	}));
+}
+
+criterion_group!(benches, search, render);
+criterion_main!(benches);
@@ -0,0 +1,19 @@
+//! Benchmarks of noise grids, sampled one position at a time and in batches.
+//! Run with `cargo bench --bench noise`.
+
+use criterion::{ criterion_group, criterion_main, Criterion };
+use deepslate_rs::{ grid, noise };
+
+fn normal(c: &mut Criterion) {
+	let noise = noise::NormalNoise::from_seed(0, -9, &[1.0, 1.0, 0.0, 1.0, 1.0]);
+	let grid = grid::Grid::flat(grid::Axis::new(-1024.0, 16.0, 128), 64.0, grid::Axis::new(-1024.0, 16.0, 128));
+	c.bench_function("normal noise 128x128", |b| b.iter(|| {
+		grid.map(|x, y, z| noise.sample(x, y, z))
+	}));
+	c.bench_function("normal noise 128x128 batched", |b| b.iter(|| {
+		grid.map_lanes(|x, y, z| noise.sample4(x, y, z))
+	}));
+}
+
+criterion_group!(benches, normal);
+criterion_main!(benches);
@@ -0,0 +1,398 @@
+//! Command line tool for inspecting the world generation of a seed, built
+//! with the `cli` feature.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use clap::{ Args, Parser, Subcommand };
+use serde_json::Value;
+use deepslate_rs::{ climate, datapack, density, dimension, grid, random, registry, render, stats, vanilla, world_preset };
+use deepslate_rs::random::RandomSource;
+
+#[derive(Parser)]
+#[command(name = "deepslate", about = "Inspect the world generation of a seed")]
+struct Cli {
+	/// World seed, as a number or as text like in the world creation screen
+	#[arg(long, short, allow_hyphen_values = true)]
+	seed: String,
+	/// Datapack directory or zip file, applied on top of the vanilla data
+	#[arg(long, short)]
+	datapack: Option<PathBuf>,
+	/// Game version whose bundled vanilla data to use, like 1.19.4
+	#[arg(long)]
+	game_version: Option<String>,
+	/// World preset whose dimensions to use, like minecraft:large_biomes
+	#[arg(long)]
+	preset: Option<String>,
+	/// Dimension to inspect
+	#[arg(long, default_value = "minecraft:overworld")]
+	dimension: String,
+	#[command(subcommand)]
+	command: Command,
+}
+
+#[derive(Args)]
+struct Position {
+	#[arg(allow_negative_numbers = true)]
+	x: i32,
+	#[arg(allow_negative_numbers = true)]
+	y: i32,
+	#[arg(allow_negative_numbers = true)]
+	z: i32,
+}
+
+#[derive(Args)]
+struct Center {
+	/// Block x coordinate to center on
+	#[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+	x: i32,
+	/// Block z coordinate to center on
+	#[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+	z: i32,
+}
+
+#[derive(Subcommand)]
+enum Command {
+	/// Print the biome at a block position
+	Biome {
+		#[command(flatten)]
+		position: Position,
+	},
+	/// Explain why a biome was chosen at a block position
+	Explain {
+		#[command(flatten)]
+		position: Position,
+		/// Number of runner-up biomes to print
+		#[arg(long, default_value_t = 3)]
+		count: usize,
+	},
+	/// Print the climate parameters around a block position as CSV
+	Climate {
+		#[command(flatten)]
+		position: Position,
+		/// Number of samples along each horizontal axis
+		#[arg(long, default_value_t = 1)]
+		size: usize,
+		/// Distance in blocks between samples
+		#[arg(long, default_value_t = 4)]
+		step: i32,
+	},
+	/// Render a biome map, or a heatmap of a noise router output, to PNG
+	Render {
+		/// The PNG file to write
+		output: PathBuf,
+		#[command(flatten)]
+		center: Center,
+		#[arg(long, default_value_t = 64, allow_negative_numbers = true)]
+		y: i32,
+		/// Width and height of the image in pixels
+		#[arg(long, default_value_t = 256)]
+		size: usize,
+		/// Blocks per pixel
+		#[arg(long, default_value_t = 4)]
+		scale: i32,
+		/// Render this noise router output instead of biomes
+		#[arg(long)]
+		noise: Option<String>,
+		/// Colour ramp for noise: greyscale, viridis, coolwarm or spectral
+		#[arg(long, default_value = "viridis")]
+		ramp: String,
+	},
+	/// Find the closest position of a biome
+	LocateBiome {
+		biome: String,
+		#[command(flatten)]
+		center: Center,
+		#[arg(long, default_value_t = 64, allow_negative_numbers = true)]
+		y: i32,
+		/// Search radius in blocks
+		#[arg(long, default_value_t = 6400)]
+		radius: i32,
+		/// Distance in blocks between checked positions
+		#[arg(long, default_value_t = 32)]
+		step: i32,
+	},
+	/// Find the closest start chunk of a random spread structure set
+	LocateStructure {
+		structure_set: String,
+		#[command(flatten)]
+		center: Center,
+		/// Search radius in chunks
+		#[arg(long, default_value_t = 100)]
+		radius: i32,
+		/// Skip checking the biome at the start chunk
+		#[arg(long)]
+		any_biome: bool,
+	},
+	/// Print the share of each biome around a position and how close it is
+	Stats {
+		#[command(flatten)]
+		center: Center,
+		#[arg(long, default_value_t = 64, allow_negative_numbers = true)]
+		y: i32,
+		/// Half the side of the square to sample, in blocks
+		#[arg(long, default_value_t = 4096)]
+		radius: i32,
+		/// Distance in blocks between samples
+		#[arg(long, default_value_t = 64)]
+		stride: i32,
+		/// Number of consecutive seeds to sum, starting at the world seed
+		#[arg(long, default_value_t = 1)]
+		seeds: i64,
+		/// Also print how often each pair of biomes are neighbours
+		#[arg(long)]
+		adjacency: bool,
+	},
+	/// Print the spawn position that the climate points to
+	Spawn,
+}
+
+fn main() {
+	let cli = Cli::parse();
+	if let Err(e) = run(&cli) {
+		eprintln!("Error: {}", e);
+		process::exit(1);
+	}
+}
+
+fn run(cli: &Cli) -> Result<(), String> {
+	let version = match &cli.game_version {
+		Some(name) => vanilla::Version::from_name(name).ok_or_else(|| format!("No bundled vanilla data for version {}", name))?,
+		None => vanilla::Version::latest(),
+	};
+	let mut registry = version.registry();
+	if let Some(path) = &cli.datapack {
+		datapack::Datapack::open(path)?.apply(&mut registry)?;
+	}
+	let load = |seed: i64| match &cli.preset {
+		Some(preset) => world_preset::WorldPreset::load(&registry, preset)?.dimension(&cli.dimension, &registry, seed),
+		None => dimension::Dimension::load(&registry, &cli.dimension, seed),
+	};
+	let world = load(random::parse_seed(&cli.seed).ok_or("Seed can't be empty")?)?;
+	match &cli.command {
+		Command::Biome { position } => {
+			println!("{}", world.biome_source().biome_id(world.biome(position.x, position.y, position.z)));
+		},
+		Command::Explain { position, count } => {
+			let explanation = world.explain(position.x, position.y, position.z, *count)
+				.ok_or("The biomes of this dimension don't depend on the climate")?;
+			let source = world.biome_source();
+			let target = explanation.target();
+			let chosen = explanation.chosen();
+			println!("{} (fitness {})", source.biome_id(chosen.biome), chosen.fitness());
+			for (i, name) in climate::PARAMETER_NAMES.iter().enumerate() {
+				let [min, max] = chosen.ranges[i];
+				println!("  {:<16} {:>8} in [{}, {}] distance {}", name, target[i], min, max, chosen.distances[i]);
+			}
+			for runner_up in explanation.runners_up() {
+				println!("{} (fitness {})", source.biome_id(runner_up.biome), runner_up.fitness());
+			}
+		},
+		Command::Climate { position, size, step } => {
+			println!("x,y,z,temperature,humidity,continentalness,erosion,weirdness,depth");
+			for dz in 0..*size as i32 {
+				for dx in 0..*size as i32 {
+					let (x, z) = (position.x + dx * step, position.z + dz * step);
+					let values = world.sampler().target(x, position.y, z).values();
+					let values = values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+					println!("{},{},{},{}", x, position.y, z, values.join(","));
+				}
+			}
+		},
+		Command::Render { output, center, y, size, scale, noise, ramp } => {
+			let from = |c: i32| (c - *size as i32 * scale / 2) as f64;
+			let grid = grid::Grid::flat(
+				grid::Axis::new(from(center.x), *scale as f64, *size),
+				*y as f64,
+				grid::Axis::new(from(center.z), *scale as f64, *size),
+			).with_order(grid::AxisOrder::ZYX);
+			let rgba = match noise {
+				Some(name) => {
+					let function = world.router().get(name).ok_or_else(|| format!("Unknown router output {}", name))?;
+					let values = grid.map(|x, y, z| function.compute(&density::Context::new(x as i32, y as i32, z as i32)));
+					let heatmap = render::Heatmap::new(&values, parse_ramp(ramp)?, None, None, 10);
+					let stats = heatmap.stats();
+					eprintln!("min {} max {} mean {} stddev {}", stats.min(), stats.max(), stats.mean(), stats.stddev());
+					heatmap.pixels().to_vec()
+				},
+				None => {
+					let biomes = grid.map(|x, y, z| world.biome(x as i32, y as i32, z as i32));
+					render::render_biomes(&biomes, &render::Palette::from_names(world.biome_source().possible_biomes()))
+				},
+			};
+			let png = render::encode_png(&rgba, *size as u32, *size as u32)?;
+			fs::write(output, png).map_err(|e| format!("Cannot write {}: {}", output.display(), e))?;
+		},
+		Command::LocateBiome { biome, center, y, radius, step } => {
+			let target = registry::normalize_id(biome);
+			let biome = world.biome_source().possible_biomes().iter().position(|b| *b == target)
+				.ok_or_else(|| format!("Biome {} doesn't generate in this dimension", target))? as climate::Biome;
+			if *step <= 0 {
+				return Err("Step must be positive".to_string())
+			}
+			match locate(*radius / step, |dx, dz| {
+				let (x, z) = (center.x + dx * step, center.z + dz * step);
+				if world.biome(x, *y, z) == biome { Some((x, z)) } else { None }
+			}) {
+				Some((x, z)) => println!("{} {} {}", x, y, z),
+				None => return Err(format!("Could not find {} within {} blocks", target, radius)),
+			}
+		},
+		Command::LocateStructure { structure_set, center, radius, any_biome } => {
+			let placement = RandomSpread::load(&registry, structure_set)?;
+			let allowed = if *any_biome { None } else { Some(structure_biomes(&registry, structure_set)?) };
+			let (center_x, center_z) = (center.x >> 4, center.z >> 4);
+			let regions = *radius / placement.spacing + 1;
+			let region_x = center_x.div_euclid(placement.spacing);
+			let region_z = center_z.div_euclid(placement.spacing);
+			let mut closest: Option<(i64, i32, i32)> = None;
+			for rz in region_z - regions..=region_z + regions {
+				for rx in region_x - regions..=region_x + regions {
+					let (chunk_x, chunk_z) = placement.start_chunk(world.seed(), rx, rz);
+					let (x, z) = (chunk_x * 16 + 8, chunk_z * 16 + 8);
+					let distance = ((chunk_x - center_x) as i64).pow(2) + ((chunk_z - center_z) as i64).pow(2);
+					if distance > (*radius as i64).pow(2) || closest.is_some_and(|c| c.0 <= distance) {
+						continue
+					}
+					if let Some(allowed) = &allowed {
+						if !allowed.contains(world.biome_source().biome_id(world.biome(x, 64, z))) {
+							continue
+						}
+					}
+					closest = Some((distance, x, z));
+				}
+			}
+			match closest {
+				Some((_, x, z)) => println!("{} ~ {}", x, z),
+				None => return Err(format!("Could not find {} within {} chunks", structure_set, radius)),
+			}
+		},
+		Command::Stats { center, y, radius, stride, seeds, adjacency } => {
+			if *seeds <= 0 {
+				return Err("Seeds must be positive".to_string())
+			}
+			let region = stats::Region::new(center.x, center.z, *radius, *y, *stride)?;
+			let seed = world.seed();
+			let end = seed.checked_add(*seeds).ok_or("Seed range overflows")?;
+			let stats = stats::BiomeStats::over_seeds(&region, seed..end, load)?;
+			let source = world.biome_source();
+			let mut biomes = stats.biomes();
+			biomes.sort_by_key(|&biome| std::cmp::Reverse(stats.get(biome).map_or(0, |c| c.count)));
+			println!("{:<36} {:>8} {:>9} {:>9} {:>6}", "biome", "share", "nearest", "mean", "seeds");
+			for biome in biomes {
+				let count = stats.get(biome).unwrap();
+				println!("{:<36} {:>7.3}% {:>9.0} {:>9.0} {:>6}", source.biome_id(biome), stats.share(biome) * 100.0, count.nearest, count.mean_nearest(), count.seeds);
+			}
+			if *adjacency {
+				let mut pairs = stats.adjacency().iter().collect::<Vec<_>>();
+				pairs.sort_by_key(|(_, &count)| std::cmp::Reverse(count));
+				for ((a, b), count) in pairs {
+					println!("{} {} {}", source.biome_id(*a), source.biome_id(*b), count);
+				}
+			}
+		},
+		Command::Spawn => {
+			let (x, z) = world.sampler().find_spawn(&climate::spawn_targets());
+			println!("{} ~ {}", x, z);
+		},
+	}
+	Ok(())
+}
+
+fn parse_ramp(ramp: &str) -> Result<render::ColorRamp, String> {
+	match ramp {
+		"greyscale" => Ok(render::ColorRamp::Greyscale),
+		"viridis" => Ok(render::ColorRamp::Viridis),
+		"coolwarm" => Ok(render::ColorRamp::Coolwarm),
+		"spectral" => Ok(render::ColorRamp::Spectral),
+		_ => Err(format!("Unknown colour ramp {}", ramp)),
+	}
+}
+
+/// Searches square rings of increasing size around the origin, returning
+/// the closest match in the first ring that has one.
+fn locate<T, F: Fn(i32, i32) -> Option<T>>(rings: i32, f: F) -> Option<T> {
+	for ring in 0..=rings {
+		let mut closest: Option<(i32, T)> = None;
+		for dz in -ring..=ring {
+			for dx in -ring..=ring {
+				if dx.abs() != ring && dz.abs() != ring {
+					continue
+				}
+				let distance = dx * dx + dz * dz;
+				if closest.as_ref().is_some_and(|c| c.0 <= distance) {
+					continue
+				}
+				if let Some(result) = f(dx, dz) {
+					closest = Some((distance, result));
+				}
+			}
+		}
+		if let Some((_, result)) = closest {
+			return Some(result)
+		}
+	}
+	None
+}
+
+/// A `random_spread` structure placement.
+struct RandomSpread {
+	spacing: i32,
+	separation: i32,
+	salt: i32,
+	triangular: bool,
+}
+
+impl RandomSpread {
+	fn load(registry: &registry::Registry, structure_set: &str) -> Result<Self, String> {
+		let json = registry.resource("worldgen/structure_set", structure_set)
+			.ok_or_else(|| format!("Unknown structure set {}", structure_set))?;
+		let placement = json.get("placement").ok_or("Structure set needs a placement")?;
+		if placement.get("type").and_then(Value::as_str).map(registry::normalize_id).as_deref() != Some("minecraft:random_spread") {
+			return Err("Only random spread placements are supported".to_string())
+		}
+		let int = |key: &str| placement.get(key).and_then(Value::as_i64).map(|v| v as i32)
+			.ok_or_else(|| format!("Placement needs {}", key));
+		let spread_type = placement.get("spread_type").and_then(Value::as_str).unwrap_or("linear");
+		let (spacing, separation) = (int("spacing")?, int("separation")?);
+		if separation < 0 || spacing <= separation {
+			return Err(format!("Placement spacing {} must be larger than separation {}", spacing, separation))
+		}
+		Ok(Self {
+			spacing,
+			separation,
+			salt: int("salt")?,
+			triangular: spread_type == "triangular",
+		})
+	}
+
+	/// The start chunk of the structure in a region, like vanilla's
+	/// `RandomSpreadStructurePlacement.getPotentialFeatureChunk`.
+	fn start_chunk(&self, seed: i64, region_x: i32, region_z: i32) -> (i32, i32) {
+		let mut random = random::LegacyRandomSource::new(0);
+		random.set_large_feature_with_salt(seed, region_x, region_z, self.salt);
+		let range = self.spacing - self.separation;
+		let mut offset = || if self.triangular {
+			(random.next_int_max(range) + random.next_int_max(range)) / 2
+		} else {
+			random.next_int_max(range)
+		};
+		let x = offset();
+		let z = offset();
+		(region_x * self.spacing + x, region_z * self.spacing + z)
+	}
+}
+
+/// The biomes any structure in a structure set can start in.
+fn structure_biomes(registry: &registry::Registry, structure_set: &str) -> Result<HashSet<String>, String> {
+	let json = registry.resource("worldgen/structure_set", structure_set)
+		.ok_or_else(|| format!("Unknown structure set {}", structure_set))?;
+	let mut biomes = HashSet::new();
+	for entry in json.get("structures").and_then(Value::as_array).ok_or("Structure set needs structures")? {
+		let id = entry.get("structure").and_then(Value::as_str).ok_or("Structure set entries need a structure")?;
+		let structure = registry.resource("worldgen/structure", id).ok_or_else(|| format!("Unknown structure {}", id))?;
+		biomes.extend(registry.resolve_tag("worldgen/biome", structure.get("biomes").ok_or("Structure needs biomes")?)?);
+	}
+	Ok(biomes)
+}
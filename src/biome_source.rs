@@ -0,0 +1,214 @@
+use std::sync::Arc;
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+use super::climate::{ Biome, ParameterList, TargetPoint };
+use super::registry::{ normalize_id, Registry };
+use super::sampler::Sampler;
+
+/// Chooses the biome at each quart position, like vanilla's `BiomeSource`.
+/// Biomes are numbered by their index in `possible_biomes`.
+pub trait BiomeSource: Send + Sync {
+	fn get_noise_biome(&self, x: i32, y: i32, z: i32, sampler: &Sampler) -> Biome;
+
+	/// The IDs of the biomes this source can return, indexed by their number.
+	fn possible_biomes(&self) -> &[String];
+
+	/// The climate parameters, if the biomes depend on them.
+	fn parameters(&self) -> Option<&ParameterList> {
+		None
+	}
+
+	fn biome_id(&self, biome: Biome) -> &str {
+		&self.possible_biomes()[biome as usize]
+	}
+}
+
+/// Parses a biome source of any of the supported types.
+pub fn from_json(json: &Value, registry: &Registry) -> Result<Arc<dyn BiomeSource>, String> {
+	let kind = json.get("type").and_then(Value::as_str).map(normalize_id).unwrap_or_else(|| "minecraft:multi_noise".to_string());
+	Ok(match kind.as_str() {
+		"minecraft:multi_noise" => Arc::new(MultiNoiseBiomeSource::from_json(json, registry)?),
+		"minecraft:fixed" => Arc::new(FixedBiomeSource::from_json(json)?),
+		"minecraft:checkerboard" => Arc::new(CheckerboardBiomeSource::from_json(json, registry)?),
+		"minecraft:the_end" => Arc::new(TheEndBiomeSource::new()),
+		kind => return Err(format!("Unsupported biome source {}", kind)),
+	})
+}
+
+/// A shared biome source of any type, for the wasm exports.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct DynBiomeSource(Arc<dyn BiomeSource>);
+
+impl DynBiomeSource {
+	pub fn new(source: Arc<dyn BiomeSource>) -> Self {
+		Self(source)
+	}
+
+	pub fn get(&self) -> &dyn BiomeSource {
+		self.0.as_ref()
+	}
+}
+
+/// Chooses biomes from the climate, like vanilla's `multi_noise` biome source.
+#[derive(Clone)]
+pub struct MultiNoiseBiomeSource {
+	parameters: ParameterList,
+	biomes: Vec<String>,
+}
+
+impl MultiNoiseBiomeSource {
+	pub fn new(parameters: ParameterList, biomes: Vec<String>) -> Self {
+		Self { parameters, biomes }
+	}
+
+	/// Parses a `multi_noise` biome source with a list of biomes, or with a
+	/// preset from the registry's `worldgen/multi_noise_biome_source_parameter_list`.
+	pub fn from_json(json: &Value, registry: &Registry) -> Result<Self, String> {
+		if let Some(biomes) = json.get("biomes") {
+			let (parameters, biomes) = ParameterList::from_json(biomes)?;
+			return Ok(Self::new(parameters, biomes))
+		}
+		let preset = json.get("preset").and_then(Value::as_str).ok_or("Multi noise biome source needs biomes or a preset")?;
+		let list = registry.resource("worldgen/multi_noise_biome_source_parameter_list", preset)
+			.ok_or_else(|| format!("Unknown biome parameter list {}", normalize_id(preset)))?;
+		match list.get("biomes") {
+			Some(_) => Self::from_json(list, registry),
+			None => Err(format!("Biome parameter list {} has no biomes", normalize_id(preset))),
+		}
+	}
+
+	pub fn find(&self, target: TargetPoint) -> Biome {
+		self.parameters.find(target)
+	}
+}
+
+impl BiomeSource for MultiNoiseBiomeSource {
+	fn get_noise_biome(&self, x: i32, y: i32, z: i32, sampler: &Sampler) -> Biome {
+		self.find(sampler.sample(x, y, z))
+	}
+
+	fn possible_biomes(&self) -> &[String] {
+		&self.biomes
+	}
+
+	fn parameters(&self) -> Option<&ParameterList> {
+		Some(&self.parameters)
+	}
+}
+
+/// A single biome everywhere, like the `single_biome_surface` world type.
+#[derive(Clone, Debug)]
+pub struct FixedBiomeSource {
+	biome: [String; 1],
+}
+
+impl FixedBiomeSource {
+	pub fn new(biome: &str) -> Self {
+		Self { biome: [normalize_id(biome)] }
+	}
+
+	pub fn from_json(json: &Value) -> Result<Self, String> {
+		Ok(Self::new(json.get("biome").and_then(Value::as_str).ok_or("Fixed biome source needs a biome")?))
+	}
+}
+
+impl BiomeSource for FixedBiomeSource {
+	fn get_noise_biome(&self, _x: i32, _y: i32, _z: i32, _sampler: &Sampler) -> Biome {
+		0
+	}
+
+	fn possible_biomes(&self) -> &[String] {
+		&self.biome
+	}
+}
+
+/// Diagonal stripes of biomes, each `1 << (scale + 2)` blocks wide.
+#[derive(Clone, Debug)]
+pub struct CheckerboardBiomeSource {
+	biomes: Vec<String>,
+	shift: i32,
+}
+
+impl CheckerboardBiomeSource {
+	pub fn new(biomes: Vec<String>, scale: i32) -> Result<Self, String> {
+		if biomes.is_empty() {
+			return Err("Checkerboard biome source needs at least one biome".to_string())
+		}
+		if !(0..=62).contains(&scale) {
+			return Err(format!("Checkerboard scale {} is not between 0 and 62", scale))
+		}
+		Ok(Self { biomes, shift: scale + 2 })
+	}
+
+	/// Parses a checkerboard biome source, whose biomes are a list, an ID or a tag.
+	pub fn from_json(json: &Value, registry: &Registry) -> Result<Self, String> {
+		let biomes = registry.resolve_tag("worldgen/biome", json.get("biomes").ok_or("Checkerboard biome source needs biomes")?)?;
+		let scale = json.get("scale").and_then(Value::as_i64).unwrap_or(2);
+		Self::new(biomes, scale as i32)
+	}
+}
+
+impl BiomeSource for CheckerboardBiomeSource {
+	fn get_noise_biome(&self, x: i32, _y: i32, z: i32, _sampler: &Sampler) -> Biome {
+		// Like Java, shifts only use the lowest 5 bits
+		let shift = self.shift & 31;
+		(x >> shift).wrapping_add(z >> shift).rem_euclid(self.biomes.len() as i32)
+	}
+
+	fn possible_biomes(&self) -> &[String] {
+		&self.biomes
+	}
+}
+
+/// The end's biomes, chosen from the height of the end islands, which the
+/// end noise settings route to the erosion output.
+#[derive(Clone, Debug)]
+pub struct TheEndBiomeSource {
+	biomes: [String; 5],
+}
+
+impl TheEndBiomeSource {
+	const END: Biome = 0;
+	const HIGHLANDS: Biome = 1;
+	const MIDLANDS: Biome = 2;
+	const SMALL_ISLANDS: Biome = 3;
+	const BARRENS: Biome = 4;
+
+	pub fn new() -> Self {
+		Self {
+			biomes: ["the_end", "end_highlands", "end_midlands", "small_end_islands", "end_barrens"].map(|b| format!("minecraft:{}", b)),
+		}
+	}
+}
+
+impl Default for TheEndBiomeSource {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl BiomeSource for TheEndBiomeSource {
+	fn get_noise_biome(&self, x: i32, y: i32, z: i32, sampler: &Sampler) -> Biome {
+		let (chunk_x, chunk_z) = ((x << 2) >> 4, (z << 2) >> 4);
+		if (chunk_x as i64).pow(2) + (chunk_z as i64).pow(2) <= 4096 {
+			return Self::END
+		}
+		let center_x = (chunk_x * 2 + 1) * 8;
+		let center_z = (chunk_z * 2 + 1) * 8;
+		let height = sampler.erosion(center_x, y << 2, center_z);
+		if height > 0.25 {
+			Self::HIGHLANDS
+		} else if height >= -0.0625 {
+			Self::MIDLANDS
+		} else if height < -0.21875 {
+			Self::SMALL_ISLANDS
+		} else {
+			Self::BARRENS
+		}
+	}
+
+	fn possible_biomes(&self) -> &[String] {
+		&self.biomes
+	}
+}
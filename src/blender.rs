@@ -0,0 +1,285 @@
+use std::collections::BTreeMap;
+use wasm_bindgen::prelude::*;
+use serde_json::Value;
+use super::climate::Biome;
+use super::noise::{ NoiseParameters, NormalNoise };
+use super::random::XoroshiroRandomSource;
+use super::util;
+
+const HEIGHT_BLENDING_RANGE_CELLS: i32 = 7 * 4 - 1;
+const HEIGHT_BLENDING_RANGE_CHUNKS: i32 = (HEIGHT_BLENDING_RANGE_CELLS + 3) >> 2;
+const DENSITY_BLENDING_RANGE_CELLS: f64 = 2.0;
+const DENSITY_BLENDING_RANGE_CHUNKS: i32 = 1;
+
+/// A column of quart cells along the border of an old chunk. `x` and `z` are
+/// relative to the chunk, in the range `0..=4`.
+#[derive(Clone, Debug)]
+pub struct BlendingColumn {
+	x: i32,
+	z: i32,
+	height: Option<f64>,
+	densities: Vec<f64>,
+	biomes: Vec<Biome>,
+}
+
+impl BlendingColumn {
+	pub fn new(x: i32, z: i32, height: Option<f64>, densities: Vec<f64>, biomes: Vec<Biome>) -> Self {
+		assert!((0..=4).contains(&x) && (0..=4).contains(&z), "Blending column is outside the chunk border");
+		Self { x, z, height, densities, biomes }
+	}
+}
+
+/// The terrain of an old chunk that new terrain is blended into. Densities
+/// are stored per 8 block cell and biomes per quart, both starting at `min_y`.
+#[derive(Clone, Debug)]
+pub struct BlendingData {
+	min_y: i32,
+	columns: Vec<BlendingColumn>,
+}
+
+impl BlendingData {
+	pub fn new(min_y: i32, columns: Vec<BlendingColumn>) -> Self {
+		Self { min_y, columns }
+	}
+
+	pub fn from_json(json: &Value) -> Result<Self, String> {
+		let min_y = json.get("min_y").and_then(Value::as_i64).unwrap_or(0) as i32;
+		let columns = json.get("columns").and_then(Value::as_array).ok_or("Blending data needs columns")?;
+		let columns = columns.iter().map(|column| {
+			let coord = |key: &str| column.get(key).and_then(Value::as_i64).map(|v| v as i32)
+				.filter(|v| (0..=4).contains(v))
+				.ok_or_else(|| format!("Blending column needs {} between 0 and 4", key));
+			let densities = column.get("densities").and_then(Value::as_array).map_or(Ok(Vec::new()), |d| {
+				d.iter().map(|v| v.as_f64().ok_or("Densities must be numbers")).collect::<Result<Vec<_>, _>>()
+			})?;
+			let biomes = column.get("biomes").and_then(Value::as_array).map_or(Ok(Vec::new()), |b| {
+				b.iter().map(|v| v.as_i64().map(|v| v as Biome).ok_or("Biomes must be integers")).collect::<Result<Vec<_>, _>>()
+			})?;
+			Ok(BlendingColumn::new(coord("x")?, coord("z")?, column.get("height").and_then(Value::as_f64), densities, biomes))
+		}).collect::<Result<Vec<_>, String>>()?;
+		Ok(Self::new(min_y, columns))
+	}
+
+	fn min_cell_y(&self) -> i32 {
+		self.min_y.div_euclid(8)
+	}
+
+	fn min_quart_y(&self) -> i32 {
+		self.min_y.div_euclid(4)
+	}
+
+	fn column(&self, x: i32, z: i32) -> Option<&BlendingColumn> {
+		self.columns.iter().find(|c| c.x == x && c.z == z)
+	}
+
+	fn height(&self, x: i32, z: i32) -> Option<f64> {
+		self.column(x, z).and_then(|c| c.height)
+	}
+
+	fn density(&self, x: i32, y: i32, z: i32) -> Option<f64> {
+		let i = y - self.min_cell_y();
+		self.column(x, z).filter(|_| i >= 0).and_then(|c| c.densities.get(i as usize).copied())
+	}
+
+	fn iterate_heights<F: FnMut(i32, i32, f64)>(&self, min_x: i32, min_z: i32, mut f: F) {
+		for column in self.columns.iter() {
+			if let Some(height) = column.height {
+				f(min_x + column.x, min_z + column.z, height);
+			}
+		}
+	}
+
+	fn iterate_densities<F: FnMut(i32, i32, i32, f64)>(&self, min_x: i32, min_z: i32, min_y: i32, max_y: i32, mut f: F) {
+		let min_cell_y = self.min_cell_y();
+		for column in self.columns.iter() {
+			let from = min_y.max(min_cell_y);
+			let to = max_y.min(min_cell_y + column.densities.len() as i32 - 1);
+			for y in from..=to {
+				f(min_x + column.x, y, min_z + column.z, column.densities[(y - min_cell_y) as usize]);
+			}
+		}
+	}
+
+	fn iterate_biomes<F: FnMut(i32, i32, Biome)>(&self, min_x: i32, quart_y: i32, min_z: i32, mut f: F) {
+		let i = quart_y - self.min_quart_y();
+		if i < 0 {
+			return
+		}
+		for column in self.columns.iter() {
+			if let Some(&biome) = column.biomes.get(i as usize) {
+				f(min_x + column.x, min_z + column.z, biome);
+			}
+		}
+	}
+}
+
+/// Blends new terrain and biomes into the old chunks surrounding a chunk,
+/// equivalent to vanilla's `Blender`.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct Blender {
+	// Ordered maps, so that the sums over old chunks and the choice between
+	// equally close biomes don't depend on hash order
+	height_and_biome_data: BTreeMap<(i32, i32), BlendingData>,
+	density_data: BTreeMap<(i32, i32), BlendingData>,
+	shift_noise: NormalNoise,
+}
+
+impl Blender {
+	/// Creates a blender for the chunk at `chunk_x`, `chunk_z`, using the
+	/// old chunks in range of it.
+	pub fn new(chunk_x: i32, chunk_z: i32, old_chunks: &BTreeMap<(i32, i32), BlendingData>) -> Self {
+		let mut height_and_biome_data = BTreeMap::new();
+		let mut density_data = BTreeMap::new();
+		for (&(x, z), data) in old_chunks.iter() {
+			let distance = (x - chunk_x).abs().max((z - chunk_z).abs());
+			if distance <= HEIGHT_BLENDING_RANGE_CHUNKS {
+				height_and_biome_data.insert((x, z), data.clone());
+			}
+			if distance <= DENSITY_BLENDING_RANGE_CHUNKS {
+				density_data.insert((x, z), data.clone());
+			}
+		}
+		Self {
+			height_and_biome_data,
+			density_data,
+			shift_noise: NormalNoise::create(&mut XoroshiroRandomSource::from(42), &NoiseParameters::new(-3, &[1.0, 1.0, 1.0, 0.0])),
+		}
+	}
+
+	/// Parses a list of old chunks, each with a `chunk_x` and `chunk_z`
+	/// alongside its [`BlendingData`].
+	pub fn from_json(chunk_x: i32, chunk_z: i32, json: &Value) -> Result<Self, String> {
+		let mut old_chunks = BTreeMap::new();
+		for chunk in json.as_array().ok_or("Old chunks must be an array")? {
+			let x = chunk.get("chunk_x").and_then(Value::as_i64).ok_or("Old chunk needs a chunk_x")?;
+			let z = chunk.get("chunk_z").and_then(Value::as_i64).ok_or("Old chunk needs a chunk_z")?;
+			old_chunks.insert((x as i32, z as i32), BlendingData::from_json(chunk)?);
+		}
+		Ok(Self::new(chunk_x, chunk_z, &old_chunks))
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.height_and_biome_data.is_empty() && self.density_data.is_empty()
+	}
+
+	/// Returns the alpha and offset used by the `blend_alpha` and `blend_offset`
+	/// density functions.
+	pub fn blend_offset_and_factor(&self, block_x: i32, block_z: i32) -> (f64, f64) {
+		let quart_x = block_x >> 2;
+		let quart_z = block_z >> 2;
+		if let Some(height) = self.blending_data_value(quart_x, 0, quart_z, |data, x, _, z| data.height(x, z)) {
+			return (0.0, Self::height_to_offset(height))
+		}
+		let mut total_weight = 0.0;
+		let mut weighted_heights = 0.0;
+		let mut closest = f64::INFINITY;
+		for (&(chunk_x, chunk_z), data) in self.height_and_biome_data.iter() {
+			data.iterate_heights(chunk_x << 2, chunk_z << 2, |x, z, height| {
+				let distance = length2(quart_x - x, quart_z - z);
+				if distance <= HEIGHT_BLENDING_RANGE_CELLS as f64 {
+					closest = closest.min(distance);
+					let weight = 1.0 / (distance * distance * distance * distance);
+					weighted_heights += height * weight;
+					total_weight += weight;
+				}
+			});
+		}
+		if closest == f64::INFINITY {
+			return (1.0, 0.0)
+		}
+		let alpha = (closest / (HEIGHT_BLENDING_RANGE_CELLS + 1) as f64).clamp(0.0, 1.0);
+		let alpha = 3.0 * alpha * alpha - 2.0 * alpha * alpha * alpha;
+		(alpha, Self::height_to_offset(weighted_heights / total_weight))
+	}
+
+	fn height_to_offset(height: f64) -> f64 {
+		let target_y = height + 0.5;
+		let target_y_mod = util::positive_modulo(target_y, 8.0);
+		(32.0 * (target_y - 128.0) - 3.0 * (target_y - 120.0) * target_y_mod + 3.0 * target_y_mod * target_y_mod) / (128.0 * (32.0 - 3.0 * target_y_mod))
+	}
+
+	/// Blends a density towards the densities of nearby old chunks, used by
+	/// the `blend_density` density function.
+	pub fn blend_density(&self, block_x: i32, block_y: i32, block_z: i32, density: f64) -> f64 {
+		let quart_x = block_x >> 2;
+		let cell_y = block_y / 8;
+		let quart_z = block_z >> 2;
+		if let Some(fixed) = self.blending_data_value(quart_x, cell_y, quart_z, BlendingData::density) {
+			return fixed
+		}
+		let mut total_weight = 0.0;
+		let mut weighted_densities = 0.0;
+		let mut closest = f64::INFINITY;
+		for (&(chunk_x, chunk_z), data) in self.density_data.iter() {
+			data.iterate_densities(chunk_x << 2, chunk_z << 2, cell_y - 1, cell_y + 1, |x, y, z, value| {
+				let distance = length3(quart_x - x, (cell_y - y) * 2, quart_z - z);
+				if distance <= DENSITY_BLENDING_RANGE_CELLS {
+					closest = closest.min(distance);
+					let weight = 1.0 / (distance * distance * distance * distance);
+					weighted_densities += value * weight;
+					total_weight += weight;
+				}
+			});
+		}
+		if closest == f64::INFINITY {
+			return density
+		}
+		let alpha = (closest / 3.0).clamp(0.0, 1.0);
+		util::lerp(alpha, weighted_densities / total_weight, density)
+	}
+
+	/// Returns the biome of a nearby old chunk if the quart position should
+	/// keep it, or `None` when the biome source should decide.
+	pub fn blend_biome(&self, quart_x: i32, quart_y: i32, quart_z: i32) -> Option<Biome> {
+		let mut closest = f64::INFINITY;
+		let mut closest_biome = None;
+		for (&(chunk_x, chunk_z), data) in self.height_and_biome_data.iter() {
+			data.iterate_biomes(chunk_x << 2, quart_y, chunk_z << 2, |x, z, biome| {
+				let distance = length2(quart_x - x, quart_z - z);
+				if distance <= HEIGHT_BLENDING_RANGE_CELLS as f64 && distance < closest {
+					closest_biome = Some(biome);
+					closest = distance;
+				}
+			});
+		}
+		if closest == f64::INFINITY {
+			return None
+		}
+		let shift = self.shift_noise.sample(quart_x as f64, 0.0, quart_z as f64) * 12.0;
+		let alpha = ((closest + shift) / (HEIGHT_BLENDING_RANGE_CELLS + 1) as f64).clamp(0.0, 1.0);
+		if alpha > 0.5 { None } else { closest_biome }
+	}
+
+	/// Resolves the biome at a quart position, falling back to `resolver`
+	/// when no old chunk biome is kept.
+	pub fn resolve_biome<F: FnOnce() -> Biome>(&self, quart_x: i32, quart_y: i32, quart_z: i32, resolver: F) -> Biome {
+		self.blend_biome(quart_x, quart_y, quart_z).unwrap_or_else(resolver)
+	}
+
+	fn blending_data_value<F: Fn(&BlendingData, i32, i32, i32) -> Option<f64>>(&self, cell_x: i32, cell_y: i32, cell_z: i32, getter: F) -> Option<f64> {
+		let chunk_x = cell_x >> 2;
+		let chunk_z = cell_z >> 2;
+		let min_x = (cell_x & 3) == 0;
+		let min_z = (cell_z & 3) == 0;
+		let get = |chunk_x: i32, chunk_z: i32| {
+			self.height_and_biome_data.get(&(chunk_x, chunk_z))
+				.and_then(|data| getter(data, cell_x - (chunk_x << 2), cell_y, cell_z - (chunk_z << 2)))
+		};
+		get(chunk_x, chunk_z)
+			.or_else(|| if min_x && min_z { get(chunk_x - 1, chunk_z - 1) } else { None })
+			.or_else(|| if min_x { get(chunk_x - 1, chunk_z) } else { None })
+			.or_else(|| if min_z { get(chunk_x, chunk_z - 1) } else { None })
+	}
+}
+
+/// Like vanilla's `Mth.length`, which squares the distances as doubles.
+fn length2(x: i32, z: i32) -> f64 {
+	let (x, z) = (x as f64, z as f64);
+	(x * x + z * z).sqrt()
+}
+
+fn length3(x: i32, y: i32, z: i32) -> f64 {
+	let (x, y, z) = (x as f64, y as f64, z as f64);
+	(x * x + y * y + z * z).sqrt()
+}
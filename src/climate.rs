@@ -1,22 +1,41 @@
+use std::sync::Arc;
 use wasm_bindgen::prelude::*;
+use serde_json::Value;
+use super::random::{ RandomSource, XoroshiroRandomSource };
+use super::registry::normalize_id;
 
 const SPACE: usize = 7;
 type TargetSpace = [i64; SPACE];
 type ParamSpace = [Param; SPACE];
 const QUANTIZE_SCALE: f64 = 10000.0;
-type Biome = i32;
+/// Vanilla's `Climate.RTree.CHILDREN_PER_NODE`: nodes with at most this
+/// many children aren't split any further.
+const CHILDREN_PER_NODE: usize = 6;
+pub type Biome = i32;
 
 pub fn target(temperature: f64, humidity: f64, continentalness: f64, erosion: f64, weirdness: f64, depth: f64) -> TargetPoint {
 	TargetPoint::new(temperature, humidity, continentalness, erosion, weirdness, depth)
 }
 
+/// The climate that vanilla's overworld looks for when choosing a spawn
+/// position: inland, away from rivers.
+pub fn spawn_targets() -> [ParamPoint; 2] {
+	let full = Param::span(-1.0, 1.0);
+	let inland = Param::span(-0.11, 1.0);
+	[
+		ParamPoint::new(full, full, inland, full, Param::span(-1.0, -0.16), Param::point(0.0), 0.0),
+		ParamPoint::new(full, full, inland, full, Param::span(0.16, 1.0), Param::point(0.0), 0.0),
+	]
+}
+
 #[allow(dead_code)]
 pub fn parameters(temperature: f64, humidity: f64, continentalness: f64, erosion: f64, weirdness: f64, depth: f64, offset: f64) -> ParamPoint {
 	ParamPoint::new(Param::point(temperature), Param::point(humidity), Param::point(continentalness), Param::point(erosion), Param::point(weirdness), Param::point(depth), offset)
 }
 
+/// Like vanilla's `Climate.quantizeCoord`, which works on floats.
 fn quantize(x: f64) -> i64 {
-	(x * QUANTIZE_SCALE) as i64
+	(x as f32 * QUANTIZE_SCALE as f32) as i64
 }
 
 fn unquantize(x: i64) -> f64 {
@@ -38,6 +57,25 @@ impl Param {
 		Param::new(quantize(min), quantize(max))
 	}
 
+	/// Parses a parameter that is either a single value or a `[min, max]` pair.
+	pub fn from_json(json: &Value) -> Result<Param, String> {
+		match json {
+			Value::Number(n) => Ok(Param::point(n.as_f64().unwrap_or(0.0))),
+			Value::Array(values) => match values.as_slice() {
+				[min, max] => {
+					let min = min.as_f64().ok_or("Parameter bounds must be numbers")?;
+					let max = max.as_f64().ok_or("Parameter bounds must be numbers")?;
+					if min > max {
+						return Err("Parameter min must not be larger than max".to_string())
+					}
+					Ok(Param::span(min, max))
+				},
+				_ => Err("Parameter ranges need exactly two values".to_string()),
+			},
+			_ => Err("Parameter must be a number or a range".to_string()),
+		}
+	}
+
 	fn new(min: i64, max: i64) -> Param {
 		assert!(min <= max);
 		Param { min, max }
@@ -80,8 +118,29 @@ impl ParamPoint {
 		}
 	}
 
+	pub fn from_json(json: &Value) -> Result<Self, String> {
+		let param = |key: &str| json.get(key).ok_or_else(|| format!("Parameters need {}", key)).and_then(Param::from_json);
+		Ok(Self::new(
+			param("temperature")?,
+			param("humidity")?,
+			param("continentalness")?,
+			param("erosion")?,
+			param("weirdness")?,
+			param("depth")?,
+			json.get("offset").and_then(Value::as_f64).ok_or("Parameters need offset")?,
+		))
+	}
+
+	/// The squared distance to a target, like vanilla's `ParameterPoint.fitness`.
+	pub fn fitness(&self, target: &TargetPoint) -> i64 {
+		self.space().iter().zip(target.space().iter()).map(|(param, &value)| {
+			let d = param.distance(value);
+			d * d
+		}).sum()
+	}
+
 	fn space(&self) -> ParamSpace {
-		[self.temperature, self.humidity, self.continentalness, self.erosion, self.weirdness, self.depth, Param::new(self.offset, self.offset)]
+		[self.temperature, self.humidity, self.continentalness, self.erosion, self.depth, self.weirdness, Param::new(self.offset, self.offset)]
 	}
 }
 
@@ -107,40 +166,634 @@ impl TargetPoint {
 	}
 
 	fn space(&self) -> TargetSpace {
-		[self.temperature, self.humidity, self.continentalness, self.erosion, self.weirdness, self.depth, 0]
+		[self.temperature, self.humidity, self.continentalness, self.erosion, self.depth, self.weirdness, 0]
+	}
+
+	/// The temperature, humidity, continentalness, erosion, weirdness and depth.
+	pub fn values(&self) -> [f64; 6] {
+		[self.temperature, self.humidity, self.continentalness, self.erosion, self.weirdness, self.depth].map(unquantize)
+	}
+
+	/// This target with a depth of zero, as used when choosing a spawn position.
+	pub fn at_surface(&self) -> TargetPoint {
+		TargetPoint { depth: 0, ..*self }
 	}
 
+	/// The `values` followed by an offset of zero.
 	pub fn vec(&self) -> Vec<f64> {
-		Vec::from(self.space().map(unquantize))
+		let mut vec = self.values().to_vec();
+		vec.push(0.0);
+		vec
 	}
 }
 
+/// How a parameter list finds the closest entry to a target.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub enum SearchMode {
+	/// Searches the R-tree like vanilla.
+	Tree,
+	/// Compares the target with every entry, keeping the first closest one.
+	/// Slow, but useful as a reference.
+	Linear,
+}
+
+/// The leaf that a search last returned. Vanilla keeps one per thread and
+/// starts the next search from it, so it wins ties with other leaves.
+/// Only meaningful for the parameter list that returned it.
+#[derive(Clone, Debug, Default)]
+pub struct LastResult(Option<u32>);
+
 #[wasm_bindgen]
+#[derive(Clone)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
 pub struct ParameterList {
-	root: Node,
+	tree: Arc<Tree>,
+	mode: SearchMode,
 }
 
 impl ParameterList {
 	pub fn new(biomes: &[(ParamPoint, Biome)]) -> Self {
-		let nodes = biomes.iter().map(|(point, biome)| {
-			Node::leaf(point, biome.clone())
+		let nodes = biomes.iter().enumerate().map(|(entry, (point, biome))| {
+			Node::leaf(point, *biome, entry)
 		}).collect::<Vec<_>>();
 		Self {
-			root: Node::build(nodes)
+			tree: Arc::new(Tree::new(Node::build(nodes), biomes.len())),
+			mode: SearchMode::Tree,
+		}
+	}
+
+	pub fn with_search_mode(mut self, mode: SearchMode) -> Self {
+		self.mode = mode;
+		self
+	}
+
+	pub fn search_mode(&self) -> SearchMode {
+		self.mode
+	}
+
+	/// Checks that a restored tree is consistent, so that searching it
+	/// can't index out of bounds.
+	#[cfg(feature = "snapshot")]
+	pub(crate) fn check(&self) -> Result<(), String> {
+		self.tree.check()
+	}
+
+	/// The index of the closest leaf. Linear searches ignore `last`.
+	fn search(&self, values: &TargetSpace, last: Option<u32>) -> u32 {
+		match self.mode {
+			SearchMode::Tree => self.tree.search(values, last),
+			SearchMode::Linear => self.tree.search_linear(values),
+		}
+	}
+
+	/// Parses the `biomes` list of a multi noise biome source. Biomes are
+	/// numbered in the order they first appear, and their IDs are returned
+	/// in that order.
+	pub fn from_json(json: &Value) -> Result<(Self, Vec<String>), String> {
+		let mut names: Vec<String> = Vec::new();
+		let mut biomes = Vec::new();
+		for entry in json.as_array().ok_or("Biome parameters must be an array")? {
+			let name = entry.get("biome").and_then(Value::as_str).ok_or("Biome parameters need a biome")?;
+			let point = ParamPoint::from_json(entry.get("parameters").ok_or("Biome parameters need parameters")?)?;
+			let name = normalize_id(name);
+			let biome = match names.iter().position(|n| *n == name) {
+				Some(i) => i,
+				None => {
+					names.push(name);
+					names.len() - 1
+				}
+			};
+			biomes.push((point, biome as Biome));
 		}
+		if biomes.is_empty() {
+			return Err("Need at least one biome".to_string())
+		}
+		Ok((Self::new(&biomes), names))
 	}
 
 	pub fn find(&self, target: TargetPoint) -> Biome {
-		let node = self.root.search(target.space());
-		node.biome.as_ref().expect("Expected a leaf node").clone()
+		self.tree.biomes[self.search(&target.space(), None) as usize]
+	}
+
+	/// Finds the closest entry starting from the last result, like vanilla
+	/// does for consecutive lookups on a thread. Only differs from `find`
+	/// when a target is equally close to several entries.
+	pub fn find_from(&self, target: TargetPoint, last: &mut LastResult) -> Biome {
+		let leaf = self.search(&target.space(), last.0.filter(|&leaf| self.tree.is_leaf(leaf)));
+		last.0 = Some(leaf);
+		self.tree.biomes[leaf as usize]
+	}
+
+	/// Explains why `find` returns a biome for a target: the entry it chose,
+	/// and the closest entry of up to `count` other biomes, closest first.
+	pub fn explain(&self, target: TargetPoint, count: usize) -> BiomeExplanation {
+		let values = target.space();
+		let chosen = self.search(&values, None);
+		let mut closest: Vec<LeafMatch> = Vec::new();
+		for &leaf in &self.tree.entries {
+			let biome = self.tree.biomes[leaf as usize];
+			if biome == self.tree.biomes[chosen as usize] {
+				continue
+			}
+			let candidate = LeafMatch::new(self.tree.space(leaf), biome, values);
+			match closest.iter_mut().find(|m| m.biome == biome) {
+				Some(m) if m.fitness() <= candidate.fitness() => {},
+				Some(m) => *m = candidate,
+				None => closest.push(candidate),
+			}
+		}
+		closest.sort_by_key(|m| (m.fitness(), m.biome));
+		closest.truncate(count);
+		BiomeExplanation {
+			target: values,
+			chosen: LeafMatch::new(self.tree.space(chosen), self.tree.biomes[chosen as usize], values),
+			runners_up: closest,
+		}
+	}
+
+	/// Looks for entries that can never win. Shadowed entries are found by
+	/// comparing the parameter boxes, and the coverage of each biome is
+	/// estimated from `samples` targets spread uniformly over the bounds of
+	/// all entries, so biomes without any samples are likely unreachable.
+	pub fn analyze(&self, samples: usize, seed: i64) -> ParameterAnalysis {
+		let leaves = self.tree.entries.iter().map(|&leaf| (self.tree.space(leaf), self.tree.biomes[leaf as usize])).collect::<Vec<_>>();
+
+		let mut shadows = Vec::new();
+		for (i, a) in leaves.iter().enumerate() {
+			for b in &leaves[i + 1..] {
+				match (shadows_space(&a.0, &b.0), shadows_space(&b.0, &a.0)) {
+					(true, true) => shadows.push(Shadow { entry: Entry::new(b), by: Entry::new(a), mutual: true }),
+					(true, false) => shadows.push(Shadow { entry: Entry::new(b), by: Entry::new(a), mutual: false }),
+					(false, true) => shadows.push(Shadow { entry: Entry::new(a), by: Entry::new(b), mutual: false }),
+					(false, false) => {},
+				}
+			}
+		}
+
+		let mut biomes = leaves.iter().map(|leaf| leaf.1).collect::<Vec<_>>();
+		biomes.sort_unstable();
+		biomes.dedup();
+		let mut counts = vec![0; biomes.len()];
+		let mut random = XoroshiroRandomSource::from(seed);
+		let bounds = self.tree.space(0);
+		for _ in 0..samples {
+			let mut target = [0; SPACE];
+			for (value, param) in target.iter_mut().zip(bounds.iter()).take(SPACE - 1) {
+				*value = param.min + ((param.max - param.min) as f64 * random.next_double()) as i64;
+			}
+			let biome = self.tree.biomes[self.search(&target, None) as usize];
+			if let Ok(i) = biomes.binary_search(&biome) {
+				counts[i] += 1;
+			}
+		}
+
+		ParameterAnalysis { samples, biomes, counts, shadows }
+	}
+}
+
+/// The names of the dimensions of a parameter point, in order.
+pub const PARAMETER_NAMES: [&str; SPACE] = ["temperature", "humidity", "continentalness", "erosion", "depth", "weirdness", "offset"];
+
+/// An entry of a parameter list and how far it is from a target.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LeafMatch {
+	pub biome: Biome,
+	/// The `[min, max]` range of each dimension, in `PARAMETER_NAMES` order.
+	pub ranges: [[f64; 2]; SPACE],
+	/// The squared distance to the target along each dimension, in
+	/// quantized units like vanilla's fitness.
+	pub distances: [i64; SPACE],
+}
+
+impl LeafMatch {
+	fn new(space: ParamSpace, biome: Biome, target: TargetSpace) -> Self {
+		Self {
+			biome,
+			ranges: space.map(|p| [unquantize(p.min), unquantize(p.max)]),
+			distances: std::array::from_fn(|i| {
+				let d = space[i].distance(target[i]);
+				d * d
+			}),
+		}
+	}
+
+	/// The total squared distance, which the closest entry minimizes.
+	pub fn fitness(&self) -> i64 {
+		self.distances.iter().sum()
+	}
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct BiomeExplanation {
+	target: TargetSpace,
+	chosen: LeafMatch,
+	runners_up: Vec<LeafMatch>,
+}
+
+impl BiomeExplanation {
+	pub fn chosen(&self) -> &LeafMatch {
+		&self.chosen
+	}
+
+	/// The closest entries of other biomes, closest first.
+	pub fn runners_up(&self) -> &[LeafMatch] {
+		&self.runners_up
+	}
+}
+
+#[wasm_bindgen]
+impl BiomeExplanation {
+	/// The quantized target that was explained in `PARAMETER_NAMES` order,
+	/// with an offset of zero.
+	#[wasm_bindgen(getter)]
+	pub fn target(&self) -> Vec<f64> {
+		self.target.map(unquantize).to_vec()
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn biome(&self) -> Biome {
+		self.chosen.biome
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn fitness(&self) -> f64 {
+		self.chosen.fitness() as f64
+	}
+
+	/// The min and max of each dimension of the chosen entry, flattened.
+	#[wasm_bindgen(getter)]
+	pub fn ranges(&self) -> Vec<f64> {
+		self.chosen.ranges.concat()
+	}
+
+	/// The squared distance along each dimension of the chosen entry.
+	#[wasm_bindgen(getter)]
+	pub fn distances(&self) -> Vec<f64> {
+		self.chosen.distances.map(|d| d as f64).to_vec()
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn runner_up_biomes(&self) -> Vec<Biome> {
+		self.runners_up.iter().map(|m| m.biome).collect()
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn runner_up_fitness(&self) -> Vec<f64> {
+		self.runners_up.iter().map(|m| m.fitness() as f64).collect()
+	}
+}
+
+/// An entry of a parameter list.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+	pub biome: Biome,
+	/// The `[min, max]` range of each dimension, in `PARAMETER_NAMES` order.
+	pub ranges: [[f64; 2]; SPACE],
+}
+
+impl Entry {
+	fn new((space, biome): &(ParamSpace, Biome)) -> Self {
+		Self {
+			biome: *biome,
+			ranges: space.map(|p| [unquantize(p.min), unquantize(p.max)]),
+		}
+	}
+}
+
+/// An entry that is never closer to a target than another entry, so it can
+/// only be chosen on a tie.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Shadow {
+	pub entry: Entry,
+	pub by: Entry,
+	/// Whether both entries have the same parameters, shadowing each other.
+	pub mutual: bool,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct ParameterAnalysis {
+	samples: usize,
+	biomes: Vec<Biome>,
+	counts: Vec<usize>,
+	shadows: Vec<Shadow>,
+}
+
+impl ParameterAnalysis {
+	/// The number of samples that chose each biome, in `biomes` order.
+	pub fn counts(&self) -> &[usize] {
+		&self.counts
+	}
+
+	pub fn shadows(&self) -> &[Shadow] {
+		&self.shadows
+	}
+}
+
+#[wasm_bindgen]
+impl ParameterAnalysis {
+	#[wasm_bindgen(getter)]
+	pub fn samples(&self) -> usize {
+		self.samples
+	}
+
+	/// Every biome of the parameter list, in ascending order.
+	#[wasm_bindgen(getter)]
+	pub fn biomes(&self) -> Vec<Biome> {
+		self.biomes.clone()
+	}
+
+	/// The share of samples that chose each biome, in `biomes` order.
+	#[wasm_bindgen(getter)]
+	pub fn coverage(&self) -> Vec<f64> {
+		self.counts.iter().map(|&count| count as f64 / self.samples.max(1) as f64).collect()
+	}
+
+	/// The biomes that no sample chose.
+	#[wasm_bindgen(getter)]
+	pub fn unreachable(&self) -> Vec<Biome> {
+		self.biomes.iter().zip(self.counts.iter()).filter(|(_, &count)| count == 0).map(|(&biome, _)| biome).collect()
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn shadowed_biomes(&self) -> Vec<Biome> {
+		self.shadows.iter().map(|s| s.entry.biome).collect()
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn shadowing_biomes(&self) -> Vec<Biome> {
+		self.shadows.iter().map(|s| s.by.biome).collect()
+	}
+
+	/// The min and max of each dimension of the shadowed entries, flattened.
+	#[wasm_bindgen(getter)]
+	pub fn shadowed_ranges(&self) -> Vec<f64> {
+		self.shadows.iter().flat_map(|s| s.entry.ranges.concat()).collect()
+	}
+
+	/// The min and max of each dimension of the shadowing entries, flattened.
+	#[wasm_bindgen(getter)]
+	pub fn shadowing_ranges(&self) -> Vec<f64> {
+		self.shadows.iter().flat_map(|s| s.by.ranges.concat()).collect()
+	}
+
+	/// Whether each shadow is mutual, as 0 or 1.
+	#[wasm_bindgen(getter)]
+	pub fn mutual(&self) -> Vec<u8> {
+		self.shadows.iter().map(|s| s.mutual as u8).collect()
 	}
 }
 
+/// Whether the entry `a` is at least as close as `b` to every target.
+/// Targets always have an offset of zero, so only the offsets' distance to
+/// zero matters.
+fn shadows_space(a: &ParamSpace, b: &ParamSpace) -> bool {
+	a.iter().zip(b.iter()).take(SPACE - 1).all(|(a, b)| a.min <= b.min && b.max <= a.max)
+		&& a[SPACE - 1].distance(0) <= b[SPACE - 1].distance(0)
+}
+
+/// The most nodes that a search keeps on its stack. Every level of the tree
+/// adds at most `CHILDREN_PER_NODE - 1` nodes, and a tree of 2^32 entries is
+/// only 14 levels deep.
+const SEARCH_STACK: usize = 128;
+/// The most children that a node can have. Larger lists are split into at
+/// most `CHILDREN_PER_NODE` buckets.
+const MAX_CHILDREN: usize = CHILDREN_PER_NODE;
+
+/// The R-tree of a parameter list, flattened so that searching it doesn't
+/// chase pointers. Nodes are stored breadth first, so the children of a
+/// node are next to each other.
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+struct Tree {
+	/// The bounds of every node, grouped with its siblings. A group stores
+	/// the mins of its nodes along each dimension, then the maxes, so that
+	/// the distances of all children of a node are computed in one pass.
+	/// The group of nodes `first..first + count` starts at `first * 2 * SPACE`.
+	bounds: Vec<i64>,
+	/// The index of the first child of each node and the number of
+	/// children. Leaves have none.
+	children: Vec<(u32, u32)>,
+	/// The first node and size of each node's group of siblings.
+	siblings: Vec<(u32, u32)>,
+	/// The biome of each node, which is only meaningful for leaves.
+	biomes: Vec<Biome>,
+	/// The entry of each node, which is only meaningful for leaves.
+	leaf_entries: Vec<u32>,
+	/// The index of the leaf of each entry, in the order they were given.
+	entries: Vec<u32>,
+}
+
+impl Tree {
+	fn new(root: Node, entries: usize) -> Self {
+		let mut tree = Tree {
+			bounds: Vec::new(),
+			children: Vec::new(),
+			siblings: Vec::new(),
+			biomes: Vec::new(),
+			leaf_entries: Vec::new(),
+			entries: vec![0; entries],
+		};
+		tree.push_group(&[&root]);
+		tree.siblings.push((0, 1));
+		let mut queue = std::collections::VecDeque::from([root]);
+		while let Some(node) = queue.pop_front() {
+			let index = tree.children.len() as u32;
+			let first = index + 1 + queue.len() as u32;
+			let count = node.children.len() as u32;
+			assert!(count as usize <= MAX_CHILDREN, "Too many children in a node");
+			tree.children.push((first, count));
+			tree.biomes.push(node.biome.unwrap_or(0));
+			tree.leaf_entries.push(node.entry as u32);
+			if node.biome.is_some() {
+				tree.entries[node.entry] = index;
+			}
+			tree.push_group(&node.children.iter().collect::<Vec<_>>());
+			tree.siblings.extend((0..count).map(|_| (first, count)));
+			queue.extend(node.children);
+		}
+		tree
+	}
+
+	#[cfg(feature = "snapshot")]
+	fn check(&self) -> Result<(), String> {
+		let nodes = self.children.len();
+		let in_bounds = |&(first, count): &(u32, u32)| count as usize <= MAX_CHILDREN && first as usize + count as usize <= nodes;
+		let consistent = nodes > 0
+			&& self.bounds.len() == nodes * 2 * SPACE
+			&& self.siblings.len() == nodes
+			&& self.biomes.len() == nodes
+			&& self.leaf_entries.len() == nodes
+			&& self.siblings[0] == (0, 1)
+			&& self.children.iter().all(in_bounds)
+			&& self.siblings.iter().all(|group| group.1 > 0 && in_bounds(group))
+			&& self.siblings.iter().enumerate().all(|(node, &(first, count))| (first..first + count).contains(&(node as u32)))
+			&& (0..nodes as u32).all(|node| (0..SPACE).all(|d| self.bound(node, d) <= self.bound(node, SPACE + d)))
+			&& self.entries.iter().all(|&leaf| self.is_leaf(leaf));
+		if !consistent {
+			return Err("Snapshot contains an inconsistent parameter tree".to_string())
+		}
+		// Nodes are stored breadth first, so each node's children follow
+		// the children of the nodes before it and come after the node
+		// itself. That rules out cycles and shared children, and `bound`
+		// stays inside the group. Expanding a node at depth `d` leaves at
+		// most `MAX_CHILDREN - 1` siblings per level on the search stack.
+		let mut depths = vec![0; nodes];
+		let mut next = 1;
+		for (node, &(first, count)) in self.children.iter().enumerate() {
+			if count == 0 {
+				continue
+			}
+			let group = first as usize..(first + count) as usize;
+			if first as usize != next || group.start <= node || self.siblings[group.clone()].iter().any(|&siblings| siblings != (first, count)) {
+				return Err("Snapshot contains an inconsistent parameter tree".to_string())
+			}
+			let depth = depths[node];
+			if (MAX_CHILDREN - 1) * depth + count as usize > SEARCH_STACK {
+				return Err("Snapshot contains a parameter tree that is too deep".to_string())
+			}
+			next = group.end;
+			depths[group].fill(depth + 1);
+		}
+		if next != nodes {
+			return Err("Snapshot contains an inconsistent parameter tree".to_string())
+		}
+		Ok(())
+	}
+
+	/// Adds the bounds of a group of siblings, which must be the next
+	/// nodes in breadth first order.
+	fn push_group(&mut self, nodes: &[&Node]) {
+		for d in 0..SPACE {
+			self.bounds.extend(nodes.iter().map(|node| node.space[d].min));
+		}
+		for d in 0..SPACE {
+			self.bounds.extend(nodes.iter().map(|node| node.space[d].max));
+		}
+	}
+
+	fn is_leaf(&self, node: u32) -> bool {
+		self.children.get(node as usize).is_some_and(|&(_, count)| count == 0)
+	}
+
+	fn bound(&self, node: u32, i: usize) -> i64 {
+		let (first, count) = self.siblings[node as usize];
+		self.bounds[first as usize * 2 * SPACE + i * count as usize + (node - first) as usize]
+	}
+
+	fn space(&self, node: u32) -> ParamSpace {
+		std::array::from_fn(|d| Param::new(self.bound(node, d), self.bound(node, SPACE + d)))
+	}
+
+	fn distance(&self, node: u32, values: &TargetSpace) -> i64 {
+		let mut dist = 0;
+		for (d, &value) in values.iter().enumerate() {
+			let diff = Tree::axis_distance(self.bound(node, d), self.bound(node, SPACE + d), value);
+			dist += diff * diff;
+		}
+		dist
+	}
+
+	/// Computes the distances of the group of nodes `first..first + count`
+	/// at once.
+	fn group_distances<'a>(&self, first: u32, count: u32, values: &TargetSpace, out: &'a mut [i64; MAX_CHILDREN]) -> &'a [i64] {
+		let count = count as usize;
+		let start = first as usize * 2 * SPACE;
+		let group = &self.bounds[start..start + 2 * SPACE * count];
+		let mins: [&[i64]; SPACE] = std::array::from_fn(|d| &group[d * count..(d + 1) * count]);
+		let maxes: [&[i64]; SPACE] = std::array::from_fn(|d| &group[(SPACE + d) * count..(SPACE + d + 1) * count]);
+		let out = &mut out[..count];
+		for (k, dist) in out.iter_mut().enumerate() {
+			*dist = 0;
+			for (d, &value) in values.iter().enumerate() {
+				let diff = Tree::axis_distance(mins[d][k], maxes[d][k], value);
+				*dist += diff * diff;
+			}
+		}
+		out
+	}
+
+	/// The same as `Param::distance`, without branches. Since `min <= max`,
+	/// at most one of the terms is positive.
+	#[inline]
+	fn axis_distance(min: i64, max: i64, value: i64) -> i64 {
+		(value - max).max(0) + (min - value).max(0)
+	}
+
+	/// Finds the closest leaf like vanilla's `Climate.RTree.Node.search`,
+	/// only replacing `last` with leaves that are strictly closer. Vanilla
+	/// recurses into each child whose bounds are closer than the best leaf
+	/// so far. Here, the children's distances are computed together and
+	/// they're visited from a stack in the same order.
+	fn search(&self, values: &TargetSpace, last: Option<u32>) -> u32 {
+		if self.children[0].1 == 0 {
+			return 0
+		}
+		let mut dist = last.map_or(i64::MAX, |leaf| self.distance(leaf, values));
+		let mut result = last;
+		let mut stack = [(0_u32, 0_i64); SEARCH_STACK];
+		let mut distances = [0; MAX_CHILDREN];
+		let mut len = 0;
+		let mut node = 0;
+		loop {
+			let (first, count) = self.children[node as usize];
+			if count == 0 {
+				dist = stack[len].1;
+				result = Some(node);
+			} else {
+				let group = self.group_distances(first, count, values, &mut distances);
+				for (child, &d) in (first..first + count).zip(group).rev() {
+					stack[len] = (child, d);
+					len += 1;
+				}
+			}
+			loop {
+				if len == 0 {
+					return result.expect("Expected a leaf node")
+				}
+				len -= 1;
+				if stack[len].1 < dist {
+					break
+				}
+			}
+			node = stack[len].0;
+		}
+	}
+
+	/// Finds the first entry with the smallest distance.
+	fn search_linear(&self, values: &TargetSpace) -> u32 {
+		let (mut dist, mut entry, mut result) = (i64::MAX, u32::MAX, 0);
+		let mut distances = [0; MAX_CHILDREN];
+		for &(first, count) in &self.children {
+			if count == 0 {
+				continue
+			}
+			let group = self.group_distances(first, count, values, &mut distances);
+			for (node, &d) in (first..first + count).zip(group) {
+				if d > dist || self.children[node as usize].1 != 0 {
+					continue
+				}
+				let e = self.leaf_entries[node as usize];
+				if d < dist || e < entry {
+					dist = d;
+					entry = e;
+					result = node;
+				}
+			}
+		}
+		result
+	}
+}
+
+/// A node of the R-tree while it is being built.
 #[derive(Clone)]
-pub struct Node {
+struct Node {
 	space: [Param; SPACE],
 	children: Vec<Node>,
 	biome: Option<Biome>,
+	entry: usize,
 }
 
 impl Node {
@@ -149,54 +802,27 @@ impl Node {
 			space: Self::build_space(&children),
 			children,
 			biome: None,
+			entry: 0,
 		}
 	}
 
-	fn leaf(point: &ParamPoint, biome: Biome) -> Self {
+	fn leaf(point: &ParamPoint, biome: Biome, entry: usize) -> Self {
 		Self {
 			space: point.space(),
 			children: Vec::new(),
 			biome: Some(biome),
+			entry,
 		}
 	}
 
-	fn search(&self, values: TargetSpace) -> &Node {
-		match self {
-			Node { children, biome: None, .. } => {
-				let mut dist = i64::MAX;
-				let mut result = self;
-
-				for node in children {
-					let d1 = node.distance(values);
-					if dist <= d1 { continue };
-					let child = node.search(values);
-					let d2 = if node == child { d1 } else { child.distance(values) };
-					if dist <= d2 { continue };
-					dist = d2;
-					result = child;
-				}
-
-				return result
-			}
-			_ => self
-		}
-	}
-
-	fn distance(&self, target: TargetSpace) -> i64 {
-		let mut dist: i64 = 0;
-		for i in 0..SPACE {
-			let d = self.space[i].distance(target[i]);
-			dist += d * d;
-		}
-		dist
-	}
-
 	fn build(mut nodes: Vec<Node>) -> Node {
 		match nodes.len() {
 			0 => panic!("Need at least one child to build a node"),
 			1 => nodes.pop().unwrap(),
-			2..=10 => {
-				nodes.sort_by_cached_key(|node| Node::cost(&node.space));
+			n if n <= CHILDREN_PER_NODE => {
+				nodes.sort_by_cached_key(|node| {
+					node.space.iter().map(|param| ((param.min + param.max) / 2).abs()).sum::<i64>()
+				});
 				Node::subtree(nodes)
 			},
 			_ => {
@@ -230,9 +856,11 @@ impl Node {
 		}
 	}
 
-	fn sort(nodes: &mut Vec<Node>, n: usize, abs: bool) {
+	/// Sorts nodes by the midpoint of dimension `n`, breaking ties with the
+	/// following dimensions. The sort is stable, like Java's `List.sort`.
+	fn sort(nodes: &mut [Node], n: usize, abs: bool) {
 		nodes.sort_by_cached_key(|node| {
-			[0; SPACE].map(|i| {
+			std::array::from_fn::<_, SPACE, _>(|i| {
 				let param = node.space[(n + i) % SPACE];
 				let mid = (param.min + param.max) / 2;
 				if abs { mid.abs() } else { mid }
@@ -240,11 +868,14 @@ impl Node {
 		});
 	}
 
+	/// Splits nodes into buckets of the largest power of `CHILDREN_PER_NODE`
+	/// below their number, like vanilla's `Climate.RTree.bucketize`.
 	fn bucketize(nodes: Vec<Node>) -> Vec<Node> {
 		let mut buckets = Vec::new();
 		let mut buffer = Vec::new();
 
-		let n = (10.0 as f64).powi(((nodes.len() as f64 - 0.01).ln() / (10.0 as f64).ln()).floor() as i32) as usize;
+		let base = CHILDREN_PER_NODE as f64;
+		let n = base.powi(((nodes.len() as f64 - 0.01).ln() / base.ln()).floor() as i32) as usize;
 		for node in nodes {
 			buffer.push(node);
 			if buffer.len() >= n {
@@ -252,7 +883,7 @@ impl Node {
 				buffer = Vec::new();
 			}
 		}
-		if buffer.len() != 0 {
+		if !buffer.is_empty() {
 			buckets.push(Node::subtree(buffer))
 		}
 		buckets
@@ -270,17 +901,10 @@ impl Node {
 		assert!(!nodes.is_empty(), "SubTree needs at least one child");
 		let mut space: [Option<Param>; SPACE] = [None; SPACE];
 		for node in nodes {
-			for i in 0..SPACE {
-				space[i] = Some(node.space[i].union(space[i]))
+			for (param, other) in space.iter_mut().zip(node.space.iter()) {
+				*param = Some(other.union(*param))
 			}
 		}
-		let res = space.map(|p| p.unwrap());
-		res
-	}
-}
-
-impl PartialEq for Node {
-	fn eq(&self, other: &Node) -> bool {
-		std::ptr::eq(self, other)
+		space.map(|p| p.unwrap())
 	}
 }
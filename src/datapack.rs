@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use serde_json::Value;
+use super::noise::NoiseParameters;
+use super::registry::Registry;
+
+/// The JSON files of a datapack by their path in the pack, like
+/// `data/minecraft/worldgen/noise/temperature.json`.
+#[derive(Clone, Debug, Default)]
+pub struct Datapack {
+	files: BTreeMap<String, Value>,
+}
+
+impl Datapack {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Reads a datapack from a JSON object mapping paths to the content of
+	/// each file, either as JSON or as text.
+	pub fn from_json(files: &Value) -> Result<Self, String> {
+		let mut datapack = Self::new();
+		for (path, json) in files.as_object().ok_or("Datapack files must be an object")? {
+			match json {
+				Value::String(text) => datapack.insert_text(path, text)?,
+				json => datapack.insert(path, json.clone()),
+			}
+		}
+		Ok(datapack)
+	}
+
+	/// Reads a datapack from a directory, or from a zip file with the `zip`
+	/// feature.
+	pub fn open(path: &Path) -> Result<Self, String> {
+		if path.is_dir() {
+			return Self::from_dir(path)
+		}
+		#[cfg(feature = "zip")]
+		if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("zip")) {
+			let file = fs::File::open(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+			return Self::from_zip(file)
+		}
+		Err(format!("{} is not a datapack", path.display()))
+	}
+
+	pub fn from_dir(path: &Path) -> Result<Self, String> {
+		fn walk(datapack: &mut Datapack, dir: &Path, prefix: &str) -> Result<(), String> {
+			let entries = fs::read_dir(dir).map_err(|e| format!("Cannot read {}: {}", dir.display(), e))?;
+			for entry in entries {
+				let path = entry.map_err(|e| e.to_string())?.path();
+				let name = format!("{}{}", prefix, path.file_name().unwrap_or_default().to_string_lossy());
+				if path.is_dir() {
+					walk(datapack, &path, &format!("{}/", name))?;
+				} else if name.ends_with(".json") {
+					let text = fs::read_to_string(&path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+					datapack.insert_text(&name, &text)?;
+				}
+			}
+			Ok(())
+		}
+		let mut datapack = Self::new();
+		let data = path.join("data");
+		if !data.is_dir() {
+			return Err(format!("{} has no data folder", path.display()))
+		}
+		walk(&mut datapack, &data, "data/")?;
+		Ok(datapack)
+	}
+
+	#[cfg(feature = "zip")]
+	pub fn from_zip<R: std::io::Read + std::io::Seek>(reader: R) -> Result<Self, String> {
+		use std::io::Read;
+		let mut archive = zip::ZipArchive::new(reader).map_err(|e| e.to_string())?;
+		let mut datapack = Self::new();
+		for i in 0..archive.len() {
+			let mut file = archive.by_index(i).map_err(|e| e.to_string())?;
+			let name = file.name().to_string();
+			if file.is_file() && name.starts_with("data/") && name.ends_with(".json") {
+				let mut text = String::new();
+				file.read_to_string(&mut text).map_err(|e| format!("Cannot read {}: {}", name, e))?;
+				datapack.insert_text(&name, &text)?;
+			}
+		}
+		Ok(datapack)
+	}
+
+	pub fn insert(&mut self, path: &str, json: Value) {
+		self.files.insert(path.trim_start_matches('/').to_string(), json);
+	}
+
+	fn insert_text(&mut self, path: &str, text: &str) -> Result<(), String> {
+		let json = serde_json::from_str(text).map_err(|e| format!("Invalid JSON in {}: {}", path, e))?;
+		self.insert(path, json);
+		Ok(())
+	}
+
+	pub fn len(&self) -> usize {
+		self.files.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.files.is_empty()
+	}
+
+	/// Builds a registry from the definitions in this datapack alone.
+	pub fn registry(&self) -> Result<Registry, String> {
+		let mut registry = Registry::new();
+		self.apply(&mut registry)?;
+		Ok(registry)
+	}
+
+	/// Adds the definitions in this datapack to a registry, replacing the
+	/// ones it already has. Applying datapacks in order layers them.
+	pub fn apply(&self, registry: &mut Registry) -> Result<(), String> {
+		for (path, json) in &self.files {
+			let (kind, id) = match resource_location(path) {
+				Some(location) => location,
+				None => continue,
+			};
+			match kind {
+				"worldgen/noise" => {
+					let params = NoiseParameters::from_json(json).map_err(|e| format!("Error in {}: {}", path, e))?;
+					registry.register_noise(&id, params);
+				},
+				"worldgen/density_function" => registry.register_density_function(&id, json.clone()),
+				"worldgen/noise_settings" => registry.register_noise_settings(&id, json.clone()),
+				_ => registry.register_resource(kind, &id, json.clone()),
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Splits a datapack path like `data/<namespace>/<kind>/<path>.json` into the
+/// kind and the resource ID. Kinds are a single folder, like `dimension`,
+/// except for `worldgen/<kind>`, `tags/<kind>` and `tags/worldgen/<kind>`.
+fn resource_location(path: &str) -> Option<(&str, String)> {
+	let path = path.strip_prefix("data/")?.strip_suffix(".json")?;
+	let (namespace, rest) = path.split_once('/')?;
+	let kind_len = match rest.split('/').collect::<Vec<_>>().as_slice() {
+		["tags", "worldgen", _, _, ..] => 3,
+		["tags", _, _, ..] | ["worldgen", _, _, ..] => 2,
+		[_, _, ..] => 1,
+		_ => return None,
+	};
+	let split = rest.match_indices('/').nth(kind_len - 1)?.0;
+	Some((&rest[..split], format!("{}:{}", namespace, &rest[split + 1..])))
+}
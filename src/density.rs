@@ -0,0 +1,469 @@
+use std::collections::{ HashMap, HashSet };
+use std::sync::Arc;
+use serde_json::Value;
+use super::blender::Blender;
+use super::noise::{ BlendedNoise, NoiseParameters, NormalNoise, SimplexNoise };
+use super::random::{ LegacyRandomSource, PositionalRandomFactory, RandomSource, XoroshiroRandomSource };
+use super::registry::{ Registry, normalize_id };
+use super::spline::CubicSpline;
+use super::util;
+
+/// The block position a density function is evaluated at, optionally with
+/// a blender for chunks bordering old terrain.
+pub struct Context<'a> {
+	pub x: i32,
+	pub y: i32,
+	pub z: i32,
+	pub blender: Option<&'a Blender>,
+}
+
+impl<'a> Context<'a> {
+	pub fn new(x: i32, y: i32, z: i32) -> Self {
+		Self { x, y, z, blender: None }
+	}
+
+	pub fn with_blender(x: i32, y: i32, z: i32, blender: &'a Blender) -> Self {
+		Self { x, y, z, blender: Some(blender) }
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub enum MarkerType {
+	Interpolated,
+	FlatCache,
+	Cache2D,
+	CacheOnce,
+	CacheAllInCell,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub enum MappedType {
+	Abs,
+	Square,
+	Cube,
+	HalfNegative,
+	QuarterNegative,
+	Squeeze,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub enum Ap2Type {
+	Add,
+	Mul,
+	Min,
+	Max,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub enum RarityValueMapper {
+	Tunnels,
+	Caves,
+}
+
+impl RarityValueMapper {
+	fn get(&self, value: f64) -> f64 {
+		match self {
+			RarityValueMapper::Tunnels => {
+				if value < -0.5 { 0.75 } else if value < 0.0 { 1.0 } else if value < 0.5 { 1.5 } else { 2.0 }
+			},
+			RarityValueMapper::Caves => {
+				if value < -0.75 { 0.5 } else if value < -0.5 { 0.75 } else if value < 0.5 { 1.0 } else if value < 0.75 { 2.0 } else { 3.0 }
+			},
+		}
+	}
+}
+
+/// A seeded density function tree, equivalent to vanilla's `DensityFunction`
+/// after all noises have been instantiated for a world seed.
+///
+/// Cache and interpolation markers are kept in the tree but evaluate their
+/// argument directly, the same as vanilla does outside of a `NoiseChunk`.
+#[derive(Clone)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub enum DensityFunction {
+	Constant(f64),
+	Reference(String, Arc<DensityFunction>),
+	Marker(MarkerType, Box<DensityFunction>),
+	BlendAlpha,
+	BlendOffset,
+	BlendDensity(Box<DensityFunction>),
+	Beardifier,
+	OldBlendedNoise(Arc<BlendedNoise>),
+	EndIslands(Arc<SimplexNoise>),
+	Noise {
+		noise: Arc<NormalNoise>,
+		xz_scale: f64,
+		y_scale: f64,
+	},
+	ShiftedNoise {
+		noise: Arc<NormalNoise>,
+		xz_scale: f64,
+		y_scale: f64,
+		shift_x: Box<DensityFunction>,
+		shift_y: Box<DensityFunction>,
+		shift_z: Box<DensityFunction>,
+	},
+	ShiftA(Arc<NormalNoise>),
+	ShiftB(Arc<NormalNoise>),
+	Shift(Arc<NormalNoise>),
+	WeirdScaledSampler {
+		input: Box<DensityFunction>,
+		noise: Arc<NormalNoise>,
+		rarity_value_mapper: RarityValueMapper,
+	},
+	RangeChoice {
+		input: Box<DensityFunction>,
+		min_inclusive: f64,
+		max_exclusive: f64,
+		when_in_range: Box<DensityFunction>,
+		when_out_of_range: Box<DensityFunction>,
+	},
+	Clamp {
+		input: Box<DensityFunction>,
+		min: f64,
+		max: f64,
+	},
+	Mapped(MappedType, Box<DensityFunction>),
+	Ap2(Ap2Type, Box<DensityFunction>, Box<DensityFunction>),
+	Spline(Box<CubicSpline>),
+	YClampedGradient {
+		from_y: i32,
+		to_y: i32,
+		from_value: f64,
+		to_value: f64,
+	},
+}
+
+impl DensityFunction {
+	pub fn compute(&self, context: &Context) -> f64 {
+		match self {
+			DensityFunction::Constant(value) => *value,
+			DensityFunction::Reference(_, function) => function.compute(context),
+			DensityFunction::Marker(_, function) => function.compute(context),
+			DensityFunction::BlendAlpha => match context.blender {
+				Some(blender) => blender.blend_offset_and_factor(context.x, context.z).0,
+				None => 1.0,
+			},
+			DensityFunction::BlendOffset => match context.blender {
+				Some(blender) => blender.blend_offset_and_factor(context.x, context.z).1,
+				None => 0.0,
+			},
+			DensityFunction::BlendDensity(input) => match context.blender {
+				Some(blender) => blender.blend_density(context.x, context.y, context.z, input.compute(context)),
+				None => input.compute(context),
+			},
+			DensityFunction::Beardifier => 0.0,
+			DensityFunction::OldBlendedNoise(noise) => noise.sample(context.x, context.y, context.z),
+			DensityFunction::EndIslands(noise) => (Self::end_island_height(noise, context.x / 8, context.z / 8) as f64 - 8.0) / 128.0,
+			DensityFunction::Noise { noise, xz_scale, y_scale } => {
+				noise.sample(context.x as f64 * xz_scale, context.y as f64 * y_scale, context.z as f64 * xz_scale)
+			},
+			DensityFunction::ShiftedNoise { noise, xz_scale, y_scale, shift_x, shift_y, shift_z } => {
+				let x = context.x as f64 * xz_scale + shift_x.compute(context);
+				let y = context.y as f64 * y_scale + shift_y.compute(context);
+				let z = context.z as f64 * xz_scale + shift_z.compute(context);
+				noise.sample(x, y, z)
+			},
+			DensityFunction::ShiftA(noise) => Self::shift(noise, context.x as f64, 0.0, context.z as f64),
+			DensityFunction::ShiftB(noise) => Self::shift(noise, context.z as f64, context.x as f64, 0.0),
+			DensityFunction::Shift(noise) => Self::shift(noise, context.x as f64, context.y as f64, context.z as f64),
+			DensityFunction::WeirdScaledSampler { input, noise, rarity_value_mapper } => {
+				let rarity = rarity_value_mapper.get(input.compute(context));
+				rarity * noise.sample(context.x as f64 / rarity, context.y as f64 / rarity, context.z as f64 / rarity).abs()
+			},
+			DensityFunction::RangeChoice { input, min_inclusive, max_exclusive, when_in_range, when_out_of_range } => {
+				let value = input.compute(context);
+				if value >= *min_inclusive && value < *max_exclusive {
+					when_in_range.compute(context)
+				} else {
+					when_out_of_range.compute(context)
+				}
+			},
+			DensityFunction::Clamp { input, min, max } => util::clamp(input.compute(context), *min, *max),
+			DensityFunction::Mapped(kind, input) => {
+				let d = input.compute(context);
+				match kind {
+					MappedType::Abs => d.abs(),
+					MappedType::Square => d * d,
+					MappedType::Cube => d * d * d,
+					MappedType::HalfNegative => if d > 0.0 { d } else { d * 0.5 },
+					MappedType::QuarterNegative => if d > 0.0 { d } else { d * 0.25 },
+					MappedType::Squeeze => {
+						let e = d.clamp(-1.0, 1.0);
+						e / 2.0 - e * e * e / 24.0
+					},
+				}
+			},
+			DensityFunction::Ap2(kind, argument1, argument2) => {
+				let a = argument1.compute(context);
+				match kind {
+					Ap2Type::Add => a + argument2.compute(context),
+					Ap2Type::Mul => if a == 0.0 { 0.0 } else { a * argument2.compute(context) },
+					Ap2Type::Min => a.min(argument2.compute(context)),
+					Ap2Type::Max => a.max(argument2.compute(context)),
+				}
+			},
+			DensityFunction::Spline(spline) => spline.apply(context) as f64,
+			DensityFunction::YClampedGradient { from_y, to_y, from_value, to_value } => {
+				util::clamped_map(context.y as f64, *from_y as f64, *to_y as f64, *from_value, *to_value)
+			},
+		}
+	}
+
+	/// Checks the invariants of a restored density function that parsing
+	/// JSON guarantees, so that computing it can't panic.
+	#[cfg(feature = "snapshot")]
+	pub(crate) fn check(&self) -> Result<(), String> {
+		match self {
+			DensityFunction::Constant(_)
+			| DensityFunction::BlendAlpha
+			| DensityFunction::BlendOffset
+			| DensityFunction::Beardifier
+			| DensityFunction::EndIslands(_)
+			| DensityFunction::Noise { .. }
+			| DensityFunction::ShiftA(_)
+			| DensityFunction::ShiftB(_)
+			| DensityFunction::Shift(_)
+			| DensityFunction::YClampedGradient { .. } => Ok(()),
+			DensityFunction::Reference(_, function) => function.check(),
+			DensityFunction::Marker(_, input)
+			| DensityFunction::BlendDensity(input)
+			| DensityFunction::Mapped(_, input)
+			| DensityFunction::WeirdScaledSampler { input, .. } => input.check(),
+			DensityFunction::OldBlendedNoise(noise) => noise.check(),
+			DensityFunction::ShiftedNoise { shift_x, shift_y, shift_z, .. } => {
+				shift_x.check()?;
+				shift_y.check()?;
+				shift_z.check()
+			},
+			DensityFunction::RangeChoice { input, min_inclusive, max_exclusive, when_in_range, when_out_of_range } => {
+				if !min_inclusive.is_finite() || !max_exclusive.is_finite() {
+					return Err("Snapshot contains a range choice with non-finite bounds".to_string())
+				}
+				input.check()?;
+				when_in_range.check()?;
+				when_out_of_range.check()
+			},
+			DensityFunction::Clamp { input, min, max } => {
+				if !min.is_finite() || !max.is_finite() {
+					return Err("Snapshot contains a clamp with non-finite bounds".to_string())
+				}
+				input.check()
+			},
+			DensityFunction::Ap2(_, argument1, argument2) => {
+				argument1.check()?;
+				argument2.check()
+			},
+			DensityFunction::Spline(spline) => spline.check(),
+		}
+	}
+
+	fn shift(noise: &NormalNoise, x: f64, y: f64, z: f64) -> f64 {
+		noise.sample(x * 0.25, y * 0.25, z * 0.25) * 4.0
+	}
+
+	/// The height of the end islands at a position in units of 8 blocks,
+	/// like vanilla's `EndIslandDensityFunction.getHeightValue`.
+	fn end_island_height(noise: &SimplexNoise, x: i32, z: i32) -> f32 {
+		let (cell_x, cell_z) = (x / 2, z / 2);
+		let (offset_x, offset_z) = (x % 2, z % 2);
+		let mut height = (100.0 - ((x.wrapping_mul(x).wrapping_add(z.wrapping_mul(z))) as f32).sqrt() * 8.0).clamp(-100.0, 80.0);
+		for i in -12..=12 {
+			for j in -12..=12 {
+				let island_x = (cell_x + i) as i64;
+				let island_z = (cell_z + j) as i64;
+				if island_x * island_x + island_z * island_z > 4096 && noise.sample_2d(island_x as f64, island_z as f64) < -0.9_f32 as f64 {
+					let falloff = ((island_x as f32).abs() * 3439.0 + (island_z as f32).abs() * 147.0) % 13.0 + 9.0;
+					let dx = (offset_x - i * 2) as f32;
+					let dz = (offset_z - j * 2) as f32;
+					let island = (100.0 - (dx * dx + dz * dz).sqrt() * falloff).clamp(-100.0, 80.0);
+					height = height.max(island);
+				}
+			}
+		}
+		height
+	}
+}
+
+/// Parses density functions from JSON, instantiating noises for a seed the
+/// same way vanilla's `RandomState` does.
+pub struct Loader<'a> {
+	registry: &'a Registry,
+	seed: i64,
+	legacy_random_source: bool,
+	random: Box<dyn PositionalRandomFactory>,
+	noises: HashMap<String, Arc<NormalNoise>>,
+	references: HashMap<String, Arc<DensityFunction>>,
+	/// The references that are being parsed, to detect cycles.
+	parsing: HashSet<String>,
+}
+
+impl<'a> Loader<'a> {
+	pub fn new(registry: &'a Registry, seed: i64, legacy_random_source: bool) -> Self {
+		let random = if legacy_random_source {
+			LegacyRandomSource::new(seed).fork_positional()
+		} else {
+			XoroshiroRandomSource::from(seed).fork_positional()
+		};
+		Self {
+			registry,
+			seed,
+			legacy_random_source,
+			random,
+			noises: HashMap::new(),
+			references: HashMap::new(),
+			parsing: HashSet::new(),
+		}
+	}
+
+	pub fn noise(&mut self, id: &str) -> Result<Arc<NormalNoise>, String> {
+		let id = normalize_id(id);
+		if let Some(noise) = self.noises.get(&id) {
+			return Ok(noise.clone())
+		}
+		let noise = Arc::new(match (self.legacy_random_source, id.as_str()) {
+			(true, "minecraft:temperature") => NormalNoise::new(&mut LegacyRandomSource::new(self.seed), &NoiseParameters::new(-7, &[1.0, 1.0])),
+			(true, "minecraft:vegetation") => NormalNoise::new(&mut LegacyRandomSource::new(self.seed.wrapping_add(1)), &NoiseParameters::new(-7, &[1.0, 1.0])),
+			(true, "minecraft:offset") => NormalNoise::create(self.random.from_hash_of(&id).as_mut(), &NoiseParameters::new(0, &[0.0])),
+			_ => {
+				let params = self.registry.noise(&id).ok_or_else(|| format!("Unknown noise {}", id))?;
+				NormalNoise::create(self.random.from_hash_of(&id).as_mut(), params)
+			},
+		});
+		self.noises.insert(id, noise.clone());
+		Ok(noise)
+	}
+
+	pub fn reference(&mut self, id: &str) -> Result<Arc<DensityFunction>, String> {
+		let id = normalize_id(id);
+		if let Some(function) = self.references.get(&id) {
+			return Ok(function.clone())
+		}
+		let json = self.registry.density_function(&id).ok_or_else(|| format!("Unknown density function {}", id))?;
+		if !self.parsing.insert(id.clone()) {
+			return Err(format!("Density function {} references itself", id))
+		}
+		let function = self.parse(json);
+		self.parsing.remove(&id);
+		let function = Arc::new(function?);
+		self.references.insert(id, function.clone());
+		Ok(function)
+	}
+
+	pub fn parse(&mut self, json: &Value) -> Result<DensityFunction, String> {
+		if let Some(value) = json.as_f64() {
+			return Ok(DensityFunction::Constant(value))
+		}
+		if let Some(id) = json.as_str() {
+			let function = self.reference(id)?;
+			return Ok(DensityFunction::Reference(normalize_id(id), function))
+		}
+		let kind = json.get("type").and_then(Value::as_str).ok_or("Density function needs a type")?;
+		let kind = normalize_id(kind);
+		let kind = kind.strip_prefix("minecraft:").unwrap_or(&kind);
+		Ok(match kind {
+			"constant" => DensityFunction::Constant(number(json, "argument")?),
+			"interpolated" => DensityFunction::Marker(MarkerType::Interpolated, self.argument(json, "argument")?),
+			"flat_cache" => DensityFunction::Marker(MarkerType::FlatCache, self.argument(json, "argument")?),
+			"cache_2d" => DensityFunction::Marker(MarkerType::Cache2D, self.argument(json, "argument")?),
+			"cache_once" => DensityFunction::Marker(MarkerType::CacheOnce, self.argument(json, "argument")?),
+			"cache_all_in_cell" => DensityFunction::Marker(MarkerType::CacheAllInCell, self.argument(json, "argument")?),
+			"blend_alpha" => DensityFunction::BlendAlpha,
+			"blend_offset" => DensityFunction::BlendOffset,
+			"blend_density" => DensityFunction::BlendDensity(self.argument(json, "argument")?),
+			"beardifier" => DensityFunction::Beardifier,
+			"end_islands" => {
+				let mut random = LegacyRandomSource::new(self.seed);
+				random.consume(17292);
+				DensityFunction::EndIslands(Arc::new(SimplexNoise::new(&mut random)))
+			},
+			"old_blended_noise" => {
+				let mut random: Box<dyn RandomSource> = if self.legacy_random_source {
+					Box::new(LegacyRandomSource::new(self.seed))
+				} else {
+					self.random.from_hash_of("minecraft:terrain")
+				};
+				DensityFunction::OldBlendedNoise(Arc::new(BlendedNoise::new(
+					random.as_mut(),
+					number(json, "xz_scale")?,
+					number(json, "y_scale")?,
+					number(json, "xz_factor")?,
+					number(json, "y_factor")?,
+					number(json, "smear_scale_multiplier")?,
+				)))
+			},
+			"noise" => DensityFunction::Noise {
+				noise: self.noise(string(json, "noise")?)?,
+				xz_scale: number(json, "xz_scale")?,
+				y_scale: number(json, "y_scale")?,
+			},
+			"shifted_noise" => DensityFunction::ShiftedNoise {
+				noise: self.noise(string(json, "noise")?)?,
+				xz_scale: number(json, "xz_scale")?,
+				y_scale: number(json, "y_scale")?,
+				shift_x: self.argument(json, "shift_x")?,
+				shift_y: self.argument(json, "shift_y")?,
+				shift_z: self.argument(json, "shift_z")?,
+			},
+			"shift_a" => DensityFunction::ShiftA(self.noise(string(json, "argument")?)?),
+			"shift_b" => DensityFunction::ShiftB(self.noise(string(json, "argument")?)?),
+			"shift" => DensityFunction::Shift(self.noise(string(json, "argument")?)?),
+			"weird_scaled_sampler" => DensityFunction::WeirdScaledSampler {
+				input: self.argument(json, "input")?,
+				noise: self.noise(string(json, "noise")?)?,
+				rarity_value_mapper: match string(json, "rarity_value_mapper")? {
+					"type_1" => RarityValueMapper::Tunnels,
+					"type_2" => RarityValueMapper::Caves,
+					other => return Err(format!("Unknown rarity value mapper {}", other)),
+				},
+			},
+			"range_choice" => DensityFunction::RangeChoice {
+				input: self.argument(json, "input")?,
+				min_inclusive: number(json, "min_inclusive")?,
+				max_exclusive: number(json, "max_exclusive")?,
+				when_in_range: self.argument(json, "when_in_range")?,
+				when_out_of_range: self.argument(json, "when_out_of_range")?,
+			},
+			"clamp" => DensityFunction::Clamp {
+				input: self.argument(json, "input")?,
+				min: number(json, "min")?,
+				max: number(json, "max")?,
+			},
+			"abs" => DensityFunction::Mapped(MappedType::Abs, self.argument(json, "argument")?),
+			"square" => DensityFunction::Mapped(MappedType::Square, self.argument(json, "argument")?),
+			"cube" => DensityFunction::Mapped(MappedType::Cube, self.argument(json, "argument")?),
+			"half_negative" => DensityFunction::Mapped(MappedType::HalfNegative, self.argument(json, "argument")?),
+			"quarter_negative" => DensityFunction::Mapped(MappedType::QuarterNegative, self.argument(json, "argument")?),
+			"squeeze" => DensityFunction::Mapped(MappedType::Squeeze, self.argument(json, "argument")?),
+			"add" => DensityFunction::Ap2(Ap2Type::Add, self.argument(json, "argument1")?, self.argument(json, "argument2")?),
+			"mul" => DensityFunction::Ap2(Ap2Type::Mul, self.argument(json, "argument1")?, self.argument(json, "argument2")?),
+			"min" => DensityFunction::Ap2(Ap2Type::Min, self.argument(json, "argument1")?, self.argument(json, "argument2")?),
+			"max" => DensityFunction::Ap2(Ap2Type::Max, self.argument(json, "argument1")?, self.argument(json, "argument2")?),
+			"spline" => DensityFunction::Spline(Box::new(CubicSpline::parse(json.get("spline").ok_or("Spline density function needs a spline")?, self)?)),
+			"y_clamped_gradient" => DensityFunction::YClampedGradient {
+				from_y: number(json, "from_y")? as i32,
+				to_y: number(json, "to_y")? as i32,
+				from_value: number(json, "from_value")?,
+				to_value: number(json, "to_value")?,
+			},
+			other => return Err(format!("Unknown density function type {}", other)),
+		})
+	}
+
+	fn argument(&mut self, json: &Value, key: &str) -> Result<Box<DensityFunction>, String> {
+		let argument = json.get(key).ok_or_else(|| format!("Density function needs {}", key))?;
+		Ok(Box::new(self.parse(argument)?))
+	}
+}
+
+fn number(json: &Value, key: &str) -> Result<f64, String> {
+	json.get(key).and_then(Value::as_f64).ok_or_else(|| format!("Density function needs a number {}", key))
+}
+
+fn string<'v>(json: &'v Value, key: &str) -> Result<&'v str, String> {
+	json.get(key).and_then(Value::as_str).ok_or_else(|| format!("Density function needs a string {}", key))
+}
@@ -0,0 +1,128 @@
+use std::sync::Arc;
+use serde_json::{ json, Value };
+use wasm_bindgen::prelude::*;
+use super::biome_source::{ self, BiomeSource, FixedBiomeSource };
+use super::climate::{ Biome, BiomeExplanation };
+use super::registry::{ normalize_id, Registry };
+use super::router::{ NoiseGeneratorSettings, NoiseRouter };
+use super::sampler::Sampler;
+
+/// A layer of blocks in a flat world.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlatLayer {
+	pub block: String,
+	pub height: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Generator {
+	Noise,
+	/// Layers of blocks from the bottom of the world up, with no noise.
+	Flat(Vec<FlatLayer>),
+}
+
+/// A seeded dimension, ready to sample terrain and biomes.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct Dimension {
+	seed: i64,
+	generator: Generator,
+	settings: NoiseGeneratorSettings,
+	router: NoiseRouter,
+	sampler: Sampler,
+	biome_source: Arc<dyn BiomeSource>,
+}
+
+impl Dimension {
+	/// Loads a dimension from the registry's `dimension` definitions.
+	pub fn load(registry: &Registry, id: &str, seed: i64) -> Result<Self, String> {
+		let json = registry.resource("dimension", id).ok_or_else(|| format!("Unknown dimension {}", normalize_id(id)))?;
+		Self::from_json(json, registry, seed)
+	}
+
+	pub fn from_json(json: &Value, registry: &Registry, seed: i64) -> Result<Self, String> {
+		let generator = json.get("generator").ok_or("Dimension needs a generator")?;
+		let kind = generator.get("type").and_then(Value::as_str).map(normalize_id).unwrap_or_else(|| "minecraft:noise".to_string());
+		match kind.as_str() {
+			"minecraft:noise" => Self::noise(generator, registry, seed),
+			"minecraft:flat" => Self::flat(generator.get("settings").ok_or("Flat generator needs settings")?, registry, seed),
+			kind => Err(format!("Unsupported generator {}", kind)),
+		}
+	}
+
+	fn noise(generator: &Value, registry: &Registry, seed: i64) -> Result<Self, String> {
+		let settings = match generator.get("settings") {
+			Some(Value::String(id)) => registry.noise_settings(id).ok_or_else(|| format!("Unknown noise settings {}", normalize_id(id)))?,
+			Some(settings) => settings,
+			None => return Err("Noise generator needs settings".to_string()),
+		};
+		let settings = NoiseGeneratorSettings::from_json(settings)?;
+		let biome_source = biome_source::from_json(generator.get("biome_source").ok_or("Generator needs a biome_source")?, registry)?;
+		Self::new(seed, Generator::Noise, settings, biome_source, registry)
+	}
+
+	/// Loads the settings of a flat generator. Flat worlds have no noise, so
+	/// every router output is zero.
+	fn flat(json: &Value, registry: &Registry, seed: i64) -> Result<Self, String> {
+		let layers = json.get("layers").and_then(Value::as_array).ok_or("Flat settings need layers")?.iter().map(|layer| {
+			Ok(FlatLayer {
+				block: normalize_id(layer.get("block").and_then(Value::as_str).ok_or("Flat layers need a block")?),
+				height: layer.get("height").and_then(Value::as_u64).ok_or("Flat layers need a height")? as u32,
+			})
+		}).collect::<Result<Vec<_>, String>>()?;
+		let biome = json.get("biome").and_then(Value::as_str).unwrap_or("minecraft:plains");
+		let settings = NoiseGeneratorSettings::from_json(&json!({
+			"sea_level": -63,
+			"noise": { "min_y": -64, "height": 384, "size_horizontal": 1, "size_vertical": 2 },
+			"noise_router": {},
+		}))?;
+		Self::new(seed, Generator::Flat(layers), settings, Arc::new(FixedBiomeSource::new(biome)), registry)
+	}
+
+	fn new(seed: i64, generator: Generator, settings: NoiseGeneratorSettings, biome_source: Arc<dyn BiomeSource>, registry: &Registry) -> Result<Self, String> {
+		let router = NoiseRouter::new(&settings, registry, seed)?;
+		let sampler = Sampler::new(&router);
+		Ok(Self { seed, generator, settings, router, sampler, biome_source })
+	}
+
+	pub fn seed(&self) -> i64 {
+		self.seed
+	}
+
+	pub fn generator(&self) -> &Generator {
+		&self.generator
+	}
+
+	pub fn settings(&self) -> &NoiseGeneratorSettings {
+		&self.settings
+	}
+
+	pub fn router(&self) -> &NoiseRouter {
+		&self.router
+	}
+
+	pub fn sampler(&self) -> &Sampler {
+		&self.sampler
+	}
+
+	pub fn biome_source(&self) -> &dyn BiomeSource {
+		self.biome_source.as_ref()
+	}
+
+	/// A shared handle to the biome source.
+	pub fn shared_biome_source(&self) -> Arc<dyn BiomeSource> {
+		self.biome_source.clone()
+	}
+
+	/// The biome at a block position.
+	pub fn biome(&self, x: i32, y: i32, z: i32) -> Biome {
+		self.biome_source.get_noise_biome(x >> 2, y >> 2, z >> 2, &self.sampler)
+	}
+
+	/// Explains the biome at a block position, with up to `count` runner-up
+	/// biomes. Returns `None` if the biomes don't depend on the climate.
+	pub fn explain(&self, x: i32, y: i32, z: i32, count: usize) -> Option<BiomeExplanation> {
+		let parameters = self.biome_source.parameters()?;
+		Some(parameters.explain(self.sampler.sample(x >> 2, y >> 2, z >> 2), count))
+	}
+}
@@ -0,0 +1,198 @@
+use wasm_bindgen::prelude::*;
+use super::simd::{ F64x4, LANES };
+
+/// Tolerance used when converting a range to a sample count, so that bounds
+/// which are a whole number of steps apart don't gain or lose a sample
+/// because of rounding errors.
+const EPSILON: f64 = 1e-9;
+
+/// The order in which the axes of a grid are iterated, from the outermost
+/// to the innermost loop. `ZYX` gives row-major images in the XZ plane.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AxisOrder {
+	XYZ,
+	XZY,
+	YXZ,
+	YZX,
+	ZXY,
+	ZYX,
+}
+
+impl AxisOrder {
+	/// The indices of the x, y and z axes, from outermost to innermost.
+	fn axes(&self) -> [usize; 3] {
+		match self {
+			AxisOrder::XYZ => [0, 1, 2],
+			AxisOrder::XZY => [0, 2, 1],
+			AxisOrder::YXZ => [1, 0, 2],
+			AxisOrder::YZX => [1, 2, 0],
+			AxisOrder::ZXY => [2, 0, 1],
+			AxisOrder::ZYX => [2, 1, 0],
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Axis {
+	from: f64,
+	step: f64,
+	count: usize,
+}
+
+impl Axis {
+	pub fn new(from: f64, step: f64, count: usize) -> Self {
+		Self { from, step, count }
+	}
+
+	/// An axis from `from` up to but not including `to`.
+	pub fn exclusive(from: f64, to: f64, step: f64) -> Result<Self, String> {
+		check_step(step)?;
+		let count = ((to - from) / step - EPSILON).ceil().max(0.0) as usize;
+		Ok(Self::new(from, step, count))
+	}
+
+	/// An axis from `from` up to and including `to`. An axis where `from`
+	/// and `to` are equal has a single sample.
+	pub fn inclusive(from: f64, to: f64, step: f64) -> Result<Self, String> {
+		check_step(step)?;
+		let steps = (to - from) / step + EPSILON;
+		let count = if steps < 0.0 { 0 } else { steps.floor() as usize + 1 };
+		Ok(Self::new(from, step, count))
+	}
+
+	pub fn single(value: f64) -> Self {
+		Self::new(value, 1.0, 1)
+	}
+
+	pub fn count(&self) -> usize {
+		self.count
+	}
+
+	pub fn get(&self, i: usize) -> f64 {
+		(i as f64) * self.step + self.from
+	}
+}
+
+fn check_step(step: f64) -> Result<(), String> {
+	if step > 0.0 {
+		Ok(())
+	} else {
+		Err(format!("Grid step {} must be positive", step))
+	}
+}
+
+/// Describes the sample positions of a grid and the order they are output in.
+///
+/// A flat grid samples a single y level and skips the y loop entirely.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Grid {
+	x: Axis,
+	y: Axis,
+	z: Axis,
+	order: AxisOrder,
+	flat: bool,
+}
+
+impl Grid {
+	pub fn new(x: Axis, y: Axis, z: Axis) -> Self {
+		Self { x, y, z, order: AxisOrder::XYZ, flat: false }
+	}
+
+	pub fn flat(x: Axis, y: f64, z: Axis) -> Self {
+		Self { x, y: Axis::single(y), z, order: AxisOrder::XZY, flat: true }
+	}
+
+	pub fn with_order(mut self, order: AxisOrder) -> Self {
+		self.order = order;
+		self
+	}
+
+	pub fn len(&self) -> usize {
+		self.x.count * self.y.count * self.z.count
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// The sample counts along the x, y and z axes.
+	pub fn shape(&self) -> [usize; 3] {
+		[self.x.count, self.y.count, self.z.count]
+	}
+
+	/// Returns the position of the sample at an output index.
+	pub fn position(&self, i: usize) -> (f64, f64, f64) {
+		let axes = [self.x, self.y, self.z];
+		let [outer, middle, inner] = self.order.axes();
+		let mut index = [0; 3];
+		index[inner] = i % axes[inner].count;
+		index[middle] = i / axes[inner].count % axes[middle].count;
+		index[outer] = i / (axes[inner].count * axes[middle].count);
+		(self.x.get(index[0]), self.y.get(index[1]), self.z.get(index[2]))
+	}
+
+	/// Calls `f` for every sample position, in output order.
+	pub fn for_each<F: FnMut(f64, f64, f64)>(&self, mut f: F) {
+		let axes = [self.x, self.y, self.z];
+		if self.flat {
+			let y = self.y.from;
+			let (outer, inner) = match self.order.axes() {
+				[0, _, _] | [1, 0, _] => (0, 2),
+				_ => (2, 0),
+			};
+			for a in 0..axes[outer].count {
+				for b in 0..axes[inner].count {
+					let (x, z) = if outer == 0 { (a, b) } else { (b, a) };
+					f(self.x.get(x), y, self.z.get(z));
+				}
+			}
+			return
+		}
+		let [outer, middle, inner] = self.order.axes();
+		let mut index = [0; 3];
+		for a in 0..axes[outer].count {
+			index[outer] = a;
+			for b in 0..axes[middle].count {
+				index[middle] = b;
+				for c in 0..axes[inner].count {
+					index[inner] = c;
+					f(self.x.get(index[0]), self.y.get(index[1]), self.z.get(index[2]));
+				}
+			}
+		}
+	}
+
+	pub fn map<T, F: Fn(f64, f64, f64) -> T>(&self, f: F) -> Vec<T> {
+		let mut result = Vec::with_capacity(self.len());
+		self.for_each(|x, y, z| result.push(f(x, y, z)));
+		result
+	}
+
+	/// Like [`map`](Self::map), but calls `f` with [`LANES`] positions at a
+	/// time. The last batch is padded by repeating its final position.
+	pub fn map_lanes<F: Fn(F64x4, F64x4, F64x4) -> F64x4>(&self, f: F) -> Vec<f64> {
+		let mut result = Vec::with_capacity(self.len());
+		let mut batch = [[0.0; LANES]; 3];
+		let mut count = 0;
+		self.for_each(|x, y, z| {
+			batch[0][count] = x;
+			batch[1][count] = y;
+			batch[2][count] = z;
+			count += 1;
+			if count == LANES {
+				result.extend_from_slice(&f(F64x4(batch[0]), F64x4(batch[1]), F64x4(batch[2])).0);
+				count = 0;
+			}
+		});
+		if count > 0 {
+			for axis in batch.iter_mut() {
+				let last = axis[count - 1];
+				axis[count..].fill(last);
+			}
+			result.extend_from_slice(&f(F64x4(batch[0]), F64x4(batch[1]), F64x4(batch[2])).0[..count]);
+		}
+		result
+	}
+}
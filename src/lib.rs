@@ -1,53 +1,80 @@
+#![allow(clippy::too_many_arguments)]
+
+use std::sync::Arc;
 use wasm_bindgen::prelude::*;
+use wasm_bindgen::Clamped;
+#[cfg(feature = "snapshot")]
+use snapshot::Snapshot;
 
-mod random;
-mod noise;
-mod climate;
+pub mod random;
+pub mod noise;
+pub mod climate;
+mod blender;
+pub mod density;
+pub mod registry;
+pub mod datapack;
+pub mod dimension;
+pub mod world_preset;
+mod biome_source;
+mod router;
 mod sampler;
+mod spline;
+mod tile;
+pub mod render;
+pub mod stats;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
+pub mod grid;
+mod simd;
 mod util;
+#[cfg(any(feature = "vanilla-1-18", feature = "vanilla-1-18-2", feature = "vanilla-1-19", feature = "vanilla-1-20"))]
+pub mod vanilla;
+#[cfg(all(feature = "rayon", not(target_arch = "wasm32")))]
+pub mod parallel;
 
 #[cfg(test)]
 mod test;
 
-fn iterate_grid<F, T>(f: F, x_from: f64, x_to: f64, x_step: f64, y_from: f64, y_to: f64, y_step: f64, z_from: f64, z_to: f64, z_step: f64) -> Vec<T> where F: Fn(f64, f64, f64) -> T {
-  let x_count = ((x_to - x_from) / x_step).floor() as usize;
-  let y_count = ((y_to - y_from) / y_step).floor() as usize;
-  let z_count = ((z_to - z_from) / z_step).floor() as usize;
-  let mut result = Vec::with_capacity(x_count * y_count * z_count);
-  for x in 0..x_count {
-    for y in 0..y_count {
-      for z in 0..z_count {
-        let xx = (x as f64) * x_step + x_from;
-        let yy = (y as f64) * y_step + y_from;
-        let zz = (z as f64) * z_step + z_from;
-        result.push(f(xx, yy, zz));
-      }
-    }
-  }
-  result
+#[wasm_bindgen]
+pub fn grid_range(x_from: f64, x_to: f64, x_step: f64, y_from: f64, y_to: f64, y_step: f64, z_from: f64, z_to: f64, z_step: f64, inclusive: bool, order: grid::AxisOrder) -> Result<grid::Grid, JsValue> {
+  let axis = if inclusive { grid::Axis::inclusive } else { grid::Axis::exclusive };
+  Ok(grid::Grid::new(axis(x_from, x_to, x_step)?, axis(y_from, y_to, y_step)?, axis(z_from, z_to, z_step)?).with_order(order))
+}
+
+#[wasm_bindgen]
+pub fn grid_count(x_from: f64, x_step: f64, x_count: usize, y_from: f64, y_step: f64, y_count: usize, z_from: f64, z_step: f64, z_count: usize, order: grid::AxisOrder) -> grid::Grid {
+  grid::Grid::new(grid::Axis::new(x_from, x_step, x_count), grid::Axis::new(y_from, y_step, y_count), grid::Axis::new(z_from, z_step, z_count)).with_order(order)
 }
 
 #[wasm_bindgen]
-pub fn improved_noise(seed: i64, x_from: f64, x_to: f64, x_step: f64, y_from: f64, y_to: f64, y_step: f64, z_from: f64, z_to: f64, z_step: f64) -> Vec<f64> {
-  let mut random = random::LegacyRandomSource::new(seed);
-  let noise = noise::ImprovedNoise::new(&mut random);
-  iterate_grid(|x, y, z| noise.sample(x, y, z, 0.0, 0.0), x_from, x_to, x_step, y_from, y_to, y_step, z_from, z_to, z_step)
+pub fn flat_grid_range(x_from: f64, x_to: f64, x_step: f64, y: f64, z_from: f64, z_to: f64, z_step: f64, inclusive: bool, order: grid::AxisOrder) -> Result<grid::Grid, JsValue> {
+  let axis = if inclusive { grid::Axis::inclusive } else { grid::Axis::exclusive };
+  Ok(grid::Grid::flat(axis(x_from, x_to, x_step)?, y, axis(z_from, z_to, z_step)?).with_order(order))
 }
 
 #[wasm_bindgen]
-pub fn perlin_noise(seed: i64, first_octave: i32, amplitudes: &[f64], x_from: f64, x_to: f64, x_step: f64, y_from: f64, y_to: f64, y_step: f64, z_from: f64, z_to: f64, z_step: f64) -> Vec<f64> {
-  let mut random = random::LegacyRandomSource::new(seed);
-  let params = noise::NoiseParameters::new(first_octave, amplitudes);
-  let noise = noise::PerlinNoise::new(&mut random, &params);
-  iterate_grid(|x, y, z| noise.sample(x, y, z, 0.0, 0.0, false), x_from, x_to, x_step, y_from, y_to, y_step, z_from, z_to, z_step)
+pub fn flat_grid_count(x_from: f64, x_step: f64, x_count: usize, y: f64, z_from: f64, z_step: f64, z_count: usize, order: grid::AxisOrder) -> grid::Grid {
+  grid::Grid::flat(grid::Axis::new(x_from, x_step, x_count), y, grid::Axis::new(z_from, z_step, z_count)).with_order(order)
 }
 
 #[wasm_bindgen]
-pub fn normal_noise(seed: i64, first_octave: i32, amplitudes: &[f64], x_from: f64, x_to: f64, x_step: f64, y_from: f64, y_to: f64, y_step: f64, z_from: f64, z_to: f64, z_step: f64) -> Vec<f64> {
-  let mut random = random::LegacyRandomSource::new(seed);
-  let params = noise::NoiseParameters::new(first_octave, amplitudes);
-  let noise = noise::NormalNoise::new(&mut random, &params);
-  iterate_grid(|x, y, z| noise.sample(x, y, z), x_from, x_to, x_step, y_from, y_to, y_step, z_from, z_to, z_step)
+pub fn grid_len(grid: &grid::Grid) -> usize {
+  grid.len()
+}
+
+#[wasm_bindgen]
+pub fn improved_noise(seed: i64, grid: &grid::Grid) -> Vec<f64> {
+  noise::ImprovedNoise::from_seed(seed).sample_grid(grid)
+}
+
+#[wasm_bindgen]
+pub fn perlin_noise(seed: i64, first_octave: i32, amplitudes: &[f64], grid: &grid::Grid) -> Vec<f64> {
+  noise::PerlinNoise::from_seed(seed, first_octave, amplitudes).sample_grid(grid)
+}
+
+#[wasm_bindgen]
+pub fn normal_noise(seed: i64, first_octave: i32, amplitudes: &[f64], grid: &grid::Grid) -> Vec<f64> {
+  noise::NormalNoise::from_seed(seed, first_octave, amplitudes).sample_grid(grid)
 }
 
 #[wasm_bindgen]
@@ -73,17 +100,199 @@ pub fn noise_parameters(first_octave: i32, amplitudes: Vec<f64>) -> noise::Noise
   noise::NoiseParameters::new(first_octave, amplitudes.as_slice())
 }
 
+/// Parses a seed like the world creation screen. Empty text has no seed.
+#[wasm_bindgen]
+pub fn parse_seed(text: &str) -> Option<i64> {
+  random::parse_seed(text)
+}
+
+#[wasm_bindgen]
+pub fn obfuscate_seed(seed: i64) -> i64 {
+  random::obfuscate_seed(seed)
+}
+
+/// The seed that features in a chunk are placed with, given the chunk's
+/// minimum block position.
+#[wasm_bindgen]
+pub fn decoration_seed(seed: i64, min_block_x: i32, min_block_z: i32) -> i64 {
+  random::LegacyRandomSource::new(0).set_decoration_seed(seed, min_block_x, min_block_z)
+}
+
+#[wasm_bindgen]
+pub fn noise_router(seed: i64, settings: &str, noises: &str, density_functions: &str) -> Result<router::NoiseRouter, JsValue> {
+  let parse = |json: &str| serde_json::from_str::<serde_json::Value>(json).map_err(|e| e.to_string());
+  let registry = registry::Registry::from_json(&parse(noises)?, &parse(density_functions)?)?;
+  let settings = router::NoiseGeneratorSettings::from_json(&parse(settings)?)?;
+  Ok(router::NoiseRouter::new(&settings, &registry, seed)?)
+}
+
+#[wasm_bindgen]
+pub fn router_noise(router: &router::NoiseRouter, name: &str, grid: &grid::Grid) -> Result<Vec<f64>, JsValue> {
+  let function = router.get(name).ok_or_else(|| format!("Unknown router output {}", name))?;
+  Ok(grid.map(|x, y, z| {
+    function.compute(&density::Context::new(x as i32, y as i32, z as i32))
+  }))
+}
+
+#[wasm_bindgen]
+pub fn blender(chunk_x: i32, chunk_z: i32, old_chunks: &str) -> Result<blender::Blender, JsValue> {
+  let json = serde_json::from_str::<serde_json::Value>(old_chunks).map_err(|e| e.to_string())?;
+  Ok(blender::Blender::from_json(chunk_x, chunk_z, &json)?)
+}
+
+#[wasm_bindgen]
+pub fn blended_router_noise(router: &router::NoiseRouter, name: &str, blender: &blender::Blender, grid: &grid::Grid) -> Result<Vec<f64>, JsValue> {
+  let function = router.get(name).ok_or_else(|| format!("Unknown router output {}", name))?;
+  Ok(grid.map(|x, y, z| {
+    function.compute(&density::Context::with_blender(x as i32, y as i32, z as i32, blender))
+  }))
+}
+
+#[cfg(any(feature = "vanilla-1-18", feature = "vanilla-1-18-2", feature = "vanilla-1-19", feature = "vanilla-1-20"))]
+fn vanilla_version(name: &str) -> Result<vanilla::Version, String> {
+  vanilla::Version::from_name(name).ok_or_else(|| format!("No bundled vanilla data for version {}", name))
+}
+
+/// The names of the game versions with bundled vanilla data.
+#[cfg(any(feature = "vanilla-1-18", feature = "vanilla-1-18-2", feature = "vanilla-1-19", feature = "vanilla-1-20"))]
+#[wasm_bindgen]
+pub fn vanilla_versions() -> Vec<String> {
+  vanilla::Version::ALL.iter().map(|v| v.name().to_string()).collect()
+}
+
+#[cfg(any(feature = "vanilla-1-18", feature = "vanilla-1-18-2", feature = "vanilla-1-19", feature = "vanilla-1-20"))]
+#[wasm_bindgen]
+pub fn vanilla_noise_parameters(version: &str, id: &str) -> Result<noise::NoiseParameters, JsValue> {
+  let registry = vanilla_version(version)?.registry();
+  Ok(registry.noise(id).cloned().ok_or_else(|| format!("Unknown noise {}", id))?)
+}
+
+/// Loads a dimension from the bundled vanilla data of a game version like
+/// `1.19.4`, with datapack files on top. The files are given as a JSON object
+/// mapping paths like `data/minecraft/dimension/overworld.json` to their
+/// content, and only need to contain what they change.
+#[cfg(any(feature = "vanilla-1-18", feature = "vanilla-1-18-2", feature = "vanilla-1-19", feature = "vanilla-1-20"))]
+#[wasm_bindgen]
+pub fn vanilla_dimension(seed: i64, version: &str, files: &str, dimension: &str) -> Result<dimension::Dimension, JsValue> {
+  let files = serde_json::from_str::<serde_json::Value>(files).map_err(|e| e.to_string())?;
+  let mut registry = vanilla_version(version)?.registry();
+  datapack::Datapack::from_json(&files)?.apply(&mut registry)?;
+  Ok(dimension::Dimension::load(&registry, dimension, seed)?)
+}
+
+/// Loads a dimension of a world preset like `minecraft:large_biomes`, from
+/// the bundled vanilla data with the datapack files on top.
+#[cfg(any(feature = "vanilla-1-18", feature = "vanilla-1-18-2", feature = "vanilla-1-19", feature = "vanilla-1-20"))]
+#[wasm_bindgen]
+pub fn preset_dimension(seed: i64, version: &str, files: &str, preset: &str, dimension: &str) -> Result<dimension::Dimension, JsValue> {
+  let files = serde_json::from_str::<serde_json::Value>(files).map_err(|e| e.to_string())?;
+  let mut registry = vanilla_version(version)?.registry();
+  datapack::Datapack::from_json(&files)?.apply(&mut registry)?;
+  Ok(world_preset::WorldPreset::load(&registry, preset)?.dimension(dimension, &registry, seed)?)
+}
+
+#[wasm_bindgen]
+pub fn dimension_router(dimension: &dimension::Dimension) -> router::NoiseRouter {
+  dimension.router().clone()
+}
+
+#[wasm_bindgen]
+pub fn dimension_sampler(dimension: &dimension::Dimension) -> sampler::Sampler {
+  dimension.sampler().clone()
+}
+
+#[wasm_bindgen]
+pub fn dimension_parameters(dimension: &dimension::Dimension) -> Result<climate::ParameterList, JsValue> {
+  Ok(dimension.biome_source().parameters().ok_or("The biomes of this dimension don't depend on the climate")?.clone())
+}
+
+#[wasm_bindgen]
+pub fn dimension_biomes(dimension: &dimension::Dimension) -> Vec<String> {
+  dimension.biome_source().possible_biomes().to_vec()
+}
+
+#[wasm_bindgen]
+pub fn dimension_biome_source(dimension: &dimension::Dimension) -> biome_source::DynBiomeSource {
+  biome_source::DynBiomeSource::new(dimension.shared_biome_source())
+}
+
+#[wasm_bindgen]
+pub fn multi_noise_biome_source(parameters: &climate::ParameterList, biomes: Vec<String>) -> biome_source::DynBiomeSource {
+  biome_source::DynBiomeSource::new(Arc::new(biome_source::MultiNoiseBiomeSource::new(parameters.clone(), biomes)))
+}
+
+#[wasm_bindgen]
+pub fn fixed_biome_source(biome: &str) -> biome_source::DynBiomeSource {
+  biome_source::DynBiomeSource::new(Arc::new(biome_source::FixedBiomeSource::new(biome)))
+}
+
+#[wasm_bindgen]
+pub fn checkerboard_biome_source(biomes: Vec<String>, scale: i32) -> Result<biome_source::DynBiomeSource, JsValue> {
+  let biomes = biomes.iter().map(|b| registry::normalize_id(b)).collect();
+  Ok(biome_source::DynBiomeSource::new(Arc::new(biome_source::CheckerboardBiomeSource::new(biomes, scale)?)))
+}
+
+/// The end's biome source, which needs a sampler from the end noise settings.
+#[wasm_bindgen]
+pub fn end_biome_source() -> biome_source::DynBiomeSource {
+  biome_source::DynBiomeSource::new(Arc::new(biome_source::TheEndBiomeSource::new()))
+}
+
+#[wasm_bindgen]
+pub fn biome_source_biomes(source: &biome_source::DynBiomeSource) -> Vec<String> {
+  source.get().possible_biomes().to_vec()
+}
+
+/// The biome at each block position of the grid, as indices into
+/// `biome_source_biomes`.
+#[wasm_bindgen]
+pub fn biome_source_noise(source: &biome_source::DynBiomeSource, sampler: &sampler::Sampler, grid: &grid::Grid) -> Vec<i32> {
+  let source = source.get();
+  grid.map(|x, y, z| source.get_noise_biome(x as i32 >> 2, y as i32 >> 2, z as i32 >> 2, sampler))
+}
+
 #[wasm_bindgen]
-pub fn climate_sampler(seed: i64, t_first: i32, t_amplitudes: Vec<f64>, h_first: i32, h_amplitudes: Vec<f64>, c_first: i32, c_amplitudes: Vec<f64>, e_first: i32, e_amplitudes: Vec<f64>, w_first: i32, w_amplitudes: Vec<f64>, s_first: i32, s_amplitudes: Vec<f64>) -> sampler::Sampler {
-  let octaves = sampler::NoiseOctaves::new(
-    noise::NoiseParameters::new(t_first, t_amplitudes.as_slice()),
-    noise::NoiseParameters::new(h_first, h_amplitudes.as_slice()),
-    noise::NoiseParameters::new(c_first, c_amplitudes.as_slice()),
-    noise::NoiseParameters::new(e_first, e_amplitudes.as_slice()),
-    noise::NoiseParameters::new(w_first, w_amplitudes.as_slice()),
-    noise::NoiseParameters::new(s_first, s_amplitudes.as_slice()),
-  );
-  sampler::Sampler::new(seed, &octaves)
+pub fn biome_source_tile(source: &biome_source::DynBiomeSource, sampler: &sampler::Sampler, layout: &tile::TileLayout, index: usize, out: &mut [i32]) -> Result<(), JsValue> {
+  let source = source.get();
+  Ok(layout.fill(index, out, |x, y, z| source.get_noise_biome(x as i32 >> 2, y as i32 >> 2, z as i32 >> 2, sampler))?)
+}
+
+#[wasm_bindgen]
+pub fn climate_sampler(router: &router::NoiseRouter) -> sampler::Sampler {
+  sampler::Sampler::new(router)
+}
+
+/// Saves a sampler, so that a worker can restore it without seeding.
+#[cfg(feature = "snapshot")]
+#[wasm_bindgen]
+pub fn sampler_snapshot(sampler: &sampler::Sampler) -> Result<Vec<u8>, JsValue> {
+  Ok(sampler.to_snapshot()?)
+}
+
+#[cfg(feature = "snapshot")]
+#[wasm_bindgen]
+pub fn sampler_from_snapshot(snapshot: &[u8]) -> Result<sampler::Sampler, JsValue> {
+  Ok(sampler::Sampler::from_snapshot(snapshot)?)
+}
+
+/// Saves a built parameter list, so that a worker can restore it without
+/// building its tree again.
+#[cfg(feature = "snapshot")]
+#[wasm_bindgen]
+pub fn parameters_snapshot(parameters: &climate::ParameterList) -> Result<Vec<u8>, JsValue> {
+  Ok(parameters.to_snapshot()?)
+}
+
+#[cfg(feature = "snapshot")]
+#[wasm_bindgen]
+pub fn parameters_from_snapshot(snapshot: &[u8]) -> Result<climate::ParameterList, JsValue> {
+  Ok(climate::ParameterList::from_snapshot(snapshot)?)
+}
+
+/// A copy of a parameter list that searches for entries in another way.
+#[wasm_bindgen]
+pub fn parameters_search_mode(parameters: &climate::ParameterList, mode: climate::SearchMode) -> climate::ParameterList {
+  parameters.clone().with_search_mode(mode)
 }
 
 #[wasm_bindgen]
@@ -92,22 +301,151 @@ pub fn find_biome(parameters: &climate::ParameterList, target: Vec<f64>) -> i32
   parameters.find(climate::target(target[0], target[1], target[2], target[3], target[4], target[5]))
 }
 
+/// Explains the biome chosen for a target of temperature, humidity,
+/// continentalness, erosion, weirdness and depth.
 #[wasm_bindgen]
-pub fn multi_noise(parameters: &climate::ParameterList, sampler: &sampler::Sampler, x_from: f64, x_to: f64, x_step: f64, y_from: f64, y_to: f64, y_step: f64, z_from: f64, z_to: f64, z_step: f64) -> Vec<i32> {
-  iterate_grid(|x, y, z| {
-    let target = sampler.target(x as i64, y as i64, z as i64);
+pub fn explain_biome(parameters: &climate::ParameterList, target: Vec<f64>, count: usize) -> Result<climate::BiomeExplanation, JsValue> {
+  match target.as_slice() {
+    &[t, h, c, e, w, d] => Ok(parameters.explain(climate::target(t, h, c, e, w, d), count)),
+    _ => Err(format!("Target needs 6 values, got {}", target.len()).into()),
+  }
+}
+
+#[wasm_bindgen]
+pub fn explain_dimension_biome(dimension: &dimension::Dimension, x: i32, y: i32, z: i32, count: usize) -> Result<climate::BiomeExplanation, JsValue> {
+  Ok(dimension.explain(x, y, z, count).ok_or("The biomes of this dimension don't depend on the climate")?)
+}
+
+/// Looks for unreachable biomes and shadowed entries of a parameter list.
+#[wasm_bindgen]
+pub fn analyze_parameters(parameters: &climate::ParameterList, samples: usize, seed: i64) -> climate::ParameterAnalysis {
+  parameters.analyze(samples, seed)
+}
+
+/// A square region of block positions to collect biome statistics over.
+#[wasm_bindgen]
+pub fn stats_region(center_x: i32, center_z: i32, radius: i32, y: i32, stride: i32) -> Result<stats::Region, JsValue> {
+  Ok(stats::Region::new(center_x, center_z, radius, y, stride)?)
+}
+
+#[wasm_bindgen]
+pub fn biome_stats(source: &biome_source::DynBiomeSource, sampler: &sampler::Sampler, region: &stats::Region) -> stats::BiomeStats {
+  stats::BiomeStats::collect(source.get(), sampler, region)
+}
+
+/// Collects biome statistics of a dimension for every seed from `from_seed`
+/// up to but not including `to_seed`, from the bundled vanilla data of a
+/// game version with the datapack files on top, given like in `vanilla_dimension`.
+#[cfg(any(feature = "vanilla-1-18", feature = "vanilla-1-18-2", feature = "vanilla-1-19", feature = "vanilla-1-20"))]
+#[wasm_bindgen]
+pub fn datapack_biome_stats(version: &str, files: &str, dimension: &str, from_seed: i64, to_seed: i64, region: &stats::Region) -> Result<stats::BiomeStats, JsValue> {
+  let files = serde_json::from_str::<serde_json::Value>(files).map_err(|e| e.to_string())?;
+  let mut registry = vanilla_version(version)?.registry();
+  datapack::Datapack::from_json(&files)?.apply(&mut registry)?;
+  Ok(stats::BiomeStats::over_seeds(region, from_seed..to_seed, |seed| dimension::Dimension::load(&registry, dimension, seed))?)
+}
+
+#[wasm_bindgen]
+pub fn multi_noise(parameters: &climate::ParameterList, sampler: &sampler::Sampler, grid: &grid::Grid) -> Vec<i32> {
+  grid.map(|x, y, z| {
+    let target = sampler.target(x as i32, y as i32, z as i32);
     parameters.find(target)
-  }, x_from, x_to, x_step, y_from, y_to, y_step, z_from, z_to, z_step)
+  })
 }
 
 #[wasm_bindgen]
-pub fn climate_noise(sampler: &sampler::Sampler, x_from: f64, x_to: f64, x_step: f64, y_from: f64, y_to: f64, y_step: f64, z_from: f64, z_to: f64, z_step: f64) -> Vec<f64> {
-  iterate_grid(|x, y, z| {
-    sampler.target(x as i64, y as i64, z as i64).vec()
-  }, x_from, x_to, x_step, y_from, y_to, y_step, z_from, z_to, z_step)
+pub fn blended_multi_noise(parameters: &climate::ParameterList, sampler: &sampler::Sampler, blender: &blender::Blender, grid: &grid::Grid) -> Vec<i32> {
+  grid.map(|x, y, z| {
+    let (x, y, z) = (x as i32, y as i32, z as i32);
+    blender.resolve_biome(x >> 2, y >> 2, z >> 2, || parameters.find(sampler.target(x, y, z)))
+  })
+}
+
+#[wasm_bindgen]
+pub fn climate_noise(sampler: &sampler::Sampler, grid: &grid::Grid) -> Vec<f64> {
+  grid.map(|x, y, z| {
+    sampler.target(x as i32, y as i32, z as i32).vec()
+  })
     .into_iter().flatten().collect::<Vec<_>>()
 }
 
+#[wasm_bindgen]
+pub fn tile_layout(x_from: f64, z_from: f64, y: f64, step: f64, size: usize, tiles_x: usize, tiles_z: usize) -> Result<tile::TileLayout, JsValue> {
+  Ok(tile::TileLayout::new(x_from, z_from, y, step, size, tiles_x, tiles_z)?)
+}
+
+#[wasm_bindgen]
+pub fn tile_count(layout: &tile::TileLayout) -> usize {
+  layout.tile_count()
+}
+
+#[wasm_bindgen]
+pub fn multi_noise_tile(parameters: &climate::ParameterList, sampler: &sampler::Sampler, layout: &tile::TileLayout, index: usize, out: &mut [i32]) -> Result<(), JsValue> {
+  Ok(layout.fill(index, out, |x, y, z| parameters.find(sampler.target(x as i32, y as i32, z as i32)))?)
+}
+
+#[wasm_bindgen]
+pub fn climate_noise_tile(sampler: &sampler::Sampler, layout: &tile::TileLayout, index: usize, out: &mut [f64]) -> Result<(), JsValue> {
+  Ok(layout.fill_chunks(index, out, |x, y, z| sampler.target(x as i32, y as i32, z as i32).values())?)
+}
+
+#[wasm_bindgen]
+pub fn router_noise_tile(router: &router::NoiseRouter, name: &str, layout: &tile::TileLayout, index: usize, out: &mut [f64]) -> Result<(), JsValue> {
+  let function = router.get(name).ok_or_else(|| format!("Unknown router output {}", name))?;
+  Ok(layout.fill(index, out, |x, y, z| function.compute(&density::Context::new(x as i32, y as i32, z as i32)))?)
+}
+
+#[wasm_bindgen]
+pub fn biome_palette(names: &str) -> Result<render::Palette, JsValue> {
+  let names = serde_json::from_str::<Vec<String>>(names).map_err(|e| e.to_string())?;
+  Ok(render::Palette::from_names(&names))
+}
+
+#[wasm_bindgen]
+pub fn set_biome_color(palette: &mut render::Palette, biome: i32, r: u8, g: u8, b: u8) {
+  palette.set(biome, [r, g, b])
+}
+
+#[wasm_bindgen]
+pub fn render_biome_map(biomes: &[i32], palette: &render::Palette) -> Clamped<Vec<u8>> {
+  Clamped(render::render_biomes(biomes, palette))
+}
+
+#[wasm_bindgen]
+pub fn render_shaded_biome_map(biomes: &[i32], heights: &[f64], width: usize, spacing: f64, palette: &render::Palette) -> Result<Clamped<Vec<u8>>, JsValue> {
+  let mut rgba = render::render_biomes(biomes, palette);
+  render::hillshade(&mut rgba, heights, width, spacing)?;
+  Ok(Clamped(rgba))
+}
+
+#[wasm_bindgen]
+pub fn render_slope_shaded_biome_map(biomes: &[i32], slopes: &[f64], palette: &render::Palette) -> Result<Clamped<Vec<u8>>, JsValue> {
+  let mut rgba = render::render_biomes(biomes, palette);
+  render::shade_slopes(&mut rgba, slopes)?;
+  Ok(Clamped(rgba))
+}
+
+#[wasm_bindgen]
+pub fn noise_heatmap(values: &[f64], ramp: render::ColorRamp, min: Option<f64>, max: Option<f64>, bins: usize) -> render::Heatmap {
+  render::Heatmap::new(values, ramp, min, max, bins)
+}
+
+#[wasm_bindgen]
+pub fn noise_stats(values: &[f64], bins: usize, min: Option<f64>, max: Option<f64>) -> render::NoiseStats {
+  render::NoiseStats::new(values, bins, min, max)
+}
+
+#[wasm_bindgen]
+pub fn grid_channel(values: &[f64], stride: usize, index: usize) -> Result<Vec<f64>, JsValue> {
+  Ok(render::channel(values, stride, index)?)
+}
+
+#[cfg(feature = "png")]
+#[wasm_bindgen]
+pub fn encode_png(rgba: &[u8], width: u32, height: u32) -> Result<Vec<u8>, JsValue> {
+  Ok(render::encode_png(rgba, width, height)?)
+}
+
 #[wasm_bindgen]
 pub struct Test {
   x: i32,
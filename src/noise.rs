@@ -1,9 +1,11 @@
 use wasm_bindgen::prelude::*;
+use serde_json::Value;
 use super::util;
-use super::random::RandomSource;
+use super::simd::{ self, F64x4, LANES };
+use super::random::{ LegacyRandomSource, RandomSource };
 
 #[wasm_bindgen]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct NoiseParameters {
 	first_octave: i32,
 	amplitudes: Vec<f64>,
@@ -16,14 +18,26 @@ impl NoiseParameters {
 			amplitudes: Vec::from(amplitudes)
 		}
 	}
+
+	pub fn from_json(json: &Value) -> Result<Self, String> {
+		let first_octave = json.get("firstOctave").and_then(Value::as_i64)
+			.ok_or("Noise parameters need a firstOctave")?;
+		let amplitudes = json.get("amplitudes").and_then(Value::as_array)
+			.ok_or("Noise parameters need amplitudes")?
+			.iter().map(|a| a.as_f64().ok_or("Amplitudes must be numbers"))
+			.collect::<Result<Vec<_>, _>>()?;
+		Ok(Self::new(first_octave as i32, &amplitudes))
+	}
 }
 
 #[wasm_bindgen]
 #[derive(Clone)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
 pub struct ImprovedNoise {
 	xo: f64,
 	yo: f64,
 	zo: f64,
+	#[cfg_attr(feature = "snapshot", serde(with = "super::snapshot::permutation"))]
 	p: [u8; 256]
 }
 
@@ -33,19 +47,57 @@ impl ImprovedNoise {
 		let yo = random.next_double() * 256.0;
 		let zo = random.next_double() * 256.0;
 		let mut p: [u8; 256] = [0; 256];
-		for i in 0..256 {
-			p[i] = i as u8
+		for (i, v) in p.iter_mut().enumerate() {
+			*v = i as u8
 		}
-		for i in 0..256 as usize {
+		for i in 0..256 {
 			let j = random.next_int_max(256 - i as i32) as usize;
-			let temp = p[i];
-			p[i] = p[i + j];
-			p[i + j] = temp;
+			p.swap(i, i + j);
 		}
 		Self { xo, yo, zo, p }
 	}
 
 	pub fn sample(&self, x: f64, y: f64, z: f64, y_scale: f64, y_limit: f64) -> f64 {
+		let (a, b, c, d, e, f, g) = self.cell(x, y, z, y_scale, y_limit);
+		self.sample_and_lerp(a, b, c, d, e, f, g)
+	}
+
+	/// Samples the noise together with its gradient, the partial derivatives
+	/// along x, y and z. The value is the same as [`sample`](Self::sample).
+	/// With a y scale the noise has steps along y, which the gradient ignores.
+	pub fn sample_gradient(&self, x: f64, y: f64, z: f64, y_scale: f64, y_limit: f64) -> (f64, [f64; 3]) {
+		let (a, b, c, d, e, f, g) = self.cell(x, y, z, y_scale, y_limit);
+		let [g0, g1, g2, g3, g4, g5, g6, g7] = self.gradients(a, b, c);
+		let dot = |grad: (f64, f64, f64), x: f64, y: f64, z: f64| grad.0 * x + grad.1 * y + grad.2 * z;
+
+		let n = dot(g0, d, e, f);
+		let o = dot(g1, d - 1.0, e, f);
+		let p = dot(g2, d, e - 1.0, f);
+		let q = dot(g3, d - 1.0, e - 1.0, f);
+		let r = dot(g4, d, e, f - 1.0);
+		let s = dot(g5, d - 1.0, e, f - 1.0);
+		let t = dot(g6, d, e - 1.0, f - 1.0);
+		let u = dot(g7, d - 1.0, e - 1.0, f - 1.0);
+
+		let v = util::smoothstep(d);
+		let w = util::smoothstep(g);
+		let x = util::smoothstep(f);
+		let value = util::lerp3(v, w, x, n, o, p, q, r, s, t, u);
+
+		// The derivative of each axis has a part from the gradients at the
+		// corners and a part from the smoothstep weights along that axis
+		let dx = util::lerp3(v, w, x, g0.0, g1.0, g2.0, g3.0, g4.0, g5.0, g6.0, g7.0)
+			+ util::smoothstep_derivative(d) * util::lerp2(w, x, o - n, q - p, s - r, u - t);
+		let dy = util::lerp3(v, w, x, g0.1, g1.1, g2.1, g3.1, g4.1, g5.1, g6.1, g7.1)
+			+ util::smoothstep_derivative(g) * util::lerp2(v, x, p - n, q - o, t - r, u - s);
+		let dz = util::lerp3(v, w, x, g0.2, g1.2, g2.2, g3.2, g4.2, g5.2, g6.2, g7.2)
+			+ util::smoothstep_derivative(f) * util::lerp2(v, w, r - n, s - o, t - p, u - q);
+		(value, [dx, dy, dz])
+	}
+
+	/// The lattice cell of a position and the arguments of `sample_and_lerp`.
+	#[inline(always)]
+	fn cell(&self, x: f64, y: f64, z: f64, y_scale: f64, y_limit: f64) -> (i32, i32, i32, f64, f64, f64, f64) {
 		let x2 = x + self.xo;
 		let y2 = y + self.yo;
 		let z2 = z + self.zo;
@@ -59,12 +111,13 @@ impl ImprovedNoise {
 		let mut y6 = 0.0;
 		if y_scale != 0.0 {
 			let t = if y_limit >= 0.0 && y_limit < y4 { y_limit } else { y4 };
-			y6 = (t / y_scale + 1.0e-7).floor()
+			y6 = (t / y_scale + 1.0e-7_f32 as f64).floor() * y_scale
 		}
 
-		self.sample_and_lerp(x3 as i32, y3 as i32, z3 as i32, x4, y4 - y6, z4, y4)
+		(x3 as i32, y3 as i32, z3 as i32, x4, y4 - y6, z4, y4)
 	}
 
+	#[allow(clippy::too_many_arguments)]
 	fn sample_and_lerp(&self, a: i32, b: i32, c: i32, d: f64, e: f64, f: f64, g: f64) -> f64 {
 		let h = self.p(a);
 		let i = self.p(a + 1);
@@ -89,13 +142,112 @@ impl ImprovedNoise {
 		util::lerp3(v, w, x, n, o, p, q, r, s, t, u)
 	}
 
+	/// Samples [`LANES`] positions at once with the same y scale and limit,
+	/// giving the same results as [`sample`](Self::sample) for each lane.
+	pub fn sample4(&self, x: F64x4, y: F64x4, z: F64x4, y_scale: f64, y_limit: f64) -> F64x4 {
+		let x2 = x + self.xo;
+		let y2 = y + self.yo;
+		let z2 = z + self.zo;
+		let x3 = x2.floor();
+		let y3 = y2.floor();
+		let z3 = z2.floor();
+		let x4 = x2 - x3;
+		let y4 = y2 - y3;
+		let z4 = z2 - z3;
+
+		let mut y6 = F64x4::splat(0.0);
+		if y_scale != 0.0 {
+			y6 = y4.map(|y4| {
+				let t = if y_limit >= 0.0 && y_limit < y4 { y_limit } else { y4 };
+				(t / y_scale + 1.0e-7_f32 as f64).floor() * y_scale
+			});
+		}
+
+		self.sample_and_lerp4(x3.0.map(|v| v as i32), y3.0.map(|v| v as i32), z3.0.map(|v| v as i32), x4, y4 - y6, z4, y4)
+	}
+
+	/// The batched version of `sample_and_lerp`. The permutation and gradient
+	/// lookups are done per lane, everything after that on whole vectors.
+	#[allow(clippy::too_many_arguments)]
+	fn sample_and_lerp4(&self, a: [i32; LANES], b: [i32; LANES], c: [i32; LANES], d: F64x4, e: F64x4, f: F64x4, g: F64x4) -> F64x4 {
+		let mut corners = [[(0.0, 0.0, 0.0); LANES]; 8];
+		for lane in 0..LANES {
+			let (a, b, c) = (a[lane], b[lane], c[lane]);
+			let h = self.p(a);
+			let i = self.p(a + 1);
+			let j = self.p(h + b);
+			let k = self.p(h + b + 1);
+			let l = self.p(i + b);
+			let m = self.p(i + b + 1);
+			let hashes = [j + c, l + c, k + c, m + c, j + c + 1, l + c + 1, k + c + 1, m + c + 1];
+			for (corner, hash) in corners.iter_mut().zip(hashes) {
+				corner[lane] = util::GRADIENT[(self.p(hash) & 15) as usize];
+			}
+		}
+		let grad_dot = |corner: &[(f64, f64, f64); LANES], x: F64x4, y: F64x4, z: F64x4| {
+			F64x4::from_fn(|i| corner[i].0) * x + F64x4::from_fn(|i| corner[i].1) * y + F64x4::from_fn(|i| corner[i].2) * z
+		};
+
+		let n = grad_dot(&corners[0], d, e, f);
+		let o = grad_dot(&corners[1], d - 1.0, e, f);
+		let p = grad_dot(&corners[2], d, e - 1.0, f);
+		let q = grad_dot(&corners[3], d - 1.0, e - 1.0, f);
+		let r = grad_dot(&corners[4], d, e, f - 1.0);
+		let s = grad_dot(&corners[5], d - 1.0, e, f - 1.0);
+		let t = grad_dot(&corners[6], d, e - 1.0, f - 1.0);
+		let u = grad_dot(&corners[7], d - 1.0, e - 1.0, f - 1.0);
+
+		let v = simd::smoothstep(d);
+		let w = simd::smoothstep(g);
+		let x = simd::smoothstep(f);
+
+		simd::lerp3(v, w, x, n, o, p, q, r, s, t, u)
+	}
+
+	/// The gradients at the corners of a lattice cell, in the order of the
+	/// `grad_dot` calls in `sample_and_lerp`.
+	fn gradients(&self, a: i32, b: i32, c: i32) -> [(f64, f64, f64); 8] {
+		let h = self.p(a);
+		let i = self.p(a + 1);
+		let j = self.p(h + b);
+		let k = self.p(h + b + 1);
+		let l = self.p(i + b);
+		let m = self.p(i + b + 1);
+		[j + c, l + c, k + c, m + c, j + c + 1, l + c + 1, k + c + 1, m + c + 1]
+			.map(|hash| util::GRADIENT[(self.p(hash) & 15) as usize])
+	}
+
 	fn p(&self, i: i32) -> i32 {
 		self.p[(i & 255) as usize] as i32
 	}
 }
 
+#[wasm_bindgen]
+impl ImprovedNoise {
+	#[wasm_bindgen(constructor)]
+	pub fn from_seed(seed: i64) -> ImprovedNoise {
+		ImprovedNoise::new(&mut LegacyRandomSource::new(seed))
+	}
+
+	#[wasm_bindgen(js_name = sample)]
+	pub fn sample_point(&self, x: f64, y: f64, z: f64) -> f64 {
+		self.sample(x, y, z, 0.0, 0.0)
+	}
+
+	pub fn sample_grid(&self, grid: &super::grid::Grid) -> Vec<f64> {
+		grid.map_lanes(|x, y, z| self.sample4(x, y, z, 0.0, 0.0))
+	}
+
+	/// The value and gradient at every position of a grid, flattened to
+	/// four numbers per sample.
+	pub fn sample_grid_gradient(&self, grid: &super::grid::Grid) -> Vec<f64> {
+		flatten_gradients(grid.map(|x, y, z| self.sample_gradient(x, y, z, 0.0, 0.0)))
+	}
+}
+
 #[wasm_bindgen]
 #[derive(Clone)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
 pub struct PerlinNoise {
 	levels: Vec<Option<(f64, ImprovedNoise)>>,
 	lowest_freq_input: f64,
@@ -103,6 +255,8 @@ pub struct PerlinNoise {
 }
 
 impl PerlinNoise {
+	/// Creates the noise using the legacy initialization, where octaves are
+	/// seeded in sequence from the highest frequency down.
 	pub fn new(random: &mut dyn RandomSource, params: &NoiseParameters) -> Self {
 		let n = params.amplitudes.len() as i32;
 		assert!(1 - params.first_octave >= n, "Positive octaves are disabled");
@@ -117,23 +271,86 @@ impl PerlinNoise {
 				random.consume(262);
 			}
 		}
+		levels.truncate(n as usize);
 		Self {
 			levels,
-			lowest_freq_input: (2 as f64).powi(params.first_octave),
-			lowest_freq_value: (2 as f64).powi(n - 1) / ((2 as f64).powi(n) - 1.0),
+			lowest_freq_input: 2_f64.powi(params.first_octave),
+			lowest_freq_value: 2_f64.powi(n - 1) / (2_f64.powi(n) - 1.0),
 		}
 	}
 
-	// pub fn get_octave(&self, i: i32) -> Option<ImprovedNoise> {
-	// 	self.levels[self.levels.len() - 1 - i as usize].as_ref().map(|f| f.1)
-	// }
+	/// Creates the noise by forking a positional random, where every octave
+	/// is seeded from the hash of its name.
+	pub fn create(random: &mut dyn RandomSource, params: &NoiseParameters) -> Self {
+		let n = params.amplitudes.len() as i32;
+		let positional = random.fork_positional();
+		let levels = params.amplitudes.iter().enumerate().map(|(i, &amplitude)| {
+			if amplitude == 0.0 {
+				return None
+			}
+			let octave = params.first_octave + i as i32;
+			Some((amplitude, ImprovedNoise::new(positional.from_hash_of(&format!("octave_{}", octave)).as_mut())))
+		}).collect();
+		Self {
+			levels,
+			lowest_freq_input: 2_f64.powi(params.first_octave),
+			lowest_freq_value: 2_f64.powi(n - 1) / (2_f64.powi(n) - 1.0),
+		}
+	}
+
+	/// Vanilla's `getOctaveNoise`, which counts from the highest frequency.
+	pub(crate) fn get_octave(&self, i: usize) -> Option<&ImprovedNoise> {
+		self.levels[self.levels.len() - 1 - i].as_ref().map(|f| &f.1)
+	}
+
+	/// The noise of the `i`th octave, counting from the lowest frequency like
+	/// the amplitudes of the noise parameters. Octaves with a zero amplitude
+	/// have no noise.
+	pub fn octave(&self, i: usize) -> Option<&ImprovedNoise> {
+		self.levels[i].as_ref().map(|f| &f.1)
+	}
+
+	/// The amplitude of the `i`th octave from the noise parameters.
+	pub fn amplitude(&self, i: usize) -> f64 {
+		self.levels[i].as_ref().map_or(0.0, |f| f.0)
+	}
+
+	/// The factor that positions are multiplied by before sampling the `i`th
+	/// octave.
+	pub fn frequency(&self, i: usize) -> f64 {
+		self.lowest_freq_input * 2_f64.powi(i as i32)
+	}
+
+	/// The factor that the `i`th octave's noise is multiplied by, which is its
+	/// amplitude scaled down by its frequency.
+	pub fn octave_factor(&self, i: usize) -> f64 {
+		self.amplitude(i) * (self.lowest_freq_value / 2_f64.powi(i as i32))
+	}
+
+	/// The bound for samples with a y scale, which can reach beyond
+	/// [`max_value`](Self::max_value). Vanilla's `maxBrokenValue`.
+	pub fn max_broken_value(&self, y_scale: f64) -> f64 {
+		self.edge_value(y_scale + 2.0)
+	}
+
+	fn edge_value(&self, multiplier: f64) -> f64 {
+		let mut value = 0.0;
+		let mut value_factor = self.lowest_freq_value;
+		for level in &self.levels {
+			if let Some((amplitude, _)) = level {
+				value += amplitude * multiplier * value_factor;
+			}
+			value_factor /= 2.0;
+		}
+		value
+	}
 
 	pub fn sample(&self, x: f64, y: f64, z: f64, y_scale: f64, y_limit: f64, fix_y: bool) -> f64 {
 		let mut value = 0.0;
 		let mut input_factor = self.lowest_freq_input;
 		let mut value_factor = self.lowest_freq_value;
 		for i in 0..self.levels.len() {
-			if let Some((amplitude, level)) = &self.levels[i as usize] {
+			if let Some((amplitude, level)) = &self.levels[i] {
 				let noise = level.sample(
 					util::wrap(x * input_factor),
 					if fix_y { -level.yo } else { util::wrap(y * input_factor) },
@@ -148,10 +365,142 @@ impl PerlinNoise {
 		}
 		value
 	}
+
+	/// Samples the noise together with its gradient, see
+	/// [`ImprovedNoise::sample_gradient`]. With `fix_y` the noise doesn't
+	/// change along y.
+	pub fn sample_gradient(&self, x: f64, y: f64, z: f64, y_scale: f64, y_limit: f64, fix_y: bool) -> (f64, [f64; 3]) {
+		let mut value = 0.0;
+		let mut gradient = [0.0; 3];
+		let mut input_factor = self.lowest_freq_input;
+		let mut value_factor = self.lowest_freq_value;
+		for i in 0..self.levels.len() {
+			if let Some((amplitude, level)) = &self.levels[i] {
+				let (noise, [dx, dy, dz]) = level.sample_gradient(
+					util::wrap(x * input_factor),
+					if fix_y { -level.yo } else { util::wrap(y * input_factor) },
+					util::wrap(z * input_factor),
+					y_scale * input_factor,
+					y_limit * input_factor,
+				);
+				value += amplitude * value_factor * noise;
+				let factor = amplitude * value_factor * input_factor;
+				gradient[0] += factor * dx;
+				gradient[1] += if fix_y { 0.0 } else { factor * dy };
+				gradient[2] += factor * dz;
+			}
+			input_factor *= 2.0;
+			value_factor /= 2.0;
+		}
+		(value, gradient)
+	}
+
+	/// The contribution of every octave to a sample, in the order of
+	/// [`octave`](Self::octave). Adding them up in order gives exactly the
+	/// result of [`sample`](Self::sample).
+	pub fn sample_octaves(&self, x: f64, y: f64, z: f64, y_scale: f64, y_limit: f64, fix_y: bool) -> Vec<f64> {
+		let mut values = Vec::with_capacity(self.levels.len());
+		let mut input_factor = self.lowest_freq_input;
+		let mut value_factor = self.lowest_freq_value;
+		for level in &self.levels {
+			values.push(match level {
+				Some((amplitude, level)) => amplitude * value_factor * level.sample(
+					util::wrap(x * input_factor),
+					if fix_y { -level.yo } else { util::wrap(y * input_factor) },
+					util::wrap(z * input_factor),
+					y_scale * input_factor,
+					y_limit * input_factor,
+				),
+				None => 0.0,
+			});
+			input_factor *= 2.0;
+			value_factor /= 2.0;
+		}
+		values
+	}
+
+	/// Samples [`LANES`] positions at once, see [`ImprovedNoise::sample4`].
+	pub fn sample4(&self, x: F64x4, y: F64x4, z: F64x4, y_scale: f64, y_limit: f64, fix_y: bool) -> F64x4 {
+		let mut value = F64x4::splat(0.0);
+		let mut input_factor = self.lowest_freq_input;
+		let mut value_factor = self.lowest_freq_value;
+		for i in 0..self.levels.len() {
+			if let Some((amplitude, level)) = &self.levels[i] {
+				let noise = level.sample4(
+					simd::wrap(x * input_factor),
+					if fix_y { F64x4::splat(-level.yo) } else { simd::wrap(y * input_factor) },
+					simd::wrap(z * input_factor),
+					y_scale * input_factor,
+					y_limit * input_factor,
+				);
+				value = value + F64x4::splat(amplitude * value_factor) * noise;
+			}
+			input_factor *= 2.0;
+			value_factor /= 2.0;
+		}
+		value
+	}
+}
+
+#[wasm_bindgen]
+impl PerlinNoise {
+	#[wasm_bindgen(constructor)]
+	pub fn from_seed(seed: i64, first_octave: i32, amplitudes: &[f64]) -> PerlinNoise {
+		PerlinNoise::new(&mut LegacyRandomSource::new(seed), &NoiseParameters::new(first_octave, amplitudes))
+	}
+
+	#[wasm_bindgen(js_name = sample)]
+	pub fn sample_point(&self, x: f64, y: f64, z: f64) -> f64 {
+		self.sample(x, y, z, 0.0, 0.0, false)
+	}
+
+	pub fn sample_grid(&self, grid: &super::grid::Grid) -> Vec<f64> {
+		grid.map_lanes(|x, y, z| self.sample4(x, y, z, 0.0, 0.0, false))
+	}
+
+	/// The value and gradient at every position of a grid, flattened to
+	/// four numbers per sample.
+	pub fn sample_grid_gradient(&self, grid: &super::grid::Grid) -> Vec<f64> {
+		flatten_gradients(grid.map(|x, y, z| self.sample_gradient(x, y, z, 0.0, 0.0, false)))
+	}
+
+	/// The contribution of every octave to a sample, see [`sample_octaves`](Self::sample_octaves).
+	#[wasm_bindgen(js_name = sampleOctaves)]
+	pub fn sample_point_octaves(&self, x: f64, y: f64, z: f64) -> Vec<f64> {
+		self.sample_octaves(x, y, z, 0.0, 0.0, false)
+	}
+
+	/// The number of octaves, including the ones with a zero amplitude.
+	#[wasm_bindgen(getter)]
+	pub fn octaves(&self) -> usize {
+		self.levels.len()
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn amplitudes(&self) -> Vec<f64> {
+		(0..self.octaves()).map(|i| self.amplitude(i)).collect()
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn frequencies(&self) -> Vec<f64> {
+		(0..self.octaves()).map(|i| self.frequency(i)).collect()
+	}
+
+	/// A copy of the noise of an octave, see [`octave`](Self::octave).
+	pub fn octave_noise(&self, i: usize) -> Option<ImprovedNoise> {
+		self.octave(i).cloned()
+	}
+
+	/// The largest value the noise can reach, the same as vanilla's `maxValue`.
+	#[wasm_bindgen(getter)]
+	pub fn max_value(&self) -> f64 {
+		self.edge_value(2.0)
+	}
 }
 
 #[wasm_bindgen]
 #[derive(Clone)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
 pub struct NormalNoise {
 	first: PerlinNoise,
 	second: PerlinNoise,
@@ -161,10 +510,21 @@ pub struct NormalNoise {
 impl NormalNoise {
 	const INPUT_FACTOR: f64 = 1.0181268882175227;
 
+	/// Creates the noise using legacy initialized octaves, see [`PerlinNoise::new`].
 	pub fn new(random: &mut dyn RandomSource, params: &NoiseParameters) -> Self {
 		let first = PerlinNoise::new(random, params);
 		let second = PerlinNoise::new(random, params);
+		Self::from_octaves(first, second, params)
+	}
 
+	/// Creates the noise using positional octaves, see [`PerlinNoise::create`].
+	pub fn create(random: &mut dyn RandomSource, params: &NoiseParameters) -> Self {
+		let first = PerlinNoise::create(random, params);
+		let second = PerlinNoise::create(random, params);
+		Self::from_octaves(first, second, params)
+	}
+
+	fn from_octaves(first: PerlinNoise, second: PerlinNoise, params: &NoiseParameters) -> Self {
 		let mut min = i32::MAX;
 		let mut max = i32::MIN;
 		for (i, &a) in params.amplitudes.iter().enumerate() {
@@ -177,7 +537,7 @@ impl NormalNoise {
 		Self {
 			first,
 			second,
-			value_factor: (1.0 / 6.0) / (0.1 * (1.0 + 1.0 / (max - min + 1) as f64)),
+			value_factor: (1.0 / 6.0) / (0.1 * (1.0 + 1.0 / max.wrapping_sub(min).wrapping_add(1) as f64)),
 		}
 	}
 
@@ -189,4 +549,247 @@ impl NormalNoise {
 		let second = self.second.sample(xx, yy, zz, 0.0, 0.0, false);
 		(first + second) * self.value_factor
 	}
+
+	/// Samples the noise together with its gradient, see
+	/// [`ImprovedNoise::sample_gradient`].
+	pub fn sample_gradient(&self, x: f64, y: f64, z: f64) -> (f64, [f64; 3]) {
+		let (first, [dx1, dy1, dz1]) = self.first.sample_gradient(x, y, z, 0.0, 0.0, false);
+		let (second, [dx2, dy2, dz2]) = self.second.sample_gradient(x * Self::INPUT_FACTOR, y * Self::INPUT_FACTOR, z * Self::INPUT_FACTOR, 0.0, 0.0, false);
+		let value = (first + second) * self.value_factor;
+		let derivative = |first: f64, second: f64| (first + second * Self::INPUT_FACTOR) * self.value_factor;
+		(value, [derivative(dx1, dx2), derivative(dy1, dy2), derivative(dz1, dz2)])
+	}
+
+	pub fn first(&self) -> &PerlinNoise {
+		&self.first
+	}
+
+	/// The second noise, which is sampled at positions scaled by a factor
+	/// slightly above one.
+	pub fn second(&self) -> &PerlinNoise {
+		&self.second
+	}
+
+	/// The contribution of every octave to a sample, first those of the first
+	/// noise and then those of the second, scaled by the value factor. Their
+	/// sum is the result of [`sample`](Self::sample) up to rounding.
+	pub fn sample_octaves(&self, x: f64, y: f64, z: f64) -> Vec<f64> {
+		let first = self.first.sample_octaves(x, y, z, 0.0, 0.0, false);
+		let second = self.second.sample_octaves(x * Self::INPUT_FACTOR, y * Self::INPUT_FACTOR, z * Self::INPUT_FACTOR, 0.0, 0.0, false);
+		first.into_iter().chain(second).map(|v| v * self.value_factor).collect()
+	}
+
+	/// Samples [`LANES`] positions at once, see [`ImprovedNoise::sample4`].
+	pub fn sample4(&self, x: F64x4, y: F64x4, z: F64x4) -> F64x4 {
+		let xx = x * Self::INPUT_FACTOR;
+		let yy = y * Self::INPUT_FACTOR;
+		let zz = z * Self::INPUT_FACTOR;
+		let first = self.first.sample4(x, y, z, 0.0, 0.0, false);
+		let second = self.second.sample4(xx, yy, zz, 0.0, 0.0, false);
+		(first + second) * self.value_factor
+	}
+}
+
+#[wasm_bindgen]
+impl NormalNoise {
+	#[wasm_bindgen(constructor)]
+	pub fn from_seed(seed: i64, first_octave: i32, amplitudes: &[f64]) -> NormalNoise {
+		NormalNoise::new(&mut LegacyRandomSource::new(seed), &NoiseParameters::new(first_octave, amplitudes))
+	}
+
+	#[wasm_bindgen(js_name = sample)]
+	pub fn sample_point(&self, x: f64, y: f64, z: f64) -> f64 {
+		self.sample(x, y, z)
+	}
+
+	pub fn sample_grid(&self, grid: &super::grid::Grid) -> Vec<f64> {
+		grid.map_lanes(|x, y, z| self.sample4(x, y, z))
+	}
+
+	/// The value and gradient at every position of a grid, flattened to
+	/// four numbers per sample.
+	pub fn sample_grid_gradient(&self, grid: &super::grid::Grid) -> Vec<f64> {
+		flatten_gradients(grid.map(|x, y, z| self.sample_gradient(x, y, z)))
+	}
+
+	/// The contribution of every octave to a sample, see [`sample_octaves`](Self::sample_octaves).
+	#[wasm_bindgen(js_name = sampleOctaves)]
+	pub fn sample_point_octaves(&self, x: f64, y: f64, z: f64) -> Vec<f64> {
+		self.sample_octaves(x, y, z)
+	}
+
+	/// A copy of the first noise.
+	#[wasm_bindgen(getter = first)]
+	pub fn first_noise(&self) -> PerlinNoise {
+		self.first.clone()
+	}
+
+	/// A copy of the second noise.
+	#[wasm_bindgen(getter = second)]
+	pub fn second_noise(&self) -> PerlinNoise {
+		self.second.clone()
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn value_factor(&self) -> f64 {
+		self.value_factor
+	}
+
+	/// The largest value the noise can reach, the same as vanilla's `maxValue`.
+	#[wasm_bindgen(getter)]
+	pub fn max_value(&self) -> f64 {
+		(self.first.max_value() + self.second.max_value()) * self.value_factor
+	}
+}
+
+fn flatten_gradients(samples: Vec<(f64, [f64; 3])>) -> Vec<f64> {
+	samples.into_iter().flat_map(|(value, [dx, dy, dz])| [value, dx, dy, dz]).collect()
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlendedNoise {
+	min_limit_noise: PerlinNoise,
+	max_limit_noise: PerlinNoise,
+	main_noise: PerlinNoise,
+	xz_multiplier: f64,
+	y_multiplier: f64,
+	xz_factor: f64,
+	y_factor: f64,
+	smear_scale_multiplier: f64,
+}
+
+impl BlendedNoise {
+	pub fn new(random: &mut dyn RandomSource, xz_scale: f64, y_scale: f64, xz_factor: f64, y_factor: f64, smear_scale_multiplier: f64) -> Self {
+		let limit_params = NoiseParameters::new(-15, &[1.0; 16]);
+		let main_params = NoiseParameters::new(-7, &[1.0; 8]);
+		Self {
+			min_limit_noise: PerlinNoise::new(random, &limit_params),
+			max_limit_noise: PerlinNoise::new(random, &limit_params),
+			main_noise: PerlinNoise::new(random, &main_params),
+			xz_multiplier: 684.412 * xz_scale,
+			y_multiplier: 684.412 * y_scale,
+			xz_factor,
+			y_factor,
+			smear_scale_multiplier,
+		}
+	}
+
+	/// Checks that a restored noise has the octaves that sampling reads.
+	#[cfg(feature = "snapshot")]
+	pub(crate) fn check(&self) -> Result<(), String> {
+		if self.main_noise.levels.len() < 8 || self.min_limit_noise.levels.len() < 16 || self.max_limit_noise.levels.len() < 16 {
+			return Err("Snapshot contains a blended noise with missing octaves".to_string())
+		}
+		Ok(())
+	}
+
+	pub fn sample(&self, x: i32, y: i32, z: i32) -> f64 {
+		let x = x as f64 * self.xz_multiplier;
+		let y = y as f64 * self.y_multiplier;
+		let z = z as f64 * self.xz_multiplier;
+		let fx = x / self.xz_factor;
+		let fy = y / self.y_factor;
+		let fz = z / self.xz_factor;
+		let smear_y = self.y_multiplier * self.smear_scale_multiplier;
+		let smear_fy = smear_y / self.y_factor;
+
+		let mut main = 0.0;
+		let mut pow = 1.0;
+		for i in 0..8 {
+			if let Some(noise) = self.main_noise.get_octave(i) {
+				main += noise.sample(util::wrap(fx * pow), util::wrap(fy * pow), util::wrap(fz * pow), smear_fy * pow, fy * pow) / pow;
+			}
+			pow /= 2.0;
+		}
+
+		let factor = (main / 10.0 + 1.0) / 2.0;
+		let is_max = factor >= 1.0;
+		let is_min = factor <= 0.0;
+		let mut min_limit = 0.0;
+		let mut max_limit = 0.0;
+		pow = 1.0;
+		for i in 0..16 {
+			let wx = util::wrap(x * pow);
+			let wy = util::wrap(y * pow);
+			let wz = util::wrap(z * pow);
+			let y_scale = smear_y * pow;
+			if !is_max {
+				if let Some(noise) = self.min_limit_noise.get_octave(i) {
+					min_limit += noise.sample(wx, wy, wz, y_scale, y * pow) / pow;
+				}
+			}
+			if !is_min {
+				if let Some(noise) = self.max_limit_noise.get_octave(i) {
+					max_limit += noise.sample(wx, wy, wz, y_scale, y * pow) / pow;
+				}
+			}
+			pow /= 2.0;
+		}
+
+		util::clamped_lerp(min_limit / 512.0, max_limit / 512.0, factor) / 128.0
+	}
+}
+
+/// Vanilla's `SimplexNoise`, which only the end islands use.
+#[derive(Clone)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub struct SimplexNoise {
+	#[cfg_attr(feature = "snapshot", serde(with = "super::snapshot::permutation"))]
+	p: [u8; 256],
+}
+
+impl SimplexNoise {
+	const F2: f64 = 0.3660254037844386;
+	const G2: f64 = 0.21132486540518713;
+
+	pub fn new(random: &mut dyn RandomSource) -> Self {
+		// The offsets are only used by 3D samples, which aren't ported
+		for _ in 0..3 {
+			random.next_double();
+		}
+		let mut p: [u8; 256] = [0; 256];
+		for (i, v) in p.iter_mut().enumerate() {
+			*v = i as u8
+		}
+		for i in 0..256 {
+			let j = random.next_int_max(256 - i as i32) as usize;
+			p.swap(i, i + j);
+		}
+		Self { p }
+	}
+
+	fn p(&self, i: i32) -> i32 {
+		self.p[(i & 0xFF) as usize] as i32
+	}
+
+	fn corner_noise(i: i32, x: f64, y: f64) -> f64 {
+		let d = 0.5 - x * x - y * y;
+		if d < 0.0 {
+			0.0
+		} else {
+			let d = d * d;
+			d * d * util::grad_dot(i, x, y, 0.0)
+		}
+	}
+
+	pub fn sample_2d(&self, x: f64, y: f64) -> f64 {
+		let s = (x + y) * Self::F2;
+		let i = (x + s).floor() as i32;
+		let j = (y + s).floor() as i32;
+		let t = (i.wrapping_add(j)) as f64 * Self::G2;
+		let x0 = x - (i as f64 - t);
+		let y0 = y - (j as f64 - t);
+		let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+		let x1 = x0 - i1 as f64 + Self::G2;
+		let y1 = y0 - j1 as f64 + Self::G2;
+		let x2 = x0 - 1.0 + 2.0 * Self::G2;
+		let y2 = y0 - 1.0 + 2.0 * Self::G2;
+		let ii = i & 0xFF;
+		let jj = j & 0xFF;
+		let gi0 = self.p(ii + self.p(jj)) % 12;
+		let gi1 = self.p(ii + i1 + self.p(jj + j1)) % 12;
+		let gi2 = self.p(ii + 1 + self.p(jj + 1)) % 12;
+		70.0 * (Self::corner_noise(gi0, x0, y0) + Self::corner_noise(gi1, x1, y1) + Self::corner_noise(gi2, x2, y2))
+	}
 }
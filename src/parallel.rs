@@ -0,0 +1,61 @@
+//! Parallel versions of the grid exports for native callers, enabled with
+//! the `rayon` feature. Results are identical to the serial versions.
+//!
+//! All noise types, samplers and parameter lists are `Send` and `Sync`, so a
+//! single instance can be shared between threads without cloning.
+
+use rayon::prelude::*;
+use super::{ biome_source, climate, grid, noise, random, sampler };
+use super::simd::{ F64x4, LANES };
+
+fn par_iterate_grid<F, T>(f: F, grid: &grid::Grid) -> Vec<T> where F: Fn(f64, f64, f64) -> T + Sync, T: Send {
+	(0..grid.len()).into_par_iter().map(|i| {
+		let (x, y, z) = grid.position(i);
+		f(x, y, z)
+	}).collect()
+}
+
+/// Like `par_iterate_grid`, but calls `f` with [`LANES`] positions at a time.
+fn par_iterate_grid_lanes<F>(f: F, grid: &grid::Grid) -> Vec<f64> where F: Fn(F64x4, F64x4, F64x4) -> F64x4 + Sync {
+	let mut result = vec![0.0; grid.len()];
+	result.par_chunks_mut(LANES).enumerate().for_each(|(chunk, values)| {
+		let positions: [(f64, f64, f64); LANES] = std::array::from_fn(|i| grid.position(chunk * LANES + i.min(values.len() - 1)));
+		let x = F64x4::from_fn(|i| positions[i].0);
+		let y = F64x4::from_fn(|i| positions[i].1);
+		let z = F64x4::from_fn(|i| positions[i].2);
+		values.copy_from_slice(&f(x, y, z).0[..values.len()]);
+	});
+	result
+}
+
+pub fn improved_noise(seed: i64, grid: &grid::Grid) -> Vec<f64> {
+	let mut random = random::LegacyRandomSource::new(seed);
+	let noise = noise::ImprovedNoise::new(&mut random);
+	par_iterate_grid_lanes(|x, y, z| noise.sample4(x, y, z, 0.0, 0.0), grid)
+}
+
+pub fn normal_noise(seed: i64, first_octave: i32, amplitudes: &[f64], grid: &grid::Grid) -> Vec<f64> {
+	let mut random = random::LegacyRandomSource::new(seed);
+	let params = noise::NoiseParameters::new(first_octave, amplitudes);
+	let noise = noise::NormalNoise::new(&mut random, &params);
+	par_iterate_grid_lanes(|x, y, z| noise.sample4(x, y, z), grid)
+}
+
+pub fn multi_noise(parameters: &climate::ParameterList, sampler: &sampler::Sampler, grid: &grid::Grid) -> Vec<i32> {
+	par_iterate_grid(|x, y, z| {
+		let target = sampler.target(x as i32, y as i32, z as i32);
+		parameters.find(target)
+	}, grid)
+}
+
+/// The biome at each block position of the grid, from any biome source.
+pub fn biome_source(source: &dyn biome_source::BiomeSource, sampler: &sampler::Sampler, grid: &grid::Grid) -> Vec<i32> {
+	par_iterate_grid(|x, y, z| source.get_noise_biome(x as i32 >> 2, y as i32 >> 2, z as i32 >> 2, sampler), grid)
+}
+
+pub fn climate_noise(sampler: &sampler::Sampler, grid: &grid::Grid) -> Vec<f64> {
+	par_iterate_grid(|x, y, z| {
+		sampler.target(x as i32, y as i32, z as i32).vec()
+	}, grid)
+		.into_iter().flatten().collect::<Vec<_>>()
+}
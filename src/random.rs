@@ -1,15 +1,94 @@
+use std::convert::TryInto;
+use md5::{ Md5, Digest };
+use sha2::Sha256;
+use super::util;
+
 pub trait RandomSource {
-	fn set_seed(&mut self, seed: i64) -> ();
-	fn consume(&mut self, n: i32) -> ();
+	fn set_seed(&mut self, seed: i64);
+	fn consume(&mut self, n: i32);
 	fn next_int(&mut self) -> i32;
 	fn next_int_max(&mut self, max: i32) -> i32;
 	fn next_long(&mut self) -> i64;
 	fn next_float(&mut self) -> f32;
 	fn next_double(&mut self) -> f64;
+	fn next_boolean(&mut self) -> bool;
+	fn next_gaussian(&mut self) -> f64;
+	fn fork_positional(&mut self) -> Box<dyn PositionalRandomFactory>;
+
+	/// Returns a value between `min` (inclusive) and `max` (exclusive). The
+	/// bound wraps like in [`next_int_between_inclusive`](Self::next_int_between_inclusive).
+	fn next_int_between(&mut self, min: i32, max: i32) -> i32 {
+		min.wrapping_add(self.next_int_max(max.wrapping_sub(min)))
+	}
+
+	/// Returns a value between `min` and `max`, both inclusive. The bound
+	/// wraps like vanilla's int arithmetic, so ranges that don't fit in an
+	/// `i32` fail like vanilla does.
+	fn next_int_between_inclusive(&mut self, min: i32, max: i32) -> i32 {
+		self.next_int_max(max.wrapping_sub(min).wrapping_add(1)).wrapping_add(min)
+	}
+
+	/// Returns a value around `mean` following a triangular distribution.
+	fn triangle(&mut self, mean: f64, spread: f64) -> f64 {
+		mean + spread * (self.next_double() - self.next_double())
+	}
+}
+
+/// Draws two normally distributed values using the Marsaglia polar method,
+/// the second of which is cached by the caller like vanilla does.
+fn marsaglia_polar_gaussian(random: &mut dyn RandomSource) -> (f64, f64) {
+	loop {
+		let a = 2.0 * random.next_double() - 1.0;
+		let b = 2.0 * random.next_double() - 1.0;
+		let s = a * a + b * b;
+		if s < 1.0 && s != 0.0 {
+			let m = (-2.0 * util::strict_log(s) / s).sqrt();
+			return (a * m, b * m)
+		}
+	}
+}
+
+pub trait PositionalRandomFactory {
+	fn at(&self, x: i32, y: i32, z: i32) -> Box<dyn RandomSource>;
+	#[allow(clippy::wrong_self_convention)]
+	fn from_hash_of(&self, name: &str) -> Box<dyn RandomSource>;
+}
+
+/// Equivalent of `Mth.getSeed`, used to derive positional random sources.
+pub fn get_seed(x: i32, y: i32, z: i32) -> i64 {
+	let mut seed = (x.wrapping_mul(3129871) as i64) ^ (z as i64).wrapping_mul(116129781) ^ y as i64;
+	seed = seed.wrapping_mul(seed).wrapping_mul(42317861).wrapping_add(seed.wrapping_mul(11));
+	seed >> 16
+}
+
+/// Equivalent of Java's `String.hashCode`.
+pub fn java_string_hash(name: &str) -> i32 {
+	name.encode_utf16().fold(0i32, |hash, c| hash.wrapping_mul(31).wrapping_add(c as i32))
+}
+
+/// Parses a seed like the world creation screen, vanilla's
+/// `WorldOptions.parseSeed`. Numbers are used as they are and other text is
+/// hashed with [`java_string_hash`], both after trimming. Empty text gives
+/// no seed, for which the game picks a random one.
+pub fn parse_seed(text: &str) -> Option<i64> {
+	// Java's `String.trim` only removes control characters and spaces
+	let text = text.trim_matches(|c| c <= ' ');
+	if text.is_empty() {
+		return None
+	}
+	Some(text.parse::<i64>().unwrap_or_else(|_| java_string_hash(text) as i64))
+}
+
+/// The seed that `BiomeManager` uses to pick between the four nearest quart
+/// positions, the first eight bytes of the SHA-256 of the world seed.
+pub fn obfuscate_seed(seed: i64) -> i64 {
+	let hash = Sha256::digest(seed.to_le_bytes());
+	i64::from_le_bytes(hash[0..8].try_into().unwrap())
 }
 
 pub struct LegacyRandomSource {
 	seed: i64,
+	next_next_gaussian: Option<f64>,
 }
 
 impl LegacyRandomSource {
@@ -18,12 +97,12 @@ impl LegacyRandomSource {
 	const MULTIPLIER: i64 = 25214903917;
 	const INCREMENT: i64 = 11;
 	const FLOAT_MULTIPLIER: f32 = 5.9604645E-8;
-	const DOUBLE_MULTIPLIER: f64 = 1.110223E-16;
+	const DOUBLE_MULTIPLIER: f64 = 1.110223E-16_f32 as f64;
 
-	#[allow(dead_code)]
 	pub fn new(seed: i64) -> Self {
 		Self {
-			seed: Self::initial_seed(seed)
+			seed: Self::initial_seed(seed),
+			next_next_gaussian: None,
 		}
 	}
 
@@ -33,13 +112,49 @@ impl LegacyRandomSource {
 
 	fn next(&mut self, n: i32) -> i32 {
 		self.seed = self.seed.wrapping_mul(Self::MULTIPLIER).wrapping_add(Self::INCREMENT) & Self::MODULUS_MASK;
-		(self.seed >> Self::MODULUS_BITS - n) as i32
+		(self.seed >> (Self::MODULUS_BITS - n)) as i32
+	}
+
+	/// Seeds the random for decorating a chunk, like vanilla's
+	/// `WorldgenRandom.setDecorationSeed`, and returns the decoration seed.
+	pub fn set_decoration_seed(&mut self, level_seed: i64, min_block_x: i32, min_block_z: i32) -> i64 {
+		self.set_seed(level_seed);
+		let a = self.next_long() | 1;
+		let b = self.next_long() | 1;
+		let seed = (min_block_x as i64).wrapping_mul(a).wrapping_add((min_block_z as i64).wrapping_mul(b)) ^ level_seed;
+		self.set_seed(seed);
+		seed
+	}
+
+	/// Seeds the random for the `index`th feature of a generation step in a
+	/// chunk, like vanilla's `WorldgenRandom.setFeatureSeed`.
+	pub fn set_feature_seed(&mut self, decoration_seed: i64, index: i32, step: i32) {
+		self.set_seed(decoration_seed.wrapping_add(index as i64).wrapping_add(10000_i32.wrapping_mul(step) as i64));
+	}
+
+	/// Seeds the random for carvers and legacy structures in a chunk, like
+	/// vanilla's `WorldgenRandom.setLargeFeatureSeed`.
+	pub fn set_large_feature_seed(&mut self, base_seed: i64, chunk_x: i32, chunk_z: i32) {
+		self.set_seed(base_seed);
+		let a = self.next_long();
+		let b = self.next_long();
+		self.set_seed((chunk_x as i64).wrapping_mul(a) ^ (chunk_z as i64).wrapping_mul(b) ^ base_seed);
+	}
+
+	/// Seeds the random for a structure placement region, like vanilla's
+	/// `WorldgenRandom.setLargeFeatureWithSalt`.
+	pub fn set_large_feature_with_salt(&mut self, level_seed: i64, region_x: i32, region_z: i32, salt: i32) {
+		self.set_seed((region_x as i64).wrapping_mul(341873128712)
+			.wrapping_add((region_z as i64).wrapping_mul(132897987541))
+			.wrapping_add(level_seed)
+			.wrapping_add(salt as i64));
 	}
 }
 
 impl RandomSource for LegacyRandomSource {
 	fn set_seed(&mut self, seed: i64) {
-		self.seed = Self::initial_seed(seed)
+		self.seed = Self::initial_seed(seed);
+		self.next_next_gaussian = None;
 	}
 
 	fn consume(&mut self, n: i32) {
@@ -53,14 +168,14 @@ impl RandomSource for LegacyRandomSource {
 	}
 
 	fn next_int_max(&mut self, max: i32) -> i32 {
-		assert!(max > 0);
-		if (max & max - 1) == 0 {
-			return (max as i64 * self.next(31) as i64 >> 31) as i32;
+		assert!(max > 0, "Bound must be positive");
+		if (max & (max - 1)) == 0 {
+			return ((max as i64 * self.next(31) as i64) >> 31) as i32;
 		}
 		loop {
 			let a = self.next(31);
 			let b = a % max;
-			if a - b + max - 1 >= 0 {
+			if a.wrapping_sub(b).wrapping_add(max - 1) >= 0 {
 				return b;
 			};
 		}
@@ -81,47 +196,90 @@ impl RandomSource for LegacyRandomSource {
 		let hi = self.next(27) as i64;
 		((lo << 27) + hi) as f64 * Self::DOUBLE_MULTIPLIER
 	}
+
+	fn next_boolean(&mut self) -> bool {
+		self.next(1) != 0
+	}
+
+	fn next_gaussian(&mut self) -> f64 {
+		if let Some(gaussian) = self.next_next_gaussian.take() {
+			return gaussian
+		}
+		let (gaussian, next) = marsaglia_polar_gaussian(self);
+		self.next_next_gaussian = Some(next);
+		gaussian
+	}
+
+	fn fork_positional(&mut self) -> Box<dyn PositionalRandomFactory> {
+		Box::new(LegacyPositionalRandomFactory { seed: self.next_long() })
+	}
+}
+
+pub struct LegacyPositionalRandomFactory {
+	seed: i64,
+}
+
+impl PositionalRandomFactory for LegacyPositionalRandomFactory {
+	fn at(&self, x: i32, y: i32, z: i32) -> Box<dyn RandomSource> {
+		Box::new(LegacyRandomSource::new(get_seed(x, y, z) ^ self.seed))
+	}
+
+	fn from_hash_of(&self, name: &str) -> Box<dyn RandomSource> {
+		Box::new(LegacyRandomSource::new(java_string_hash(name) as i64 ^ self.seed))
+	}
 }
 
 pub struct XoroshiroRandomSource {
 	lo: i64,
 	hi: i64,
+	next_next_gaussian: Option<f64>,
 }
 
 impl XoroshiroRandomSource {
 	const FLOAT_UNIT: f32 = 5.9604645E-8;
-	const DOUBLE_UNIT: f64 = 1.110223E-16;
+	const DOUBLE_UNIT: f64 = 1.110223E-16_f32 as f64;
+	const SILVER_RATIO_64: i64 = 7640891576956012809;
+	const GOLDEN_RATIO_64: i64 = -7046029254386353131;
+
 	pub fn new(lo: i64, hi: i64) -> Self {
-		Self { lo, hi }
+		if lo == 0 && hi == 0 {
+			return Self::default()
+		}
+		Self { lo, hi, next_next_gaussian: None }
 	}
 
 	pub fn from(seed: i64) -> Self {
-		Self {
-			lo: Self::mix_stafford_13(seed ^ 0x6A09E667F3BCC909),
-			hi: Self::mix_stafford_13(seed - 7046029254386353131),
-		}
+		let (lo, hi) = Self::upgrade_seed_to_128_bit(seed);
+		Self::new(lo, hi)
+	}
+
+	fn upgrade_seed_to_128_bit(seed: i64) -> (i64, i64) {
+		let lo = seed ^ Self::SILVER_RATIO_64;
+		let hi = lo.wrapping_add(Self::GOLDEN_RATIO_64);
+		(Self::mix_stafford_13(lo), Self::mix_stafford_13(hi))
 	}
 
-	fn mix_stafford_13(mut a: i64) -> i64 {
-		a = (a ^ a >> 30).wrapping_mul(-4658895280553007687);
-		a = (a ^ a >> 27).wrapping_mul(-7723592293110705685);
-		a ^ a >> 31
+	fn mix_stafford_13(a: i64) -> i64 {
+		let mut a = a as u64;
+		a = (a ^ a >> 30).wrapping_mul(0xBF58476D1CE4E5B9);
+		a = (a ^ a >> 27).wrapping_mul(0x94D049BB133111EB);
+		(a ^ a >> 31) as i64
 	}
 
 	fn next_bits(&mut self, n: i32) -> i64 {
-		self.next_long() >> 64 - n
+		(self.next_long() as u64 >> (64 - n)) as i64
 	}
 }
 
 impl Default for XoroshiroRandomSource {
 	fn default() -> Self {
-		Self::new(-7046029254386353131, 7640891576956012809)
+		Self { lo: Self::GOLDEN_RATIO_64, hi: Self::SILVER_RATIO_64, next_next_gaussian: None }
 	}
 }
 
 impl RandomSource for XoroshiroRandomSource {
 	fn set_seed(&mut self, seed: i64) {
-		Self::from(seed);
+		*self = Self::from(seed);
 	}
 
 	fn consume(&mut self, n: i32) {
@@ -135,15 +293,25 @@ impl RandomSource for XoroshiroRandomSource {
 	}
 
 	fn next_int_max(&mut self, max: i32) -> i32 {
-		assert!(max > 0);
-		((self.next_long() as i32) % max).abs()
+		assert!(max > 0, "Bound must be positive");
+		let bound = max as u64;
+		let mut m = (self.next_int() as u32 as u64) * bound;
+		if (m & 0xFFFFFFFF) < bound {
+			let threshold = (max.wrapping_neg() as u32 % max as u32) as u64;
+			while (m & 0xFFFFFFFF) < threshold {
+				m = (self.next_int() as u32 as u64) * bound;
+			}
+		}
+		(m >> 32) as i32
 	}
 
 	fn next_long(&mut self) -> i64 {
-		let res = (self.lo.wrapping_add(self.hi)).wrapping_shl(17).wrapping_add(self.lo);
-		let a = self.hi ^ self.lo;
-		self.lo = (self.lo << 49) ^ a ^ self.hi << 21;
-		self.hi = a << 28;
+		let lo = self.lo;
+		let hi = self.hi;
+		let res = lo.wrapping_add(hi).rotate_left(17).wrapping_add(lo);
+		let hi = hi ^ lo;
+		self.lo = lo.rotate_left(49) ^ hi ^ (hi << 21);
+		self.hi = hi.rotate_left(28);
 		res
 	}
 
@@ -154,4 +322,39 @@ impl RandomSource for XoroshiroRandomSource {
 	fn next_double(&mut self) -> f64 {
 		self.next_bits(53) as f64 * Self::DOUBLE_UNIT
 	}
+
+	fn next_boolean(&mut self) -> bool {
+		(self.next_long() & 1) != 0
+	}
+
+	fn next_gaussian(&mut self) -> f64 {
+		if let Some(gaussian) = self.next_next_gaussian.take() {
+			return gaussian
+		}
+		let (gaussian, next) = marsaglia_polar_gaussian(self);
+		self.next_next_gaussian = Some(next);
+		gaussian
+	}
+
+	fn fork_positional(&mut self) -> Box<dyn PositionalRandomFactory> {
+		Box::new(XoroshiroPositionalRandomFactory { lo: self.next_long(), hi: self.next_long() })
+	}
+}
+
+pub struct XoroshiroPositionalRandomFactory {
+	lo: i64,
+	hi: i64,
+}
+
+impl PositionalRandomFactory for XoroshiroPositionalRandomFactory {
+	fn at(&self, x: i32, y: i32, z: i32) -> Box<dyn RandomSource> {
+		Box::new(XoroshiroRandomSource::new(get_seed(x, y, z) ^ self.lo, self.hi))
+	}
+
+	fn from_hash_of(&self, name: &str) -> Box<dyn RandomSource> {
+		let hash = Md5::digest(name.as_bytes());
+		let lo = i64::from_be_bytes(hash[0..8].try_into().unwrap());
+		let hi = i64::from_be_bytes(hash[8..16].try_into().unwrap());
+		Box::new(XoroshiroRandomSource::new(lo ^ self.lo, hi ^ self.hi))
+	}
 }
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use serde_json::Value;
+use super::noise::NoiseParameters;
+
+/// Adds the default `minecraft` namespace to an ID if it has none.
+pub fn normalize_id(id: &str) -> String {
+	if id.contains(':') { id.to_string() } else { format!("minecraft:{}", id) }
+}
+
+/// Holds the unseeded worldgen definitions that routers are built from.
+#[derive(Clone, Default)]
+pub struct Registry {
+	noises: HashMap<String, NoiseParameters>,
+	density_functions: HashMap<String, Value>,
+	noise_settings: HashMap<String, Value>,
+	/// All other definitions by kind, like `dimension` or `tags/worldgen/biome`.
+	resources: HashMap<String, HashMap<String, Value>>,
+}
+
+impl Registry {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Builds a registry from JSON objects mapping IDs to noise parameters and
+	/// density function definitions.
+	pub fn from_json(noises: &Value, density_functions: &Value) -> Result<Self, String> {
+		let mut registry = Self::new();
+		for (id, json) in noises.as_object().ok_or("Noises must be an object")? {
+			registry.register_noise(id, NoiseParameters::from_json(json)?);
+		}
+		for (id, json) in density_functions.as_object().ok_or("Density functions must be an object")? {
+			registry.register_density_function(id, json.clone());
+		}
+		Ok(registry)
+	}
+
+	pub fn register_noise(&mut self, id: &str, params: NoiseParameters) {
+		self.noises.insert(normalize_id(id), params);
+	}
+
+	pub fn register_density_function(&mut self, id: &str, json: Value) {
+		self.density_functions.insert(normalize_id(id), json);
+	}
+
+	pub fn register_noise_settings(&mut self, id: &str, json: Value) {
+		self.noise_settings.insert(normalize_id(id), json);
+	}
+
+	pub fn register_resource(&mut self, kind: &str, id: &str, json: Value) {
+		self.resources.entry(kind.to_string()).or_default().insert(normalize_id(id), json);
+	}
+
+	/// Adds all definitions of `other`, replacing the ones with the same ID.
+	pub fn extend(&mut self, other: Registry) {
+		self.noises.extend(other.noises);
+		self.density_functions.extend(other.density_functions);
+		self.noise_settings.extend(other.noise_settings);
+		for (kind, resources) in other.resources {
+			self.resources.entry(kind).or_default().extend(resources);
+		}
+	}
+
+	pub fn noise(&self, id: &str) -> Option<&NoiseParameters> {
+		self.noises.get(&normalize_id(id))
+	}
+
+	pub fn density_function(&self, id: &str) -> Option<&Value> {
+		self.density_functions.get(&normalize_id(id))
+	}
+
+	pub fn noise_settings(&self, id: &str) -> Option<&Value> {
+		self.noise_settings.get(&normalize_id(id))
+	}
+
+	pub fn resource(&self, kind: &str, id: &str) -> Option<&Value> {
+		self.resources.get(kind)?.get(&normalize_id(id))
+	}
+
+	/// The sorted IDs of all definitions of a kind.
+	pub fn resource_ids(&self, kind: &str) -> Vec<&str> {
+		let mut ids = self.resources.get(kind).map(|r| r.keys().map(String::as_str).collect::<Vec<_>>()).unwrap_or_default();
+		ids.sort_unstable();
+		ids
+	}
+
+	/// Expands an ID, a `#` tag or a list of either into the IDs it contains.
+	/// `kind` is the kind of the values, like `worldgen/biome`. Tag entries
+	/// that aren't required are skipped when they don't exist.
+	pub fn resolve_tag(&self, kind: &str, json: &Value) -> Result<Vec<String>, String> {
+		let mut result = Vec::new();
+		self.resolve_tag_into(kind, json, true, &mut result)?;
+		Ok(result)
+	}
+
+	fn resolve_tag_into(&self, kind: &str, json: &Value, required: bool, result: &mut Vec<String>) -> Result<(), String> {
+		match json {
+			Value::String(id) => match id.strip_prefix('#') {
+				Some(tag) => match self.resource(&format!("tags/{}", kind), tag) {
+					Some(tag) => self.resolve_tag_into(kind, tag.get("values").ok_or("Tag needs values")?, true, result)?,
+					None if required => return Err(format!("Unknown tag #{}", normalize_id(tag))),
+					None => {},
+				},
+				None => {
+					let id = normalize_id(id);
+					if !result.contains(&id) {
+						result.push(id);
+					}
+				},
+			},
+			Value::Array(values) => {
+				for value in values {
+					self.resolve_tag_into(kind, value, true, result)?;
+				}
+			},
+			Value::Object(entry) => {
+				let required = entry.get("required").and_then(Value::as_bool).unwrap_or(true);
+				self.resolve_tag_into(kind, entry.get("id").ok_or("Tag entries need an id")?, required, result)?;
+			},
+			_ => return Err("Expected an ID, a tag or a list".to_string()),
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::Clamped;
+use super::climate::Biome;
+use super::registry::normalize_id;
+use super::util;
+
+pub type Rgb = [u8; 3];
+
+/// Map colours for the vanilla biomes, close to the ones used by common
+/// biome viewers so that maps look familiar.
+pub const DEFAULT_COLORS: [(&str, Rgb); 64] = [
+	("minecraft:the_void", [0, 0, 0]),
+	("minecraft:plains", [141, 179, 96]),
+	("minecraft:sunflower_plains", [181, 219, 136]),
+	("minecraft:snowy_plains", [255, 255, 255]),
+	("minecraft:ice_spikes", [180, 220, 220]),
+	("minecraft:desert", [250, 148, 24]),
+	("minecraft:swamp", [7, 249, 178]),
+	("minecraft:mangrove_swamp", [44, 204, 142]),
+	("minecraft:forest", [5, 102, 33]),
+	("minecraft:flower_forest", [45, 142, 73]),
+	("minecraft:birch_forest", [48, 116, 68]),
+	("minecraft:dark_forest", [64, 81, 26]),
+	("minecraft:old_growth_birch_forest", [88, 156, 108]),
+	("minecraft:old_growth_pine_taiga", [89, 102, 81]),
+	("minecraft:old_growth_spruce_taiga", [129, 142, 121]),
+	("minecraft:taiga", [11, 102, 89]),
+	("minecraft:snowy_taiga", [49, 85, 74]),
+	("minecraft:savanna", [189, 178, 95]),
+	("minecraft:savanna_plateau", [167, 157, 100]),
+	("minecraft:windswept_hills", [96, 96, 96]),
+	("minecraft:windswept_gravelly_hills", [136, 136, 136]),
+	("minecraft:windswept_forest", [80, 112, 80]),
+	("minecraft:windswept_savanna", [229, 218, 135]),
+	("minecraft:jungle", [83, 123, 9]),
+	("minecraft:sparse_jungle", [98, 139, 23]),
+	("minecraft:bamboo_jungle", [118, 142, 20]),
+	("minecraft:badlands", [217, 69, 21]),
+	("minecraft:eroded_badlands", [255, 109, 61]),
+	("minecraft:wooded_badlands", [176, 151, 101]),
+	("minecraft:meadow", [96, 164, 69]),
+	("minecraft:cherry_grove", [255, 145, 200]),
+	("minecraft:grove", [71, 114, 108]),
+	("minecraft:snowy_slopes", [196, 196, 196]),
+	("minecraft:frozen_peaks", [176, 179, 206]),
+	("minecraft:jagged_peaks", [220, 220, 200]),
+	("minecraft:stony_peaks", [123, 143, 116]),
+	("minecraft:river", [0, 0, 255]),
+	("minecraft:frozen_river", [160, 160, 255]),
+	("minecraft:beach", [250, 222, 85]),
+	("minecraft:snowy_beach", [250, 240, 192]),
+	("minecraft:stony_shore", [162, 162, 132]),
+	("minecraft:warm_ocean", [0, 0, 172]),
+	("minecraft:lukewarm_ocean", [0, 0, 144]),
+	("minecraft:deep_lukewarm_ocean", [0, 0, 64]),
+	("minecraft:ocean", [0, 0, 112]),
+	("minecraft:deep_ocean", [0, 0, 48]),
+	("minecraft:cold_ocean", [32, 32, 112]),
+	("minecraft:deep_cold_ocean", [32, 32, 56]),
+	("minecraft:frozen_ocean", [112, 112, 214]),
+	("minecraft:deep_frozen_ocean", [64, 64, 144]),
+	("minecraft:mushroom_fields", [255, 0, 255]),
+	("minecraft:dripstone_caves", [78, 48, 18]),
+	("minecraft:lush_caves", [40, 60, 0]),
+	("minecraft:deep_dark", [3, 31, 41]),
+	("minecraft:nether_wastes", [191, 59, 59]),
+	("minecraft:warped_forest", [73, 144, 123]),
+	("minecraft:crimson_forest", [221, 8, 8]),
+	("minecraft:soul_sand_valley", [82, 41, 33]),
+	("minecraft:basalt_deltas", [64, 54, 54]),
+	("minecraft:the_end", [128, 128, 255]),
+	("minecraft:end_highlands", [181, 181, 54]),
+	("minecraft:end_midlands", [255, 255, 153]),
+	("minecraft:small_end_islands", [75, 75, 171]),
+	("minecraft:end_barrens", [112, 112, 204]),
+];
+
+/// The default map colour of a vanilla biome.
+pub fn default_color(id: &str) -> Option<Rgb> {
+	let id = normalize_id(id);
+	DEFAULT_COLORS.iter().find(|(name, _)| *name == id).map(|(_, color)| *color)
+}
+
+/// A stable colour for biomes without one, so that unknown biomes can
+/// still be told apart.
+fn fallback_color(biome: Biome) -> Rgb {
+	let hash = (biome as u32).wrapping_add(1).wrapping_mul(0x9E37_79B1);
+	[(hash >> 24) as u8, (hash >> 16) as u8, (hash >> 8) as u8]
+}
+
+/// Maps biome IDs to colours.
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default)]
+pub struct Palette {
+	colors: HashMap<Biome, Rgb>,
+}
+
+impl Palette {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Gives each biome the ID of its index in `names`, with the default
+	/// colour for that biome.
+	pub fn from_names<S: AsRef<str>>(names: &[S]) -> Self {
+		let mut palette = Self::new();
+		for (i, name) in names.iter().enumerate() {
+			let biome = i as Biome;
+			palette.set(biome, default_color(name.as_ref()).unwrap_or_else(|| fallback_color(biome)));
+		}
+		palette
+	}
+
+	pub fn set(&mut self, biome: Biome, color: Rgb) {
+		self.colors.insert(biome, color);
+	}
+
+	pub fn get(&self, biome: Biome) -> Rgb {
+		self.colors.get(&biome).copied().unwrap_or_else(|| fallback_color(biome))
+	}
+}
+
+/// Turns a biome grid into an RGBA buffer with one opaque pixel per biome.
+pub fn render_biomes(biomes: &[Biome], palette: &Palette) -> Vec<u8> {
+	let mut rgba = Vec::with_capacity(biomes.len() * 4);
+	for biome in biomes {
+		let [r, g, b] = palette.get(*biome);
+		rgba.extend_from_slice(&[r, g, b, 255]);
+	}
+	rgba
+}
+
+/// Shades an RGBA image by the slope of a heightmap of the same size, lit
+/// from the north-west. `spacing` is the distance in blocks between samples.
+pub fn hillshade(rgba: &mut [u8], heights: &[f64], width: usize, spacing: f64) -> Result<(), String> {
+	if width == 0 || !heights.len().is_multiple_of(width) {
+		return Err(format!("Heightmap of {} samples can't have width {}", heights.len(), width))
+	}
+	let slopes = heights.iter().enumerate().map(|(i, &height)| {
+		let west = if i % width > 0 { heights[i - 1] } else { height };
+		let north = if i >= width { heights[i - width] } else { height };
+		(2.0 * height - west - north) / spacing
+	}).collect::<Vec<_>>();
+	shade_slopes(rgba, &slopes)
+}
+
+/// Shades an RGBA image like [`hillshade`], from the slope at every pixel
+/// instead of a heightmap. The slope is the sum of the derivatives of the
+/// height along x and z, for example from a noise's analytic gradient.
+pub fn shade_slopes(rgba: &mut [u8], slopes: &[f64]) -> Result<(), String> {
+	if rgba.len() != slopes.len() * 4 {
+		return Err(format!("{} slopes don't match an image of {} pixels", slopes.len(), rgba.len() / 4))
+	}
+	for (i, &slope) in slopes.iter().enumerate() {
+		let factor = 1.0 + (slope * 0.25).clamp(-0.4, 0.4);
+		for channel in &mut rgba[i * 4..i * 4 + 3] {
+			*channel = (*channel as f64 * factor).round().min(255.0) as u8;
+		}
+	}
+	Ok(())
+}
+
+/// Encodes an RGBA buffer as a PNG image.
+#[cfg(feature = "png")]
+pub fn encode_png(rgba: &[u8], width: u32, height: u32) -> Result<Vec<u8>, String> {
+	let mut data = Vec::new();
+	let mut encoder = png::Encoder::new(&mut data, width, height);
+	encoder.set_color(png::ColorType::Rgba);
+	encoder.set_depth(png::BitDepth::Eight);
+	let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+	writer.write_image_data(rgba).map_err(|e| e.to_string())?;
+	writer.finish().map_err(|e| e.to_string())?;
+	Ok(data)
+}
+
+/// Colour ramps for rendering noise values, from low to high.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorRamp {
+	Greyscale,
+	Viridis,
+	Coolwarm,
+	Spectral,
+}
+
+impl ColorRamp {
+	fn stops(&self) -> &'static [Rgb] {
+		match self {
+			ColorRamp::Greyscale => &[[0, 0, 0], [255, 255, 255]],
+			ColorRamp::Viridis => &[[68, 1, 84], [59, 82, 139], [33, 145, 140], [94, 201, 98], [253, 231, 37]],
+			ColorRamp::Coolwarm => &[[59, 76, 192], [221, 221, 221], [180, 4, 38]],
+			ColorRamp::Spectral => &[[94, 79, 162], [50, 136, 189], [102, 194, 165], [171, 221, 164], [230, 245, 152], [254, 224, 139], [253, 174, 97], [244, 109, 67], [213, 62, 79], [158, 1, 66]],
+		}
+	}
+
+	/// The colour at `t`, which is clamped between 0 and 1.
+	pub fn get(&self, t: f64) -> Rgb {
+		let stops = self.stops();
+		let scaled = t.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+		let i = (scaled.floor() as usize).min(stops.len() - 2);
+		let f = scaled - i as f64;
+		let mut color = [0; 3];
+		for c in 0..3 {
+			color[c] = util::lerp(f, stops[i][c] as f64, stops[i + 1][c] as f64).round() as u8;
+		}
+		color
+	}
+}
+
+/// Summary statistics of the finite values in a grid.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct NoiseStats {
+	min: f64,
+	max: f64,
+	mean: f64,
+	stddev: f64,
+	count: usize,
+	range: (f64, f64),
+	histogram: Vec<u32>,
+}
+
+impl NoiseStats {
+	/// Computes the statistics of `values`, with a histogram of `bins` equal
+	/// bins. The histogram covers the range between `min` and `max`, which
+	/// default to the smallest and largest value. Values outside the range
+	/// are counted in the outermost bins.
+	pub fn new(values: &[f64], bins: usize, min: Option<f64>, max: Option<f64>) -> Self {
+		let finite = || values.iter().copied().filter(|v| v.is_finite());
+		let count = finite().count();
+		let value_min = finite().fold(f64::NAN, f64::min);
+		let value_max = finite().fold(f64::NAN, f64::max);
+		let mean = finite().sum::<f64>() / count as f64;
+		let variance = finite().map(|v| (v - mean) * (v - mean)).sum::<f64>() / count as f64;
+		let range = (min.unwrap_or(value_min), max.unwrap_or(value_max));
+		let mut histogram = vec![0; bins];
+		if bins > 0 {
+			for value in finite() {
+				let bin = (normalize(value, range) * bins as f64).floor() as usize;
+				histogram[bin.min(bins - 1)] += 1;
+			}
+		}
+		Self { min: value_min, max: value_max, mean, stddev: variance.sqrt(), count, range, histogram }
+	}
+
+	pub fn range(&self) -> (f64, f64) {
+		self.range
+	}
+}
+
+#[wasm_bindgen]
+impl NoiseStats {
+	#[wasm_bindgen(getter)]
+	pub fn min(&self) -> f64 {
+		self.min
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn max(&self) -> f64 {
+		self.max
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn mean(&self) -> f64 {
+		self.mean
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn stddev(&self) -> f64 {
+		self.stddev
+	}
+
+	/// The number of finite values.
+	#[wasm_bindgen(getter)]
+	pub fn count(&self) -> usize {
+		self.count
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn histogram(&self) -> Vec<u32> {
+		self.histogram.clone()
+	}
+
+	/// The lower bound of the range used for the histogram and colours.
+	#[wasm_bindgen(getter)]
+	pub fn range_min(&self) -> f64 {
+		self.range.0
+	}
+
+	/// The upper bound of the range used for the histogram and colours.
+	#[wasm_bindgen(getter)]
+	pub fn range_max(&self) -> f64 {
+		self.range.1
+	}
+}
+
+/// Maps a value to 0 at the start of the range and 1 at the end. Empty
+/// ranges map everything to the middle.
+fn normalize(value: f64, (min, max): (f64, f64)) -> f64 {
+	if max > min { ((value - min) / (max - min)).clamp(0.0, 1.0) } else { 0.5 }
+}
+
+/// Turns a noise grid into an RGBA buffer, colouring values by where they
+/// fall in `range`. Values that aren't finite become transparent pixels.
+pub fn render_heatmap(values: &[f64], ramp: ColorRamp, range: (f64, f64)) -> Vec<u8> {
+	let mut rgba = Vec::with_capacity(values.len() * 4);
+	for value in values {
+		if value.is_finite() {
+			let [r, g, b] = ramp.get(normalize(*value, range));
+			rgba.extend_from_slice(&[r, g, b, 255]);
+		} else {
+			rgba.extend_from_slice(&[0, 0, 0, 0]);
+		}
+	}
+	rgba
+}
+
+/// A rendered noise grid together with its statistics.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct Heatmap {
+	rgba: Vec<u8>,
+	stats: NoiseStats,
+}
+
+impl Heatmap {
+	/// Renders `values` with a fixed range where `min` or `max` are given,
+	/// and the range of the values otherwise.
+	pub fn new(values: &[f64], ramp: ColorRamp, min: Option<f64>, max: Option<f64>, bins: usize) -> Self {
+		let stats = NoiseStats::new(values, bins, min, max);
+		Self { rgba: render_heatmap(values, ramp, stats.range), stats }
+	}
+
+	pub fn pixels(&self) -> &[u8] {
+		&self.rgba
+	}
+}
+
+#[wasm_bindgen]
+impl Heatmap {
+	#[wasm_bindgen(getter)]
+	pub fn rgba(&self) -> Clamped<Vec<u8>> {
+		Clamped(self.rgba.clone())
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn stats(&self) -> NoiseStats {
+		self.stats.clone()
+	}
+}
+
+/// Extracts one channel from a grid with `stride` interleaved values per
+/// sample, such as the output of `climate_noise`.
+pub fn channel(values: &[f64], stride: usize, index: usize) -> Result<Vec<f64>, String> {
+	if index >= stride {
+		return Err(format!("Channel {} is out of bounds for {} channels", index, stride))
+	}
+	Ok(values.iter().skip(index).step_by(stride).copied().collect())
+}
@@ -0,0 +1,134 @@
+use std::sync::Arc;
+use wasm_bindgen::prelude::*;
+use serde_json::Value;
+use super::density::{ Context, DensityFunction, Loader };
+use super::registry::Registry;
+
+#[derive(Clone, Debug)]
+pub struct NoiseSettings {
+	pub min_y: i32,
+	pub height: i32,
+	pub size_horizontal: i32,
+	pub size_vertical: i32,
+}
+
+/// The parts of a `noise_settings` definition needed to build a router.
+#[derive(Clone, Debug)]
+pub struct NoiseGeneratorSettings {
+	pub sea_level: i32,
+	pub legacy_random_source: bool,
+	pub noise: NoiseSettings,
+	pub noise_router: Value,
+}
+
+impl NoiseGeneratorSettings {
+	pub fn from_json(json: &Value) -> Result<Self, String> {
+		let noise = json.get("noise").ok_or("Noise settings need noise")?;
+		let int = |json: &Value, key: &str| json.get(key).and_then(Value::as_i64).map(|v| v as i32)
+			.ok_or_else(|| format!("Noise settings need {}", key));
+		Ok(Self {
+			sea_level: int(json, "sea_level")?,
+			legacy_random_source: json.get("legacy_random_source").and_then(Value::as_bool).unwrap_or(false),
+			noise: NoiseSettings {
+				min_y: int(noise, "min_y")?,
+				height: int(noise, "height")?,
+				size_horizontal: int(noise, "size_horizontal")?,
+				size_vertical: int(noise, "size_vertical")?,
+			},
+			noise_router: json.get("noise_router").ok_or("Noise settings need a noise_router")?.clone(),
+		})
+	}
+}
+
+/// The named density functions of a noise generator, seeded for one world.
+/// Outputs are shared, so cloning a router is cheap.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct NoiseRouter {
+	barrier: Arc<DensityFunction>,
+	fluid_level_floodedness: Arc<DensityFunction>,
+	fluid_level_spread: Arc<DensityFunction>,
+	lava: Arc<DensityFunction>,
+	temperature: Arc<DensityFunction>,
+	vegetation: Arc<DensityFunction>,
+	continents: Arc<DensityFunction>,
+	erosion: Arc<DensityFunction>,
+	depth: Arc<DensityFunction>,
+	ridges: Arc<DensityFunction>,
+	initial_density_without_jaggedness: Arc<DensityFunction>,
+	final_density: Arc<DensityFunction>,
+	vein_toggle: Arc<DensityFunction>,
+	vein_ridged: Arc<DensityFunction>,
+	vein_gap: Arc<DensityFunction>,
+}
+
+impl NoiseRouter {
+	pub const OUTPUTS: [&'static str; 15] = [
+		"barrier",
+		"fluid_level_floodedness",
+		"fluid_level_spread",
+		"lava",
+		"temperature",
+		"vegetation",
+		"continents",
+		"erosion",
+		"depth",
+		"ridges",
+		"initial_density_without_jaggedness",
+		"final_density",
+		"vein_toggle",
+		"vein_ridged",
+		"vein_gap",
+	];
+
+	pub fn new(settings: &NoiseGeneratorSettings, registry: &Registry, seed: i64) -> Result<Self, String> {
+		let mut loader = Loader::new(registry, seed, settings.legacy_random_source);
+		let json = &settings.noise_router;
+		let mut output = |name: &str| match json.get(name) {
+			Some(function) => loader.parse(function).map(Arc::new).map_err(|e| format!("Error in {}: {}", name, e)),
+			None => Ok(Arc::new(DensityFunction::Constant(0.0))),
+		};
+		Ok(Self {
+			barrier: output("barrier")?,
+			fluid_level_floodedness: output("fluid_level_floodedness")?,
+			fluid_level_spread: output("fluid_level_spread")?,
+			lava: output("lava")?,
+			temperature: output("temperature")?,
+			vegetation: output("vegetation")?,
+			continents: output("continents")?,
+			erosion: output("erosion")?,
+			depth: output("depth")?,
+			ridges: output("ridges")?,
+			initial_density_without_jaggedness: output("initial_density_without_jaggedness")?,
+			final_density: output("final_density")?,
+			vein_toggle: output("vein_toggle")?,
+			vein_ridged: output("vein_ridged")?,
+			vein_gap: output("vein_gap")?,
+		})
+	}
+
+	pub fn get(&self, name: &str) -> Option<&Arc<DensityFunction>> {
+		match name {
+			"barrier" => Some(&self.barrier),
+			"fluid_level_floodedness" => Some(&self.fluid_level_floodedness),
+			"fluid_level_spread" => Some(&self.fluid_level_spread),
+			"lava" => Some(&self.lava),
+			"temperature" => Some(&self.temperature),
+			"vegetation" => Some(&self.vegetation),
+			"continents" => Some(&self.continents),
+			"erosion" => Some(&self.erosion),
+			"depth" => Some(&self.depth),
+			"ridges" => Some(&self.ridges),
+			"initial_density_without_jaggedness" => Some(&self.initial_density_without_jaggedness),
+			"final_density" => Some(&self.final_density),
+			"vein_toggle" => Some(&self.vein_toggle),
+			"vein_ridged" => Some(&self.vein_ridged),
+			"vein_gap" => Some(&self.vein_gap),
+			_ => None,
+		}
+	}
+
+	pub fn compute(&self, name: &str, x: i32, y: i32, z: i32) -> Option<f64> {
+		self.get(name).map(|function| function.compute(&Context::new(x, y, z)))
+	}
+}
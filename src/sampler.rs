@@ -1,57 +1,106 @@
+use std::sync::Arc;
 use wasm_bindgen::prelude::*;
-use super::random::{ LegacyRandomSource as Random };
-use super::noise::{ NormalNoise, NoiseParameters };
-use super::climate::{ TargetPoint };
-
-#[wasm_bindgen]
-pub struct NoiseOctaves {
-	temperature: NoiseParameters,
-	humidity: NoiseParameters,
-	continentalness: NoiseParameters,
-	erosion: NoiseParameters,
-	weirdness: NoiseParameters,
-	shift: NoiseParameters,
-}
-
-impl NoiseOctaves {
-	pub fn new(temperature: NoiseParameters, humidity: NoiseParameters, continentalness: NoiseParameters, erosion: NoiseParameters, weirdness: NoiseParameters, shift: NoiseParameters ) -> Self {
-		Self { temperature, humidity, continentalness, erosion, weirdness, shift }
-	}
-}
+use super::density::{ Context, DensityFunction };
+use super::router::NoiseRouter;
+use super::climate::{ BiomeExplanation, ParamPoint, ParameterList, TargetPoint };
 
+/// Samples the climate parameters of a router. Cloning a sampler is cheap,
+/// as the density functions are shared with the router.
 #[wasm_bindgen]
+#[derive(Clone)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
 pub struct Sampler {
-	temperature_noise: NormalNoise,
-	humidity_noise: NormalNoise,
-	continentalness_noise: NormalNoise,
-	erosion_noise: NormalNoise,
-	weirdness_noise: NormalNoise,
-	offset_noise: NormalNoise,
+	temperature: Arc<DensityFunction>,
+	humidity: Arc<DensityFunction>,
+	continentalness: Arc<DensityFunction>,
+	erosion: Arc<DensityFunction>,
+	depth: Arc<DensityFunction>,
+	weirdness: Arc<DensityFunction>,
 }
 
 impl Sampler {
-	pub fn new(seed: i64, octaves: &NoiseOctaves) -> Self {
+	pub fn new(router: &NoiseRouter) -> Self {
+		let output = |name| router.get(name).expect("Router is missing a climate output").clone();
 		Self {
-			temperature_noise: NormalNoise::new(&mut Random::new(seed), &octaves.temperature),
-			humidity_noise: NormalNoise::new(&mut Random::new(seed + 1), &octaves.humidity),
-			continentalness_noise: NormalNoise::new(&mut Random::new(seed + 2), &octaves.continentalness),
-			erosion_noise: NormalNoise::new(&mut Random::new(seed + 3), &octaves.erosion),
-			weirdness_noise: NormalNoise::new(&mut Random::new(seed + 4), &octaves.weirdness),
-			offset_noise: NormalNoise::new(&mut Random::new(seed + 5), &octaves.shift),
+			temperature: output("temperature"),
+			humidity: output("vegetation"),
+			continentalness: output("continents"),
+			erosion: output("erosion"),
+			depth: output("depth"),
+			weirdness: output("ridges"),
 		}
 	}
 
-	pub fn target(&self, x: i64, _y: i64, z: i64) -> TargetPoint {
-		let xx = x as f64 + self.offset_noise.sample(x as f64, 0.0, z as f64) * 4.0;
-		let zz = z as f64 + self.offset_noise.sample(z as f64, x as f64, 0.0) * 4.0;
+	/// Checks that the density functions of a restored sampler are
+	/// consistent, so that sampling it can't panic.
+	#[cfg(feature = "snapshot")]
+	pub(crate) fn check(&self) -> Result<(), String> {
+		[&self.temperature, &self.humidity, &self.continentalness, &self.erosion, &self.depth, &self.weirdness]
+			.iter().try_for_each(|function| function.check())
+	}
 
+	pub fn target(&self, x: i32, y: i32, z: i32) -> TargetPoint {
+		let context = Context::new(x, y, z);
 		TargetPoint::new(
-			self.temperature_noise.sample(xx, 0.0, zz),
-			self.humidity_noise.sample(xx, 0.0, zz),
-			self.continentalness_noise.sample(xx, 0.0, zz),
-			self.erosion_noise.sample(xx, 0.0, zz),
-			self.weirdness_noise.sample(xx, 0.0, zz),
-			0.0, // depth is set to 0 until terrainshaper is implemented
+			self.temperature.compute(&context),
+			self.humidity.compute(&context),
+			self.continentalness.compute(&context),
+			self.erosion.compute(&context),
+			self.weirdness.compute(&context),
+			self.depth.compute(&context),
 		)
 	}
+
+	/// The erosion at a block position, without sampling the rest of the climate.
+	pub fn erosion(&self, x: i32, y: i32, z: i32) -> f64 {
+		self.erosion.compute(&Context::new(x, y, z))
+	}
+
+	/// Samples the climate at a quart position, like vanilla's `Climate.Sampler`.
+	pub fn sample(&self, qx: i32, qy: i32, qz: i32) -> TargetPoint {
+		self.target(qx << 2, qy << 2, qz << 2)
+	}
+
+	/// Explains the biome that a parameter list chooses at a block position.
+	pub fn explain(&self, parameters: &ParameterList, x: i32, y: i32, z: i32, count: usize) -> BiomeExplanation {
+		parameters.explain(self.target(x, y, z), count)
+	}
+
+	/// Finds the block position closest to the origin whose surface climate
+	/// best matches one of `targets`, like vanilla's `Climate.findSpawnPosition`.
+	/// The game then searches this position's surroundings for a valid block.
+	pub fn find_spawn(&self, targets: &[ParamPoint]) -> (i32, i32) {
+		let mut result = self.spawn_fitness(targets, 0, 0);
+		result = self.spawn_radial_search(targets, result, 2048.0, 512.0);
+		result = self.spawn_radial_search(targets, result, 512.0, 32.0);
+		(result.0, result.1)
+	}
+
+	fn spawn_radial_search(&self, targets: &[ParamPoint], mut result: (i32, i32, i64), max_radius: f32, radius_increment: f32) -> (i32, i32, i64) {
+		let (center_x, center_z, _) = result;
+		let mut angle = 0.0_f32;
+		let mut radius = radius_increment;
+		while radius <= max_radius {
+			let x = center_x + ((angle as f64).sin() * radius as f64) as i32;
+			let z = center_z + ((angle as f64).cos() * radius as f64) as i32;
+			let candidate = self.spawn_fitness(targets, x, z);
+			if candidate.2 < result.2 {
+				result = candidate;
+			}
+			angle += radius_increment / radius;
+			if angle as f64 > std::f64::consts::PI * 2.0 {
+				angle = 0.0;
+				radius += radius_increment;
+			}
+		}
+		result
+	}
+
+	fn spawn_fitness(&self, targets: &[ParamPoint], x: i32, z: i32) -> (i32, i32, i64) {
+		let distance = ((x as i64 * x as i64 + z as i64 * z as i64) as f64 / (2500.0 * 2500.0)).powi(2);
+		let distance_fitness = (10000.0_f32 * 10000.0_f32) as f64 * distance;
+		let surface = self.sample(x >> 2, 0, z >> 2).at_surface();
+		let climate_fitness = targets.iter().map(|point| point.fitness(&surface)).min().unwrap_or(i64::MAX);
+		(x, z, (distance_fitness as i64).wrapping_add(climate_fitness))
+	}
 }
@@ -0,0 +1,160 @@
+//! A small vector of `f64` lanes for sampling several noise positions at
+//! once. Every operation is applied lane by lane in the same order as the
+//! scalar code in [`util`](super::util), so batched results are bit for bit
+//! identical to sampling each position on its own.
+//!
+//! The portable implementation is a fixed size array that the compiler
+//! vectorizes for the target. On wasm with `simd128` enabled the arithmetic
+//! uses explicit `f64x2` instructions, two at a time.
+
+use std::ops::{ Add, Div, Mul, Sub };
+
+/// The number of positions that are sampled together.
+pub const LANES: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct F64x4(pub [f64; LANES]);
+
+impl F64x4 {
+	#[inline(always)]
+	pub fn splat(value: f64) -> Self {
+		Self([value; LANES])
+	}
+
+	#[inline(always)]
+	pub fn from_fn<F: FnMut(usize) -> f64>(f: F) -> Self {
+		Self(std::array::from_fn(f))
+	}
+
+	#[inline(always)]
+	pub fn map<F: Fn(f64) -> f64>(self, f: F) -> Self {
+		Self(self.0.map(f))
+	}
+
+	#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+	#[inline(always)]
+	fn zip<F: Fn(f64, f64) -> f64>(self, other: Self, f: F) -> Self {
+		Self::from_fn(|i| f(self.0[i], other.0[i]))
+	}
+
+	#[cfg(not(any(all(target_arch = "wasm32", target_feature = "simd128"), all(any(target_arch = "x86", target_arch = "x86_64"), not(target_feature = "sse4.1")))))]
+	#[inline(always)]
+	pub fn floor(self) -> Self {
+		self.map(f64::floor)
+	}
+
+	/// Without SSE4.1 `f64::floor` is a call into libm for every lane. This
+	/// rounds with the `2^52` trick instead, which only needs additions and
+	/// comparisons and gives exactly the same results, including the sign of
+	/// zero. Values that are too large to have a fraction are returned as is.
+	#[cfg(all(any(target_arch = "x86", target_arch = "x86_64"), not(target_feature = "sse4.1")))]
+	#[inline(always)]
+	pub fn floor(self) -> Self {
+		const TWO_52: f64 = 4503599627370496.0;
+		self.map(|x| {
+			let magic = TWO_52.copysign(x);
+			let rounded = (x + magic) - magic;
+			let floor = (if rounded > x { rounded - 1.0 } else { rounded }).copysign(x);
+			if x.abs() < TWO_52 { floor } else { x }
+		})
+	}
+
+	#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+	#[inline(always)]
+	fn zip<F: Fn(core::arch::wasm32::v128, core::arch::wasm32::v128) -> core::arch::wasm32::v128>(self, other: Self, f: F) -> Self {
+		use core::arch::wasm32::*;
+		let low = f(f64x2(self.0[0], self.0[1]), f64x2(other.0[0], other.0[1]));
+		let high = f(f64x2(self.0[2], self.0[3]), f64x2(other.0[2], other.0[3]));
+		Self([f64x2_extract_lane::<0>(low), f64x2_extract_lane::<1>(low), f64x2_extract_lane::<0>(high), f64x2_extract_lane::<1>(high)])
+	}
+
+	#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+	#[inline(always)]
+	pub fn floor(self) -> Self {
+		use core::arch::wasm32::*;
+		self.zip(self, |a, _| f64x2_floor(a))
+	}
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+macro_rules! lane_op {
+	($trait:ident, $method:ident, $op:tt, $simd:ident) => {
+		impl $trait for F64x4 {
+			type Output = Self;
+
+			#[inline(always)]
+			fn $method(self, other: Self) -> Self {
+				self.zip(other, |a, b| a $op b)
+			}
+		}
+	};
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+macro_rules! lane_op {
+	($trait:ident, $method:ident, $op:tt, $simd:ident) => {
+		impl $trait for F64x4 {
+			type Output = Self;
+
+			#[inline(always)]
+			fn $method(self, other: Self) -> Self {
+				self.zip(other, |a, b| core::arch::wasm32::$simd(a, b))
+			}
+		}
+	};
+}
+
+lane_op!(Add, add, +, f64x2_add);
+lane_op!(Sub, sub, -, f64x2_sub);
+lane_op!(Mul, mul, *, f64x2_mul);
+lane_op!(Div, div, /, f64x2_div);
+
+/// Mixed operations with a scalar on the right, which is splatted first.
+macro_rules! scalar_op {
+	($trait:ident, $method:ident) => {
+		impl $trait<f64> for F64x4 {
+			type Output = Self;
+
+			#[inline(always)]
+			fn $method(self, other: f64) -> Self {
+				self.$method(Self::splat(other))
+			}
+		}
+	};
+}
+
+scalar_op!(Add, add);
+scalar_op!(Sub, sub);
+scalar_op!(Mul, mul);
+scalar_op!(Div, div);
+
+/// See [`util::lerp`](super::util::lerp).
+#[inline(always)]
+pub fn lerp(a: F64x4, b: F64x4, c: F64x4) -> F64x4 {
+	b + a * (c - b)
+}
+
+/// See [`util::lerp2`](super::util::lerp2).
+#[inline(always)]
+pub fn lerp2(a: F64x4, b: F64x4, c: F64x4, d: F64x4, e: F64x4, f: F64x4) -> F64x4 {
+	lerp(b, lerp(a, c, d), lerp(a, e, f))
+}
+
+/// See [`util::lerp3`](super::util::lerp3).
+#[allow(clippy::too_many_arguments)]
+#[inline(always)]
+pub fn lerp3(a: F64x4, b: F64x4, c: F64x4, d: F64x4, e: F64x4, f: F64x4, g: F64x4, h: F64x4, i: F64x4, j: F64x4, k: F64x4) -> F64x4 {
+	lerp(c, lerp2(a, b, d, e, f, g), lerp2(a, b, h, i, j, k))
+}
+
+/// See [`util::smoothstep`](super::util::smoothstep).
+#[inline(always)]
+pub fn smoothstep(x: F64x4) -> F64x4 {
+	x * x * x * (x * (x * 6.0 - 15.0) + 10.0)
+}
+
+/// See [`util::wrap`](super::util::wrap).
+#[inline(always)]
+pub fn wrap(value: F64x4) -> F64x4 {
+	value - (value / 3.3554432e7 + 0.5).floor() * 3.3554432e7
+}
@@ -0,0 +1,113 @@
+//! Compact binary snapshots of seeded noises, climate samplers and built
+//! parameter lists, enabled with the `snapshot` feature. Restoring a
+//! snapshot skips seeding and tree construction entirely, so a worker can
+//! receive the result of another's work instead of repeating it.
+//!
+//! A snapshot starts with a header naming its type and format version, so
+//! that loading one as the wrong type, or one written by an incompatible
+//! version of this crate, fails with an error instead of giving garbage.
+//! Density functions shared between several outputs are stored once per
+//! use, and are no longer shared after restoring.
+
+use std::convert::TryInto;
+use serde::de::{ self, DeserializeOwned, Visitor };
+use serde::{ Deserializer, Serialize, Serializer };
+use super::climate::ParameterList;
+use super::noise::{ ImprovedNoise, NormalNoise, PerlinNoise };
+use super::sampler::Sampler;
+
+const MAGIC: &[u8; 4] = b"DSRS";
+const VERSION: u16 = 1;
+
+pub trait Snapshot: Serialize + DeserializeOwned {
+	/// The name of the type in the header.
+	const KIND: &'static str;
+
+	/// Checks the invariants of a restored value that deserialization can't.
+	fn validate(&self) -> Result<(), String> {
+		Ok(())
+	}
+
+	fn to_snapshot(&self) -> Result<Vec<u8>, String> {
+		let mut bytes = Vec::from(&MAGIC[..]);
+		bytes.extend_from_slice(&VERSION.to_le_bytes());
+		bytes.push(Self::KIND.len() as u8);
+		bytes.extend_from_slice(Self::KIND.as_bytes());
+		bincode::serialize_into(&mut bytes, self).map_err(|e| e.to_string())?;
+		Ok(bytes)
+	}
+
+	fn from_snapshot(bytes: &[u8]) -> Result<Self, String> {
+		let rest = bytes.strip_prefix(&MAGIC[..]).ok_or("Not a snapshot")?;
+		if rest.len() < 3 {
+			return Err("Snapshot is truncated".to_string())
+		}
+		let version = u16::from_le_bytes([rest[0], rest[1]]);
+		if version != VERSION {
+			return Err(format!("Unsupported snapshot version {}, expected {}", version, VERSION))
+		}
+		let (kind, data) = rest[3..].split_at((rest[2] as usize).min(rest.len() - 3));
+		if kind != Self::KIND.as_bytes() {
+			return Err(format!("Snapshot contains {}, expected {}", String::from_utf8_lossy(kind), Self::KIND))
+		}
+		let value: Self = bincode::deserialize(data).map_err(|e| format!("Invalid snapshot: {}", e))?;
+		value.validate()?;
+		Ok(value)
+	}
+}
+
+impl Snapshot for ImprovedNoise {
+	const KIND: &'static str = "improved_noise";
+}
+
+impl Snapshot for PerlinNoise {
+	const KIND: &'static str = "perlin_noise";
+}
+
+impl Snapshot for NormalNoise {
+	const KIND: &'static str = "normal_noise";
+}
+
+impl Snapshot for Sampler {
+	const KIND: &'static str = "sampler";
+
+	fn validate(&self) -> Result<(), String> {
+		self.check()
+	}
+}
+
+impl Snapshot for ParameterList {
+	const KIND: &'static str = "parameter_list";
+
+	fn validate(&self) -> Result<(), String> {
+		self.check()
+	}
+}
+
+/// Serializes a permutation table as a byte string, as serde only supports
+/// arrays of up to 32 elements.
+pub(crate) mod permutation {
+	use super::*;
+
+	pub fn serialize<S: Serializer>(table: &[u8; 256], serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_bytes(table)
+	}
+
+	pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 256], D::Error> {
+		deserializer.deserialize_bytes(TableVisitor)
+	}
+
+	struct TableVisitor;
+
+	impl<'de> Visitor<'de> for TableVisitor {
+		type Value = [u8; 256];
+
+		fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+			formatter.write_str("256 bytes")
+		}
+
+		fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+			bytes.try_into().map_err(|_| E::invalid_length(bytes.len(), &self))
+		}
+	}
+}
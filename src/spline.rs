@@ -0,0 +1,90 @@
+use serde_json::Value;
+use super::density::{ Context, DensityFunction, Loader };
+
+#[derive(Clone)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
+pub enum CubicSpline {
+	Constant(f32),
+	Multipoint {
+		coordinate: Box<DensityFunction>,
+		locations: Vec<f32>,
+		values: Vec<CubicSpline>,
+		derivatives: Vec<f32>,
+	},
+}
+
+impl CubicSpline {
+	pub fn parse(json: &Value, loader: &mut Loader) -> Result<Self, String> {
+		if let Some(value) = json.as_f64() {
+			return Ok(CubicSpline::Constant(value as f32))
+		}
+		let coordinate = loader.parse(json.get("coordinate").ok_or("Spline needs a coordinate")?)?;
+		let points = json.get("points").and_then(Value::as_array).ok_or("Spline needs points")?;
+		if points.is_empty() {
+			return Err("Spline needs at least one point".to_string())
+		}
+		let mut locations = Vec::with_capacity(points.len());
+		let mut values = Vec::with_capacity(points.len());
+		let mut derivatives = Vec::with_capacity(points.len());
+		for point in points {
+			locations.push(point.get("location").and_then(Value::as_f64).ok_or("Spline point needs a location")? as f32);
+			values.push(CubicSpline::parse(point.get("value").ok_or("Spline point needs a value")?, loader)?);
+			derivatives.push(point.get("derivative").and_then(Value::as_f64).ok_or("Spline point needs a derivative")? as f32);
+		}
+		Ok(CubicSpline::Multipoint { coordinate: Box::new(coordinate), locations, values, derivatives })
+	}
+
+	pub fn apply(&self, context: &Context) -> f32 {
+		match self {
+			CubicSpline::Constant(value) => *value,
+			CubicSpline::Multipoint { coordinate, locations, values, derivatives } => {
+				let f = coordinate.compute(context) as f32;
+				let last = locations.len() - 1;
+				let start = locations.partition_point(|&l| f >= l || f.is_nan());
+				if start == 0 {
+					return Self::linear_extend(f, locations, values[0].apply(context), derivatives, 0)
+				}
+				let i = start - 1;
+				if i == last {
+					return Self::linear_extend(f, locations, values[last].apply(context), derivatives, last)
+				}
+				let k = locations[i];
+				let l = locations[i + 1];
+				let m = (f - k) / (l - k);
+				let p = values[i].apply(context);
+				let q = values[i + 1].apply(context);
+				let r = derivatives[i] * (l - k) - (q - p);
+				let s = -derivatives[i + 1] * (l - k) + (q - p);
+				lerp(m, p, q) + m * (1.0 - m) * lerp(m, r, s)
+			},
+		}
+	}
+
+	/// Checks that a restored spline has a value and derivative for every
+	/// location, so that applying it can't index out of bounds.
+	#[cfg(feature = "snapshot")]
+	pub(crate) fn check(&self) -> Result<(), String> {
+		match self {
+			CubicSpline::Constant(_) => Ok(()),
+			CubicSpline::Multipoint { coordinate, locations, values, derivatives } => {
+				if locations.is_empty() || values.len() != locations.len() || derivatives.len() != locations.len() {
+					return Err("Snapshot contains a spline with mismatched points".to_string())
+				}
+				if locations.iter().chain(derivatives).any(|v| !v.is_finite()) {
+					return Err("Snapshot contains a spline with non-finite points".to_string())
+				}
+				coordinate.check()?;
+				values.iter().try_for_each(CubicSpline::check)
+			},
+		}
+	}
+
+	fn linear_extend(f: f32, locations: &[f32], value: f32, derivatives: &[f32], i: usize) -> f32 {
+		let derivative = derivatives[i];
+		if derivative == 0.0 { value } else { value + derivative * (f - locations[i]) }
+	}
+}
+
+fn lerp(a: f32, b: f32, c: f32) -> f32 {
+	b + a * (c - b)
+}
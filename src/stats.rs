@@ -0,0 +1,216 @@
+//! Biome statistics over a region of the world, optionally summed over many
+//! seeds, for comparing changes to a parameter list.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+use wasm_bindgen::prelude::*;
+use super::biome_source::BiomeSource;
+use super::climate::Biome;
+use super::dimension::Dimension;
+use super::sampler::Sampler;
+
+/// A square of block positions around a center at a single y level, sampled
+/// every `stride` blocks. The center is always sampled.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+	center_x: i32,
+	center_z: i32,
+	radius: i32,
+	y: i32,
+	stride: i32,
+}
+
+impl Region {
+	pub fn new(center_x: i32, center_z: i32, radius: i32, y: i32, stride: i32) -> Result<Self, String> {
+		if stride <= 0 {
+			return Err(format!("Region stride {} must be positive", stride))
+		}
+		if radius < 0 {
+			return Err(format!("Region radius {} can't be negative", radius))
+		}
+		Ok(Self { center_x, center_z, radius, y, stride })
+	}
+
+	/// The number of samples along each horizontal axis.
+	pub fn size(&self) -> usize {
+		(self.radius / self.stride * 2 + 1) as usize
+	}
+
+	/// The offset from the center of the `i`th sample along an axis.
+	fn offset(&self, i: usize) -> i32 {
+		(i as i32 - self.radius / self.stride) * self.stride
+	}
+}
+
+/// How often a biome was found, and how close to the center.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BiomeCount {
+	pub count: usize,
+	/// The number of seeds that had this biome at all.
+	pub seeds: usize,
+	/// The smallest distance in blocks from the center to a sample of this
+	/// biome, over all seeds.
+	pub nearest: f64,
+	/// The sum over seeds of the distance to the nearest sample.
+	nearest_sum: f64,
+}
+
+impl BiomeCount {
+	/// The average over the seeds that had this biome of the distance to
+	/// its nearest sample.
+	pub fn mean_nearest(&self) -> f64 {
+		self.nearest_sum / self.seeds as f64
+	}
+}
+
+/// Counts of the biomes in a region, summed over one or more seeds.
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BiomeStats {
+	samples: usize,
+	seeds: usize,
+	biomes: BTreeMap<Biome, BiomeCount>,
+	adjacency: BTreeMap<(Biome, Biome), usize>,
+}
+
+impl BiomeStats {
+	/// Finds the biome at every sample of a region, at the quart position of
+	/// each sampled block like [`Dimension::biome`].
+	pub fn collect(source: &dyn BiomeSource, sampler: &Sampler, region: &Region) -> Self {
+		let size = region.size();
+		let mut grid = Vec::with_capacity(size * size);
+		for iz in 0..size {
+			for ix in 0..size {
+				let (x, z) = (region.center_x + region.offset(ix), region.center_z + region.offset(iz));
+				grid.push(source.get_noise_biome(x >> 2, region.y >> 2, z >> 2, sampler));
+			}
+		}
+
+		let mut stats = Self { samples: grid.len(), seeds: 1, ..Self::default() };
+		for iz in 0..size {
+			for ix in 0..size {
+				let biome = grid[iz * size + ix];
+				let distance = (region.offset(ix) as f64).hypot(region.offset(iz) as f64);
+				let count = stats.biomes.entry(biome).or_insert(BiomeCount { count: 0, seeds: 1, nearest: distance, nearest_sum: distance });
+				count.count += 1;
+				if distance < count.nearest {
+					count.nearest = distance;
+					count.nearest_sum = distance;
+				}
+				for neighbor in [(ix + 1 < size).then(|| grid[iz * size + ix + 1]), (iz + 1 < size).then(|| grid[(iz + 1) * size + ix])].iter().flatten() {
+					if *neighbor != biome {
+						*stats.adjacency.entry((biome.min(*neighbor), biome.max(*neighbor))).or_insert(0) += 1;
+					}
+				}
+			}
+		}
+		stats
+	}
+
+	/// Collects the statistics of a region for every seed in `seeds`, with
+	/// `dimension` loading the dimension of a seed. The dimensions must have
+	/// the same biome source, so that biomes have the same numbers.
+	pub fn over_seeds<F>(region: &Region, seeds: Range<i64>, dimension: F) -> Result<Self, String> where F: Fn(i64) -> Result<Dimension, String> {
+		let mut stats = Self::default();
+		for seed in seeds {
+			let dimension = dimension(seed)?;
+			stats.merge(&Self::collect(dimension.biome_source(), dimension.sampler(), region));
+		}
+		Ok(stats)
+	}
+
+	/// Adds the statistics of other seeds to these.
+	pub fn merge(&mut self, other: &BiomeStats) {
+		self.samples += other.samples;
+		self.seeds += other.seeds;
+		for (biome, other) in &other.biomes {
+			match self.biomes.get_mut(biome) {
+				Some(count) => {
+					count.count += other.count;
+					count.seeds += other.seeds;
+					count.nearest = count.nearest.min(other.nearest);
+					count.nearest_sum += other.nearest_sum;
+				},
+				None => {
+					self.biomes.insert(*biome, *other);
+				},
+			}
+		}
+		for (pair, count) in &other.adjacency {
+			*self.adjacency.entry(*pair).or_insert(0) += count;
+		}
+	}
+
+	pub fn get(&self, biome: Biome) -> Option<&BiomeCount> {
+		self.biomes.get(&biome)
+	}
+
+	/// The share of samples that had a biome.
+	pub fn share(&self, biome: Biome) -> f64 {
+		self.get(biome).map_or(0.0, |count| count.count as f64 / self.samples as f64)
+	}
+
+	/// The number of pairs of neighbouring samples with two different
+	/// biomes, with the smaller biome first.
+	pub fn adjacency(&self) -> &BTreeMap<(Biome, Biome), usize> {
+		&self.adjacency
+	}
+}
+
+#[wasm_bindgen]
+impl BiomeStats {
+	/// The total number of samples over all seeds.
+	#[wasm_bindgen(getter)]
+	pub fn samples(&self) -> usize {
+		self.samples
+	}
+
+	#[wasm_bindgen(getter)]
+	pub fn seeds(&self) -> usize {
+		self.seeds
+	}
+
+	/// The biomes that were found, in ascending order.
+	#[wasm_bindgen(getter)]
+	pub fn biomes(&self) -> Vec<Biome> {
+		self.biomes.keys().copied().collect()
+	}
+
+	/// The number of samples of each biome, in `biomes` order.
+	#[wasm_bindgen(getter)]
+	pub fn counts(&self) -> Vec<u32> {
+		self.biomes.values().map(|c| c.count as u32).collect()
+	}
+
+	/// The share of samples of each biome, in `biomes` order.
+	#[wasm_bindgen(getter)]
+	pub fn shares(&self) -> Vec<f64> {
+		self.biomes.keys().map(|&biome| self.share(biome)).collect()
+	}
+
+	/// The smallest distance to each biome, in `biomes` order.
+	#[wasm_bindgen(getter)]
+	pub fn nearest(&self) -> Vec<f64> {
+		self.biomes.values().map(|c| c.nearest).collect()
+	}
+
+	/// The average distance to each biome over the seeds that had it, in
+	/// `biomes` order.
+	#[wasm_bindgen(getter)]
+	pub fn mean_nearest(&self) -> Vec<f64> {
+		self.biomes.values().map(|c| c.mean_nearest()).collect()
+	}
+
+	/// The pairs of adjacent biomes, flattened.
+	#[wasm_bindgen(getter)]
+	pub fn adjacent_biomes(&self) -> Vec<Biome> {
+		self.adjacency.keys().flat_map(|&(a, b)| [a, b]).collect()
+	}
+
+	/// The number of neighbouring samples of each pair in `adjacent_biomes`.
+	#[wasm_bindgen(getter)]
+	pub fn adjacent_counts(&self) -> Vec<u32> {
+		self.adjacency.values().map(|&c| c as u32).collect()
+	}
+}
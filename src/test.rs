@@ -28,18 +28,197 @@ fn legacy_random_float() {
 #[test]
 fn legacy_random_double() {
   let mut random = random::LegacyRandomSource::new(123);
-  let expected = vec![0.7231741869568761, 0.990898874798736, 0.2532930999562567, 0.6088003568750999, 0.8058694962089253, 0.8754127658344386, 0.7160484954175045, 0.0719170208985256, 0.7962609541776712, 0.5787169245060814];
+  let expected = vec![0.7231742029971469, 0.9908988967772393, 0.25329310557439133, 0.6088003703785169, 0.8058695140834087, 0.8754127852514174, 0.7160485112997248, 0.07191702249367171, 0.7962609718390335, 0.5787169373422367];
   let actual = (0..10).map(|_| random.next_double()).collect::<Vec<_>>();
   assert_eq!(actual, expected);
 }
 
+#[test]
+fn legacy_random_gaussian() {
+  let mut random = random::LegacyRandomSource::new(123);
+  let expected = vec![-1.4380493091409068, 0.6341950751776804, 0.22606201283216426, 0.2774600474034881, 0.18431915554393896, -0.36521377741519273, 1.3520301643454316, 0.359236227002652, -0.20527935071925305, 1.0174953185160527];
+  let actual = (0..10).map(|_| random.next_gaussian()).collect::<Vec<_>>();
+  assert_eq!(actual, expected);
+  random.set_seed(123);
+  assert_eq!(random.next_gaussian(), -1.4380493091409068);
+}
+
+#[test]
+fn legacy_random_boolean() {
+  let mut random = random::LegacyRandomSource::new(123);
+  let expected = vec![true, false, true, false, false, true, true, false, true, true];
+  let actual = (0..10).map(|_| random.next_boolean()).collect::<Vec<_>>();
+  assert_eq!(actual, expected);
+}
+
+#[test]
+fn legacy_random_int_between_inclusive() {
+  let mut random = random::LegacyRandomSource::new(123);
+  let expected = vec![-3, 3, 3, -3, 1, 1, 0, -2, -1, -2];
+  let actual = (0..10).map(|_| random.next_int_between_inclusive(-3, 3)).collect::<Vec<_>>();
+  assert_eq!(actual, expected);
+}
+
+#[test]
+fn random_int_between_inclusive_extremes() {
+  let sources: [fn() -> Box<dyn random::RandomSource>; 2] = [
+    || Box::new(random::LegacyRandomSource::new(123)),
+    || Box::new(random::XoroshiroRandomSource::from(123)),
+  ];
+  for source in sources {
+    let (mut random, mut high, mut low) = (source(), source(), source());
+    for _ in 0..10 {
+      let value = random.next_int_between_inclusive(0, 3);
+      assert_eq!(high.next_int_between_inclusive(i32::MAX - 3, i32::MAX), i32::MAX - 3 + value);
+      assert_eq!(low.next_int_between_inclusive(i32::MIN, i32::MIN + 3), i32::MIN + value);
+    }
+  }
+}
+
+#[test]
+#[should_panic(expected = "Bound must be positive")]
+fn random_int_between_inclusive_overflow() {
+  random::LegacyRandomSource::new(123).next_int_between_inclusive(i32::MIN, i32::MAX);
+}
+
+#[test]
+#[should_panic(expected = "Bound must be positive")]
+fn random_int_between_overflow() {
+  random::XoroshiroRandomSource::from(123).next_int_between(-1, i32::MAX);
+}
+
+#[test]
+fn legacy_random_triangle() {
+  let mut random = random::LegacyRandomSource::new(123);
+  let expected = vec![0.6613765310995379, 0.22246367597937233, 1.6522836441599562, 5.220657444030266, 3.087720172483984];
+  let actual = (0..5).map(|_| random.triangle(2.0, 5.0)).collect::<Vec<_>>();
+  assert_eq!(actual, expected);
+}
+
 #[test]
 fn xoroshiro_random() {
   let mut random = random::XoroshiroRandomSource::default();
-  assert_eq!(random.next_int(), 159808533);
-  assert_eq!(random.next_long(), 7502368011707135260);
-  assert_eq!(random.next_float(), 0.019376636);
-  assert_eq!(random.next_double(), -0.03839469124758511);
+  assert_eq!(random.next_int(), 159812759);
+  assert_eq!(random.next_long(), 5275285228792843439);
+  assert_eq!(random.next_float(), 0.89791507);
+  assert_eq!(random.next_double(), 0.5944388423954948);
+}
+
+#[test]
+fn seeds() {
+  assert_eq!(random::parse_seed("123"), Some(123));
+  assert_eq!(random::parse_seed(" -5\t"), Some(-5));
+  assert_eq!(random::parse_seed("+5"), Some(5));
+  assert_eq!(random::parse_seed("  hello "), Some(99162322));
+  assert_eq!(random::parse_seed("99999999999999999999"), Some(1260560192));
+  assert_eq!(random::parse_seed(" \n"), None);
+  // Java only trims characters up to the space, unlike Rust's whitespace
+  assert_eq!(random::parse_seed("\u{a0}1"), Some(random::java_string_hash("\u{a0}1") as i64));
+
+  assert_eq!(random::obfuscate_seed(0), 8794265229978523055);
+  assert_eq!(random::obfuscate_seed(-4172144997902289642), 2159143436479834350);
+
+  let mut random = random::LegacyRandomSource::new(0);
+  let decoration = random.set_decoration_seed(1234567890123, -160, 48);
+  assert_eq!(decoration, 2785059234115991163);
+  assert_eq!(random.next_int(), 591637516);
+  random.set_feature_seed(decoration, 3, 9);
+  assert_eq!(random.next_int(), -1764859196);
+  random.set_large_feature_seed(1234567890123, -7, 11);
+  assert_eq!(random.next_int(), 1895102131);
+}
+
+#[test]
+fn xoroshiro_random_int() {
+  let mut random = random::XoroshiroRandomSource::from(123);
+  let expected = vec![1821857589, -1022476066, -2126358323, -105956394, 703988666, 398230800, -337889801, -1866657770, 363802582, -911067111];
+  let actual = (0..10).map(|_| random.next_int()).collect::<Vec<_>>();
+  assert_eq!(actual, expected);
+}
+
+#[test]
+fn xoroshiro_random_long() {
+  let mut random = random::XoroshiroRandomSource::from(123);
+  let expected = vec![1248535248033503029, 4378946378199351518, -8700526626154063667, -5006513703400555562, 2390204413948330938];
+  let actual = (0..5).map(|_| random.next_long()).collect::<Vec<_>>();
+  assert_eq!(actual, expected);
+  let mut random = random::XoroshiroRandomSource::from(-7046029254386353131);
+  assert_eq!(random.next_long(), 663205598535688870);
+}
+
+#[test]
+fn xoroshiro_random_int_max() {
+  let mut random = random::XoroshiroRandomSource::from(123);
+  assert_eq!(random.next_int_max(256), 108);
+  assert_eq!(random.next_int_max(255), 194);
+  assert_eq!(random.next_int_max(254), 128);
+  assert_eq!(random.next_int_max(1000000007), 975330111);
+  assert_eq!(random.next_int_max(i32::MAX), 351994332);
+}
+
+#[test]
+fn xoroshiro_random_int_between() {
+  let mut random = random::XoroshiroRandomSource::from(123);
+  let expected = vec![1, 6, 2, 9, -3, -4, 8, 3, -4, 6];
+  let actual = (0..10).map(|_| random.next_int_between(-5, 10)).collect::<Vec<_>>();
+  assert_eq!(actual, expected);
+}
+
+#[test]
+fn xoroshiro_random_int_between_inclusive() {
+  let mut random = random::XoroshiroRandomSource::from(123);
+  let expected = vec![-1, 2, 0, 3, -2, -3, 3, 0, -3, 2];
+  let actual = (0..10).map(|_| random.next_int_between_inclusive(-3, 3)).collect::<Vec<_>>();
+  assert_eq!(actual, expected);
+}
+
+#[test]
+fn xoroshiro_random_triangle() {
+  let mut random = random::XoroshiroRandomSource::from(123);
+  let expected = vec![1.1515003629753455, 0.9987357909902799, 0.2787074475446598, 1.9657257929862753, 5.693802708639796];
+  let actual = (0..5).map(|_| random.triangle(2.0, 5.0)).collect::<Vec<_>>();
+  assert_eq!(actual, expected);
+}
+
+#[test]
+fn xoroshiro_random_float() {
+  let mut random = random::XoroshiroRandomSource::from(123);
+  let expected = vec![0.06768322, 0.23738313, 0.5283435, 0.7285963, 0.12957323, 0.4738317, 0.38941127, 0.3962661, 0.74628615, 0.007525623];
+  let actual = (0..10).map(|_| random.next_float()).collect::<Vec<_>>();
+  assert_eq!(actual, expected);
+}
+
+#[test]
+fn xoroshiro_random_double() {
+  let mut random = random::XoroshiroRandomSource::from(123);
+  let expected = vec![0.0676832314171325, 0.2373831588220634, 0.5283435065077894, 0.7285963483097334, 0.12957324091436107, 0.4738317514054291, 0.3894113208915406, 0.3962661622942856, 0.7462861826840564, 0.0075256409560972015];
+  let actual = (0..10).map(|_| random.next_double()).collect::<Vec<_>>();
+  assert_eq!(actual, expected);
+}
+
+#[test]
+fn xoroshiro_random_gaussian() {
+  let mut random = random::XoroshiroRandomSource::from(123);
+  let expected = vec![0.21664988180838146, 1.7473269169966934, -1.0880922498885042, -0.07686666200679514, -1.5933837130859017, -1.4946177929666404, -0.7091469066440713, 1.60836801548747, 0.25902982287914156, -0.2384428591837497];
+  let actual = (0..10).map(|_| random.next_gaussian()).collect::<Vec<_>>();
+  assert_eq!(actual, expected);
+}
+
+#[test]
+fn xoroshiro_random_boolean() {
+  let mut random = random::XoroshiroRandomSource::from(123);
+  let expected = vec![true, false, true, false, false, false, true, false, false, true];
+  let actual = (0..10).map(|_| random.next_boolean()).collect::<Vec<_>>();
+  assert_eq!(actual, expected);
+}
+
+#[test]
+fn xoroshiro_random_set_seed() {
+  let mut random = random::XoroshiroRandomSource::default();
+  random.next_gaussian();
+  random.set_seed(123);
+  assert_eq!(random.next_gaussian(), 0.21664988180838146);
+  assert_eq!(random.next_gaussian(), 1.7473269169966934);
 }
 
 #[test]
@@ -85,3 +264,974 @@ fn climate() {
   println!("{}", parameters.find(climate::target(0.0, 0.0, 0.6, 0.0, 0.0, 0.0)));
   println!("{}", parameters.find(climate::target(1.0, 0.0, 0.6, 0.0, 0.0, 0.0)));
 }
+
+fn test_registry() -> registry::Registry {
+  let noises = serde_json::json!({
+    "minecraft:offset": { "firstOctave": -3, "amplitudes": [1.0, 1.0, 1.0, 0.0] },
+    "minecraft:continentalness": { "firstOctave": -9, "amplitudes": [1.0, 1.0, 2.0, 2.0, 2.0, 1.0, 1.0, 1.0, 1.0] },
+  });
+  let density_functions = serde_json::json!({
+    "minecraft:shift_x": { "type": "minecraft:flat_cache", "argument": { "type": "minecraft:cache_2d", "argument": { "type": "minecraft:shift_a", "argument": "minecraft:offset" } } },
+    "minecraft:shift_z": { "type": "minecraft:flat_cache", "argument": { "type": "minecraft:cache_2d", "argument": { "type": "minecraft:shift_b", "argument": "minecraft:offset" } } },
+  });
+  registry::Registry::from_json(&noises, &density_functions).unwrap()
+}
+
+fn test_settings(legacy_random_source: bool) -> router::NoiseGeneratorSettings {
+  router::NoiseGeneratorSettings::from_json(&serde_json::json!({
+    "sea_level": 63,
+    "legacy_random_source": legacy_random_source,
+    "noise": { "min_y": -64, "height": 384, "size_horizontal": 1, "size_vertical": 2 },
+    "noise_router": {
+      "temperature": { "type": "minecraft:shifted_noise", "noise": "minecraft:temperature", "xz_scale": 0.25, "y_scale": 0.0, "shift_x": "minecraft:shift_x", "shift_y": 0.0, "shift_z": "minecraft:shift_z" },
+      "continents": { "type": "minecraft:shifted_noise", "noise": "minecraft:continentalness", "xz_scale": 0.25, "y_scale": 0.0, "shift_x": "minecraft:shift_x", "shift_y": 0.0, "shift_z": "minecraft:shift_z" },
+      "depth": { "type": "minecraft:add", "argument1": { "type": "minecraft:y_clamped_gradient", "from_y": -64, "to_y": 320, "from_value": 1.5, "to_value": -1.5 }, "argument2": 0.2 },
+      "vein_gap": { "type": "minecraft:clamp", "input": { "type": "minecraft:y_clamped_gradient", "from_y": 0, "to_y": 100, "from_value": 0.0, "to_value": 1.0 }, "min": 0.5, "max": 0.25 },
+      "final_density": { "type": "minecraft:spline", "spline": { "coordinate": "minecraft:shift_x", "points": [
+        { "location": -1.0, "value": -0.5, "derivative": 0.0 },
+        { "location": 1.0, "value": 0.5, "derivative": 1.0 },
+      ] } },
+    },
+  })).unwrap()
+}
+
+#[test]
+fn noise_router() {
+  let registry = test_registry();
+  let router = router::NoiseRouter::new(&test_settings(true), &registry, 123).unwrap();
+
+  // The legacy random source replaces temperature and disables the offset noise
+  let temperature = noise::NormalNoise::new(&mut random::LegacyRandomSource::new(123), &noise::NoiseParameters::new(-7, &[1.0, 1.0]));
+  assert_eq!(router.compute("temperature", 100, 64, -200), Some(temperature.sample(25.0, 0.0, -50.0)));
+  assert_eq!(router.compute("final_density", 100, 64, -200), Some(-0.25));
+
+  assert_eq!(router.compute("depth", 0, -64, 0), Some(1.5 + 0.2));
+  assert_eq!(router.compute("depth", 0, 320, 0), Some(-1.5 + 0.2));
+  assert_eq!(router.compute("barrier", 0, 0, 0), Some(0.0));
+  assert_eq!(router.compute("unknown", 0, 0, 0), None);
+
+  // Clamping with inverted bounds gives the min below it and the max otherwise
+  assert_eq!(router.compute("vein_gap", 0, 20, 0), Some(0.5));
+  assert_eq!(router.compute("vein_gap", 0, 80, 0), Some(0.25));
+
+  // Cyclic references are an error instead of overflowing the stack
+  let cyclic = registry::Registry::from_json(&serde_json::json!({}), &serde_json::json!({
+    "minecraft:a": { "type": "minecraft:add", "argument1": 1.0, "argument2": "minecraft:b" },
+    "minecraft:b": { "type": "minecraft:abs", "argument": "minecraft:a" },
+    "minecraft:c": "minecraft:c",
+  })).unwrap();
+  let mut loader = density::Loader::new(&cyclic, 123, false);
+  assert_eq!(loader.reference("a").err(), Some("Density function minecraft:a references itself".to_string()));
+  assert_eq!(loader.reference("c").err(), Some("Density function minecraft:c references itself".to_string()));
+}
+
+#[test]
+fn noise_router_sampler() {
+  let registry = test_registry();
+  let router = router::NoiseRouter::new(&test_settings(false), &registry, 123);
+  assert!(router.is_err(), "temperature is not registered for the xoroshiro source");
+
+  let router = router::NoiseRouter::new(&test_settings(true), &registry, 123).unwrap();
+  let sampler = sampler::Sampler::new(&router);
+  let target = sampler.target(16, 0, 32).vec();
+  assert_eq!(target, sampler.sample(4, 0, 8).vec());
+  assert_eq!(target[2], climate::target(0.0, 0.0, router.compute("continents", 16, 0, 32).unwrap(), 0.0, 0.0, 0.0).vec()[2]);
+}
+
+#[test]
+fn blender() {
+  let empty = blender::Blender::new(0, 0, &std::collections::BTreeMap::new());
+  assert!(empty.is_empty());
+  assert_eq!(empty.blend_offset_and_factor(5, 5), (1.0, 0.0));
+  assert_eq!(empty.blend_density(5, 5, 5, 0.3), 0.3);
+  assert_eq!(empty.blend_biome(1, 1, 1), None);
+
+  let blender = blender::Blender::from_json(1, 0, &serde_json::json!([{
+    "chunk_x": 0, "chunk_z": 0, "min_y": 0,
+    "columns": (0..=4).map(|z| serde_json::json!({ "x": 4, "z": z, "height": 70.0, "densities": [1.0, 1.0, -1.0], "biomes": [7, 7, 7, 7] })).collect::<Vec<_>>(),
+  }])).unwrap();
+  assert!(!blender.is_empty());
+
+  // Positions on the border of the old chunk keep its height and density
+  let (alpha, offset) = blender.blend_offset_and_factor(16, 4);
+  assert_eq!(alpha, 0.0);
+  assert!(offset < 0.0);
+  assert_eq!(blender.blend_density(16, 8, 4, 0.3), 1.0);
+  assert_eq!(blender.blend_density(16, 16, 4, 0.3), -1.0);
+
+  let (alpha, _) = blender.blend_offset_and_factor(24, 4);
+  assert!(alpha > 0.0 && alpha < 1.0);
+  assert_eq!(blender.blend_offset_and_factor(1000, 4), (1.0, 0.0));
+  assert_eq!(blender.blend_density(1000, 8, 4, 0.3), 0.3);
+
+  assert_eq!(blender.resolve_biome(5, 2, 1, || 3), 7);
+  assert_eq!(blender.resolve_biome(5, 20, 1, || 3), 3);
+  assert_eq!(blender.resolve_biome(100, 2, 1, || 3), 3);
+
+  // Distances far from the old chunk are squared as doubles like vanilla
+  assert_eq!(blender.blend_biome(60000, 0, 0), None);
+  assert_eq!(blender.blend_offset_and_factor(240000, -240000), (1.0, 0.0));
+  assert_eq!(blender.blend_density(240000, 8, 0, 0.3), 0.3);
+
+  // Several old chunks at the same distance give the same result every time
+  let old_chunks = serde_json::json!((-1..=1_i32).flat_map(|x| (-1..=1_i32).filter(move |&z| x != 0 || z != 0).map(move |z| serde_json::json!({
+    "chunk_x": x, "chunk_z": z, "min_y": 0,
+    "columns": (0..=4).map(|i| serde_json::json!({ "x": i, "z": (i * 3 + x + z).rem_euclid(5), "height": 60.0 + (x * 7 + z * 3 + i) as f64, "densities": [1.0, -0.5, (x - z) as f64], "biomes": [x + 10, z + 20, 7, 7] })).collect::<Vec<_>>(),
+  }))).collect::<Vec<_>>());
+  let sample = |blender: &blender::Blender| (-8..24).flat_map(|x| (-8..24).map(move |z| (x, z))).map(|(x, z)| {
+    let (alpha, offset) = blender.blend_offset_and_factor(x, z);
+    (alpha.to_bits(), offset.to_bits(), blender.blend_density(x, 8, z, 0.3).to_bits(), blender.blend_biome(x >> 2, 0, z >> 2))
+  }).collect::<Vec<_>>();
+  let first = sample(&blender::Blender::from_json(0, 0, &old_chunks).unwrap());
+  for _ in 0..4 {
+    assert_eq!(sample(&blender::Blender::from_json(0, 0, &old_chunks).unwrap()), first);
+  }
+}
+
+#[test]
+fn thread_safety() {
+  fn assert_send_sync<T: Send + Sync>() {}
+  assert_send_sync::<noise::ImprovedNoise>();
+  assert_send_sync::<noise::PerlinNoise>();
+  assert_send_sync::<noise::NormalNoise>();
+  assert_send_sync::<router::NoiseRouter>();
+  assert_send_sync::<sampler::Sampler>();
+  assert_send_sync::<climate::ParameterList>();
+  assert_send_sync::<blender::Blender>();
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn parallel_grids() {
+  let grid = grid_range(-5.0, 5.0, 0.7, 0.0, 3.0, 1.0, 10.0, 20.0, 1.5, false, grid::AxisOrder::ZXY).unwrap();
+  assert_eq!(parallel::improved_noise(123, &grid), super::improved_noise(123, &grid));
+  let grid = flat_grid_range(-50.0, 50.0, 3.0, 0.0, 0.0, 50.0, 2.0, true, grid::AxisOrder::ZYX).unwrap();
+  assert_eq!(parallel::normal_noise(123, -4, &[1.0, 2.0, 0.5], &grid), super::normal_noise(123, -4, &[1.0, 2.0, 0.5], &grid));
+
+  let router = router::NoiseRouter::new(&test_settings(true), &test_registry(), 123).unwrap();
+  let sampler = sampler::Sampler::new(&router);
+  let parameters = climate::ParameterList::new(&[
+    (climate::parameters(-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0), 1),
+    (climate::parameters(0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0), 2),
+  ]);
+  let grid = grid_range(0.0, 400.0, 16.0, 0.0, 1.0, 1.0, 0.0, 400.0, 16.0, false, grid::AxisOrder::XYZ).unwrap();
+  assert_eq!(parallel::climate_noise(&sampler, &grid), climate_noise(&sampler, &grid));
+  assert_eq!(parallel::multi_noise(&parameters, &sampler, &grid), multi_noise(&parameters, &sampler, &grid));
+  let source = checkerboard_biome_source(vec!["a".into(), "b".into()], 1).unwrap();
+  assert_eq!(parallel::biome_source(source.get(), &sampler, &grid), biome_source_noise(&source, &sampler, &grid));
+}
+
+#[test]
+fn tiles() {
+  let router = router::NoiseRouter::new(&test_settings(true), &test_registry(), 123).unwrap();
+  let sampler = sampler::Sampler::new(&router);
+  let parameters = climate::ParameterList::new(&[
+    (climate::parameters(-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0), 1),
+    (climate::parameters(0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0), 2),
+  ]);
+  let layout = tile::TileLayout::new(-64.0, 32.0, 0.0, 4.0, 8, 3, 2).unwrap();
+  assert_eq!(tile_count(&layout), 6);
+
+  // Tile 4 is the second tile of the second row
+  let mut biomes = vec![0; 64];
+  multi_noise_tile(&parameters, &sampler.clone(), &layout, 4, &mut biomes).unwrap();
+  let expected = multi_noise(&parameters, &sampler, &flat_grid_count(-32.0, 4.0, 8, 0.0, 64.0, 4.0, 8, grid::AxisOrder::ZYX));
+  assert_eq!(biomes, expected);
+
+  let mut climate = vec![0.0; 64 * 6];
+  climate_noise_tile(&sampler, &layout, 4, &mut climate).unwrap();
+  assert_eq!(climate[6 * 9..6 * 10], sampler.target(-28, 0, 68).values());
+
+  let mut continents = vec![0.0; 64];
+  router_noise_tile(&router, "continents", &layout, 0, &mut continents).unwrap();
+  assert_eq!(continents[63], router.compute("continents", -36, 0, 60).unwrap());
+
+  assert!(tile::TileLayout::new(0.0, 0.0, 0.0, 4.0, 0, 3, 2).is_err());
+  assert!(layout.grid(6).is_err());
+  assert!(layout.fill(0, &mut [0.0; 63], |_, _, _| 0.0).is_err());
+  assert!(layout.fill_chunks(0, &mut [0.0; 64], |_, _, _| [0.0; 2]).is_err());
+}
+
+#[test]
+fn noise_handles() {
+  let grid = grid_range(0.0, 4.0, 0.5, 0.0, 1.0, 1.0, 0.0, 2.0, 0.5, false, grid::AxisOrder::XYZ).unwrap();
+  let improved = noise::ImprovedNoise::from_seed(123);
+  assert_eq!(improved.sample_point(0.5, 2.0, 1.0), -0.3989706759689653);
+  let values = improved.sample_grid(&grid);
+  assert_eq!(values.len(), 32);
+  assert_eq!([values[0], values[5], values[31]], [0.4783069667320268, 0.25991663629454903, -0.4377859389318463]);
+
+  let grid = grid_range(0.0, 40.0, 3.5, 0.0, 2.0, 1.0, 0.0, 20.0, 2.5, false, grid::AxisOrder::XYZ).unwrap();
+  let perlin = noise::PerlinNoise::from_seed(123, -4, &[1.0, 2.0, 0.5]);
+  let values = perlin.sample_grid(&grid);
+  assert_eq!(values.len(), 192);
+  assert_eq!([values[0], values[25], values[191]], [0.24981116999172598, 0.23666075849341436, -0.06065128845700232]);
+
+  let normal = noise::NormalNoise::from_seed(123, -4, &[1.0, 2.0, 0.5]);
+  assert_eq!(normal.sample_point(5.4, -4.0, 0.7), 0.051380355988908596);
+  let values = normal.sample_grid(&grid);
+  assert_eq!(values.len(), 192);
+  assert_eq!([values[0], values[25], values[191]], [-0.09822247385629118, 0.08577437933052778, 0.164731563096922]);
+}
+
+#[test]
+fn noise_octaves() {
+  let perlin = noise::PerlinNoise::from_seed(123, -7, &[1.0, 0.0, 2.0, 0.5]);
+  assert_eq!(perlin.octaves(), 4);
+  assert!(perlin.octave(1).is_none());
+  assert_eq!(perlin.amplitudes(), vec![1.0, 0.0, 2.0, 0.5]);
+  assert_eq!(perlin.frequencies(), vec![1.0 / 128.0, 1.0 / 64.0, 1.0 / 32.0, 1.0 / 16.0]);
+  assert!((perlin.max_value() - 5.0 / 3.0).abs() < 1e-12);
+  assert!(perlin.max_broken_value(1.0) > perlin.max_value());
+
+  let octaves = perlin.sample_octaves(13.5, -4.0, 220.1, 0.0, 0.0, false);
+  assert_eq!(octaves.iter().sum::<f64>(), perlin.sample(13.5, -4.0, 220.1, 0.0, 0.0, false));
+  assert_eq!(octaves[1], 0.0);
+  let f = perlin.frequency(2);
+  assert_eq!(octaves[2], perlin.octave_factor(2) * perlin.octave(2).unwrap().sample(13.5 * f, -4.0 * f, 220.1 * f, 0.0, 0.0));
+
+  let normal = noise::NormalNoise::from_seed(123, -4, &[1.0, 2.0, 0.5]);
+  assert_eq!(normal.max_value(), (normal.first().max_value() + normal.second().max_value()) * normal.value_factor());
+  let mut random = random::XoroshiroRandomSource::from(3);
+  for _ in 0..1000 {
+    let (x, y, z) = (random.next_double() * 1000.0, random.next_double() * 100.0, random.next_double() * 1000.0);
+    let octaves = normal.sample_octaves(x, y, z);
+    assert_eq!(octaves.len(), 6);
+    assert!((octaves.iter().sum::<f64>() - normal.sample(x, y, z)).abs() < 1e-12);
+    assert!(normal.sample(x, y, z).abs() <= normal.max_value());
+  }
+}
+
+#[test]
+fn noise_gradients() {
+  let improved = noise::ImprovedNoise::from_seed(123);
+  let perlin = noise::PerlinNoise::from_seed(123, -5, &[1.0, 0.0, 2.0, 0.5]);
+  let normal = noise::NormalNoise::from_seed(123, -4, &[1.0, 2.0, 0.5]);
+  let h = 1e-6;
+  let check = |f: &dyn Fn(f64, f64, f64) -> f64, (value, gradient): (f64, [f64; 3]), (x, y, z): (f64, f64, f64)| {
+    assert_eq!(value, f(x, y, z));
+    let differences = [
+      (f(x + h, y, z) - f(x - h, y, z)) / (2.0 * h),
+      (f(x, y + h, z) - f(x, y - h, z)) / (2.0 * h),
+      (f(x, y, z + h) - f(x, y, z - h)) / (2.0 * h),
+    ];
+    for (analytic, difference) in gradient.iter().zip(differences) {
+      assert!((analytic - difference).abs() < 1e-5, "{:?} vs {:?} at {:?}", gradient, differences, (x, y, z));
+    }
+  };
+  let mut random = random::XoroshiroRandomSource::from(8);
+  for _ in 0..200 {
+    let p = (random.next_double() * 200.0 - 100.0, random.next_double() * 200.0 - 100.0, random.next_double() * 200.0 - 100.0);
+    check(&|x, y, z| improved.sample(x, y, z, 0.0, 0.0), improved.sample_gradient(p.0, p.1, p.2, 0.0, 0.0), p);
+    check(&|x, y, z| perlin.sample(x, y, z, 0.0, 0.0, false), perlin.sample_gradient(p.0, p.1, p.2, 0.0, 0.0, false), p);
+    check(&|x, y, z| perlin.sample(x, y, z, 0.0, 0.0, true), perlin.sample_gradient(p.0, p.1, p.2, 0.0, 0.0, true), p);
+    check(&|x, y, z| normal.sample(x, y, z), normal.sample_gradient(p.0, p.1, p.2), p);
+  }
+
+  let grid = grid_range(0.0, 4.0, 1.0, 0.0, 1.0, 1.0, 0.0, 2.0, 1.0, false, grid::AxisOrder::XYZ).unwrap();
+  let gradients = normal.sample_grid_gradient(&grid);
+  assert_eq!(gradients.len(), grid.len() * 4);
+  let (value, [dx, dy, dz]) = normal.sample_gradient(3.0, 0.0, 1.0);
+  assert_eq!(gradients[grid.len() * 4 - 4..], [value, dx, dy, dz]);
+}
+
+#[test]
+fn batched_noise() {
+  let mut random = random::XoroshiroRandomSource::from(5);
+  let mut lanes = || simd::F64x4::from_fn(|_| (random.next_double() - 0.5) * 2.0e8);
+  let improved = noise::ImprovedNoise::from_seed(123);
+  let perlin = noise::PerlinNoise::from_seed(123, -7, &[1.0, 0.0, 2.0, 0.5]);
+  let normal = noise::NormalNoise::from_seed(123, -4, &[1.0, 2.0, 0.5]);
+  let bits = |v: simd::F64x4| v.0.map(f64::to_bits);
+  for values in [[0.0, -0.0, 0.5, -0.5], [-1.0, -1.0e-300, 4503599627370495.5, -4503599627370495.5], [1.0e300, f64::NEG_INFINITY, 2.5, -2.5]] {
+    assert_eq!(bits(simd::F64x4(values).floor()), values.map(|v| v.floor().to_bits()));
+  }
+  assert!(simd::F64x4::splat(f64::NAN).floor().0[0].is_nan());
+  for _ in 0..200 {
+    let (x, y, z) = (lanes(), lanes().map(|y| y * 1.0e-6), lanes());
+    let at = |f: &dyn Fn(f64, f64, f64) -> f64| bits(simd::F64x4::from_fn(|i| f(x.0[i], y.0[i], z.0[i])));
+    assert_eq!(bits(improved.sample4(x, y, z, 0.0, 0.0)), at(&|x, y, z| improved.sample(x, y, z, 0.0, 0.0)));
+    assert_eq!(bits(improved.sample4(x, y, z, 0.3, 0.7)), at(&|x, y, z| improved.sample(x, y, z, 0.3, 0.7)));
+    assert_eq!(bits(perlin.sample4(x, y, z, 0.0, 0.0, false)), at(&|x, y, z| perlin.sample(x, y, z, 0.0, 0.0, false)));
+    assert_eq!(bits(perlin.sample4(x, y, z, 0.5, -1.0, true)), at(&|x, y, z| perlin.sample(x, y, z, 0.5, -1.0, true)));
+    assert_eq!(bits(normal.sample4(x, y, z)), at(&|x, y, z| normal.sample(x, y, z)));
+  }
+
+  // Grids that aren't a whole number of batches pad the last one
+  let grid = grid_range(-3.0, 3.0, 0.7, 0.0, 2.0, 1.0, 0.0, 1.0, 1.0, false, grid::AxisOrder::ZYX).unwrap();
+  assert_eq!(grid.len() % simd::LANES, 2);
+  assert_eq!(normal.sample_grid(&grid), grid.map(|x, y, z| normal.sample(x, y, z)));
+}
+
+#[test]
+fn grids() {
+  // Exclusive bounds keep the last sample when the range isn't a whole number of steps
+  assert_eq!(grid::Axis::exclusive(0.0, 4.5, 1.0).unwrap().count(), 5);
+  assert_eq!(grid::Axis::exclusive(0.0, 0.3, 0.1).unwrap().count(), 3);
+  assert_eq!(grid::Axis::exclusive(2.0, 2.0, 1.0).unwrap().count(), 0);
+  assert_eq!(grid::Axis::inclusive(0.0, 0.3, 0.1).unwrap().count(), 4);
+  assert_eq!(grid::Axis::inclusive(2.0, 2.0, 1.0).unwrap().count(), 1);
+  assert_eq!(grid::Axis::inclusive(3.0, 2.0, 1.0).unwrap().count(), 0);
+  assert!(grid::Axis::exclusive(0.0, 4.0, 0.0).is_err());
+  assert!(grid::Axis::inclusive(0.0, 4.0, -1.0).is_err());
+  assert!(grid::Axis::inclusive(0.0, 4.0, f64::NAN).is_err());
+
+  let grid = grid_count(0.0, 1.0, 2, 10.0, 1.0, 3, 20.0, 1.0, 4, grid::AxisOrder::XYZ);
+  assert_eq!(grid.shape(), [2, 3, 4]);
+  let positions = grid.map(|x, y, z| (x, y, z));
+  assert_eq!(positions[1], (0.0, 10.0, 21.0));
+  assert_eq!(positions[4], (0.0, 11.0, 20.0));
+  let zyx = grid.with_order(grid::AxisOrder::ZYX).map(|x, y, z| (x, y, z));
+  assert_eq!(zyx[1], (1.0, 10.0, 20.0));
+  assert_eq!(zyx[2], (0.0, 11.0, 20.0));
+  for order in [grid::AxisOrder::XYZ, grid::AxisOrder::XZY, grid::AxisOrder::YXZ, grid::AxisOrder::YZX, grid::AxisOrder::ZXY, grid::AxisOrder::ZYX].iter() {
+    let grid = grid.with_order(*order);
+    let positions = grid.map(|x, y, z| (x, y, z));
+    assert_eq!(positions.len(), 24);
+    for (i, position) in positions.iter().enumerate() {
+      assert_eq!(grid.position(i), *position);
+    }
+  }
+
+  // Flat grids are row-major images when z is the outer axis
+  let image = flat_grid_range(0.0, 2.0, 1.0, 64.0, 0.0, 1.0, 1.0, true, grid::AxisOrder::ZYX).unwrap();
+  assert_eq!(grid_len(&image), 6);
+  assert_eq!(image.map(|x, y, z| (x, y, z)), vec![
+    (0.0, 64.0, 0.0), (1.0, 64.0, 0.0), (2.0, 64.0, 0.0),
+    (0.0, 64.0, 1.0), (1.0, 64.0, 1.0), (2.0, 64.0, 1.0),
+  ]);
+  for i in 0..6 {
+    assert_eq!(image.position(i), image.map(|x, y, z| (x, y, z))[i]);
+  }
+}
+
+#[test]
+fn biome_maps() {
+  let palette = biome_palette(r#"["plains", "minecraft:ocean", "custom:biome"]"#).unwrap();
+  assert_eq!(palette.get(0), [141, 179, 96]);
+  assert_eq!(palette.get(1), [0, 0, 112]);
+  // Unknown biomes get a fallback colour that is the same in every palette
+  assert_eq!(palette.get(2), [218, 166, 109]);
+  assert_eq!(biome_palette(r#"["plains", "minecraft:ocean", "custom:biome"]"#).unwrap().get(2), palette.get(2));
+  assert_ne!(palette.get(2), palette.get(3));
+
+  let mut palette = palette;
+  set_biome_color(&mut palette, 1, 10, 20, 30);
+  let Clamped(rgba) = render_biome_map(&[0, 1, 1, 0], &palette);
+  assert_eq!(rgba, vec![141, 179, 96, 255, 10, 20, 30, 255, 10, 20, 30, 255, 141, 179, 96, 255]);
+
+  // Slopes facing the north-west are lit, those facing away are shaded
+  let Clamped(flat) = render_shaded_biome_map(&[0, 0, 0, 0], &[64.0, 64.0, 64.0, 64.0], 2, 4.0, &palette).unwrap();
+  assert_eq!(flat, render_biome_map(&[0, 0, 0, 0], &palette).0);
+  let Clamped(shaded) = render_shaded_biome_map(&[0, 0, 0, 0], &[64.0, 68.0, 60.0, 64.0], 2, 4.0, &palette).unwrap();
+  assert!(shaded[4] > flat[4]);
+  assert!(shaded[8] < flat[8]);
+  assert_eq!(shaded[12..16], flat[12..16]);
+  assert_eq!(shaded[7], 255);
+  assert_eq!(render_slope_shaded_biome_map(&[0, 0, 0, 0], &[0.0, 1.0, -1.0, 0.0], &palette).unwrap().0, shaded);
+
+  let mut rgba = flat.clone();
+  assert!(render::hillshade(&mut rgba, &[64.0; 4], 0, 4.0).is_err());
+  assert!(render::hillshade(&mut rgba, &[64.0; 4], 3, 4.0).is_err());
+  assert!(render::shade_slopes(&mut rgba, &[0.0; 3]).is_err());
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn biome_map_png() {
+  let palette = render::Palette::from_names(&["plains", "desert"]);
+  let rgba = render::render_biomes(&[0, 1, 1, 0, 1, 0], &palette);
+  let png = encode_png(&rgba, 3, 2).unwrap();
+  let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+  let mut decoded = vec![0; reader.output_buffer_size()];
+  let info = reader.next_frame(&mut decoded).unwrap();
+  assert_eq!((info.width, info.height), (3, 2));
+  assert_eq!(decoded, rgba);
+}
+
+#[test]
+fn noise_heatmaps() {
+  let values = [0.0, 1.0, 2.0, 3.0, f64::NAN];
+  let stats = noise_stats(&values, 3, None, None);
+  assert_eq!((stats.min(), stats.max(), stats.count()), (0.0, 3.0, 4));
+  assert_eq!(stats.mean(), 1.5);
+  assert!((stats.stddev() - 1.25_f64.sqrt()).abs() < 1e-12);
+  assert_eq!(stats.histogram(), vec![1, 1, 2]);
+
+  // A fixed range clamps values into the outermost bins and colours
+  let heatmap = noise_heatmap(&values, render::ColorRamp::Greyscale, Some(1.0), Some(2.0), 2);
+  assert_eq!((heatmap.stats().range_min(), heatmap.stats().range_max()), (1.0, 2.0));
+  assert_eq!(heatmap.stats().histogram(), vec![2, 2]);
+  assert_eq!(heatmap.rgba().0, vec![0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255, 255, 255, 255, 255, 255, 0, 0, 0, 0]);
+
+  let heatmap = render::Heatmap::new(&values, render::ColorRamp::Viridis, None, None, 4);
+  assert_eq!(heatmap.pixels()[0..3], [68, 1, 84]);
+  assert_eq!(heatmap.pixels()[12..15], [253, 231, 37]);
+  assert_eq!(render::ColorRamp::Coolwarm.get(0.5), [221, 221, 221]);
+
+  let router = router::NoiseRouter::new(&test_settings(true), &test_registry(), 123).unwrap();
+  let sampler = sampler::Sampler::new(&router);
+  let grid = flat_grid_count(0.0, 16.0, 4, 0.0, 0.0, 16.0, 4, grid::AxisOrder::ZYX);
+  let climate = climate_noise(&sampler, &grid);
+  let continents = grid_channel(&climate, 7, 2).unwrap();
+  assert_eq!(continents, grid.map(|x, y, z| sampler.target(x as i32, y as i32, z as i32).vec()[2]));
+  let stats = noise_stats(&continents, 8, None, None);
+  assert_eq!(stats.histogram().iter().sum::<u32>(), 16);
+  assert!(render::channel(&climate, 7, 7).is_err());
+  assert!(render::channel(&climate, 0, 0).is_err());
+}
+
+#[test]
+fn parameter_list_json() {
+  let (parameters, names) = climate::ParameterList::from_json(&serde_json::json!([
+    { "biome": "plains", "parameters": { "temperature": [-1.0, 0.0], "humidity": 0.0, "continentalness": 0.0, "erosion": 0.0, "weirdness": 0.0, "depth": 0.0, "offset": 0.0 } },
+    { "biome": "minecraft:desert", "parameters": { "temperature": [0.0, 1.0], "humidity": 0.0, "continentalness": 0.0, "erosion": 0.0, "weirdness": 0.0, "depth": 0.0, "offset": 0.0 } },
+    { "biome": "minecraft:plains", "parameters": { "temperature": 0.0, "humidity": 1.0, "continentalness": 0.0, "erosion": 0.0, "weirdness": 0.0, "depth": 0.0, "offset": 0.0 } },
+  ])).unwrap();
+  assert_eq!(names, vec!["minecraft:plains", "minecraft:desert"]);
+  assert_eq!(parameters.find(climate::target(-0.5, 0.0, 0.0, 0.0, 0.0, 0.0)), 0);
+  assert_eq!(parameters.find(climate::target(0.5, 0.0, 0.0, 0.0, 0.0, 0.0)), 1);
+  assert_eq!(parameters.find(climate::target(0.5, 0.9, 0.0, 0.0, 0.0, 0.0)), 0);
+  assert!(climate::ParameterList::from_json(&serde_json::json!([])).is_err());
+  assert!(climate::ParamPoint::from_json(&serde_json::json!({ "temperature": [1.0] })).is_err());
+  let reversed = climate::ParameterList::from_json(&serde_json::json!([{ "biome": "a", "parameters": { "temperature": [0.5, -0.5] } }]));
+  assert_eq!(reversed.err(), Some("Parameter min must not be larger than max".to_string()));
+}
+
+#[test]
+fn spawn_position() {
+  let [west, east] = climate::spawn_targets();
+  assert_eq!(west.fitness(&climate::target(0.3, 0.1, 0.5, 0.2, -0.5, 0.0)), 0);
+  assert_eq!(west.fitness(&climate::target(0.3, 0.1, -0.21, 0.2, -0.5, 0.0)), 1000 * 1000);
+  assert_eq!(east.fitness(&climate::target(0.3, 0.1, 0.5, 0.2, 0.0, 0.0)), 1600 * 1600);
+
+  let router = router::NoiseRouter::new(&test_settings(true), &test_registry(), 123).unwrap();
+  let sampler = sampler::Sampler::new(&router);
+  let full = climate::Param::span(-2.0, 2.0);
+  let anywhere = climate::ParamPoint::new(full, full, full, full, full, full, 0.0);
+  let target = sampler.sample(0, 0, 0).at_surface();
+  assert_eq!(sampler.find_spawn(&[anywhere]), (0, 0));
+  assert_eq!(target.values()[5], 0.0);
+
+  // Spawn moves away from the origin towards a matching climate
+  let (x, z) = sampler.find_spawn(&climate::spawn_targets());
+  let spawn = sampler.sample(x >> 2, 0, z >> 2).at_surface();
+  let origin = sampler.sample(0, 0, 0).at_surface();
+  let fitness = |t: &climate::TargetPoint| climate::spawn_targets().iter().map(|p| p.fitness(t)).min().unwrap();
+  assert!((x, z) == (0, 0) || fitness(&spawn) < fitness(&origin));
+  assert_eq!(sampler.find_spawn(&climate::spawn_targets()), (x, z));
+}
+
+fn test_datapack() -> serde_json::Value {
+  serde_json::json!({
+    "data/minecraft/worldgen/noise/offset.json": { "firstOctave": -3, "amplitudes": [1.0, 1.0, 1.0, 0.0] },
+    "data/minecraft/worldgen/noise/continentalness.json": "{ \"firstOctave\": -9, \"amplitudes\": [1.0, 1.0, 2.0, 2.0, 2.0, 1.0, 1.0, 1.0, 1.0] }",
+    "data/minecraft/worldgen/density_function/shift_x.json": { "type": "minecraft:shift_a", "argument": "minecraft:offset" },
+    "data/minecraft/worldgen/density_function/shift_z.json": { "type": "minecraft:shift_b", "argument": "minecraft:offset" },
+    "data/minecraft/worldgen/noise_settings/overworld.json": {
+      "sea_level": 63, "legacy_random_source": true,
+      "noise": { "min_y": -64, "height": 384, "size_horizontal": 1, "size_vertical": 2 },
+      "noise_router": {
+        "continents": { "type": "minecraft:shifted_noise", "noise": "minecraft:continentalness", "xz_scale": 0.25, "y_scale": 0.0, "shift_x": "minecraft:shift_x", "shift_y": 0.0, "shift_z": "minecraft:shift_z" },
+      },
+    },
+    "data/minecraft/worldgen/multi_noise_biome_source_parameter_list/test.json": { "biomes": [
+      { "biome": "minecraft:ocean", "parameters": { "temperature": 0.0, "humidity": 0.0, "continentalness": [-1.0, 0.0], "erosion": 0.0, "weirdness": 0.0, "depth": 0.0, "offset": 0.0 } },
+      { "biome": "minecraft:plains", "parameters": { "temperature": 0.0, "humidity": 0.0, "continentalness": [0.0, 1.0], "erosion": 0.0, "weirdness": 0.0, "depth": 0.0, "offset": 0.0 } },
+    ] },
+    "data/minecraft/dimension/overworld.json": { "type": "minecraft:overworld", "generator": {
+      "type": "minecraft:noise", "settings": "minecraft:overworld",
+      "biome_source": { "type": "minecraft:multi_noise", "preset": "minecraft:test" },
+    } },
+    "data/minecraft/tags/worldgen/biome/is_ocean.json": { "values": ["minecraft:ocean", "#minecraft:is_deep_ocean", { "id": "#custom:missing", "required": false }] },
+    "data/minecraft/tags/worldgen/biome/is_deep_ocean.json": { "values": ["deep_ocean"] },
+    "data/custom/worldgen/biome/nested/forest.json": { "temperature": 0.7 },
+    "data/custom/structures/house.nbt.json": {},
+    "pack.mcmeta": { "pack": { "pack_format": 10 } },
+  })
+}
+
+#[test]
+fn datapacks() {
+  let datapack = datapack::Datapack::from_json(&test_datapack()).unwrap();
+  let registry = datapack.registry().unwrap();
+  assert!(registry.noise("continentalness").is_some());
+  assert!(registry.density_function("minecraft:shift_x").is_some());
+  assert!(registry.noise_settings("overworld").is_some());
+  assert_eq!(registry.resource("worldgen/biome", "custom:nested/forest"), Some(&serde_json::json!({ "temperature": 0.7 })));
+  assert_eq!(registry.resource_ids("dimension"), vec!["minecraft:overworld"]);
+  assert_eq!(registry.resource_ids("structures"), vec!["custom:house.nbt"]);
+  assert_eq!(registry.resolve_tag("worldgen/biome", &serde_json::json!("#is_ocean")).unwrap(), vec!["minecraft:ocean", "minecraft:deep_ocean"]);
+  assert!(registry.resolve_tag("worldgen/biome", &serde_json::json!("#is_river")).is_err());
+
+  let dimension = dimension::Dimension::load(&registry, "overworld", 123).unwrap();
+  assert_eq!(dimension.biome_source().possible_biomes(), ["minecraft:ocean", "minecraft:plains"]);
+  let continents = dimension.router().compute("continents", 100, 64, -200).unwrap();
+  assert_eq!(dimension.biome_source().biome_id(dimension.biome(100, 64, -200)), if continents < 0.0 { "minecraft:ocean" } else { "minecraft:plains" });
+
+  // Later datapacks replace the definitions of earlier ones
+  let mut layered = registry.clone();
+  datapack::Datapack::from_json(&serde_json::json!({
+    "data/minecraft/worldgen/noise/continentalness.json": { "firstOctave": -7, "amplitudes": [1.0] },
+  })).unwrap().apply(&mut layered).unwrap();
+  assert_eq!(layered.noise("continentalness"), Some(&noise::NoiseParameters::new(-7, &[1.0])));
+  assert_eq!(layered.resource_ids("dimension"), vec!["minecraft:overworld"]);
+
+  assert!(datapack::Datapack::from_json(&serde_json::json!({ "data/minecraft/dimension/broken.json": "{" })).is_err());
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn datapack_zip() {
+  use std::io::Write;
+  let mut buffer = std::io::Cursor::new(Vec::new());
+  {
+    let mut zip = zip::ZipWriter::new(&mut buffer);
+    for (path, json) in test_datapack().as_object().unwrap() {
+      zip.start_file(path.as_str(), zip::write::FileOptions::default()).unwrap();
+      let text = match json { serde_json::Value::String(text) => text.clone(), json => json.to_string() };
+      zip.write_all(text.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+  }
+  buffer.set_position(0);
+  let registry = datapack::Datapack::from_zip(buffer).unwrap().registry().unwrap();
+  let expected = datapack::Datapack::from_json(&test_datapack()).unwrap().registry().unwrap();
+  assert_eq!(registry.resource_ids("dimension"), expected.resource_ids("dimension"));
+  assert_eq!(registry.noise("offset"), expected.noise("offset"));
+  assert!(dimension::Dimension::load(&registry, "overworld", 123).is_ok());
+}
+
+#[cfg(feature = "vanilla-1-20")]
+#[test]
+fn vanilla_data() {
+  let version = vanilla::Version::latest();
+  assert_eq!(vanilla::Version::from_name("1.20.4"), Some(vanilla::Version::V1_20));
+  assert_eq!(vanilla::Version::from_name("1.17.1"), None);
+  assert_eq!(vanilla::Version::from_name("snapshot"), None);
+
+  let registry = version.registry();
+  assert_eq!(registry.noise("minecraft:temperature"), Some(&noise::NoiseParameters::new(-10, &[1.5, 0.0, 1.0, 0.0, 0.0, 0.0])));
+  assert_eq!(registry.noise("continentalness_large"), Some(&noise::NoiseParameters::new(-11, &[1.0, 1.0, 2.0, 2.0, 2.0, 1.0, 1.0, 1.0, 1.0])));
+
+  let overworld = dimension::Dimension::load(&registry, "overworld", 123).unwrap();
+  let biomes = overworld.biome_source().possible_biomes();
+  for biome in ["minecraft:cherry_grove", "minecraft:mangrove_swamp", "minecraft:deep_dark", "minecraft:mushroom_fields", "minecraft:jagged_peaks"] {
+    assert!(biomes.iter().any(|b| b == biome), "Missing {}", biome);
+  }
+  // The terrain shaping functions are bundled, so depth is sampled below
+  // the surface and finds underground biomes
+  let depth = overworld.router().compute("depth", -2048, -48, -512).unwrap();
+  assert!(depth > 1.1, "Depth {} is too shallow", depth);
+  assert_eq!(overworld.biome_source().biome_id(overworld.biome(-2048, -48, -512)), "minecraft:deep_dark");
+  assert_ne!(overworld.biome_source().biome_id(overworld.biome(-2048, 160, -512)), "minecraft:deep_dark");
+  let amplified = world_preset::WorldPreset::load(&registry, "amplified").unwrap().dimension("overworld", &registry, 123).unwrap();
+  assert_ne!(amplified.router().compute("depth", -2048, 64, -512), overworld.router().compute("depth", -2048, 64, -512));
+  for x in (-4096..4096).step_by(512) {
+    let continents = overworld.router().compute("continents", x, 64, 0).unwrap();
+    let biome = overworld.biome_source().biome_id(overworld.biome(x, 64, 0));
+    if continents > -1.0 && continents < -0.2 {
+      assert!(biome.ends_with("ocean"), "{} at continentalness {}", biome, continents);
+    } else if continents > -0.1 {
+      assert!(!biome.ends_with("ocean"), "{} at continentalness {}", biome, continents);
+    }
+  }
+
+  let nether = dimension::Dimension::load(&registry, "the_nether", 123).unwrap();
+  assert_eq!(nether.biome_source().possible_biomes().len(), 5);
+  assert!(nether.settings().legacy_random_source);
+
+  // Datapacks on top of the vanilla data replace parts of it
+  let wasm = vanilla_dimension(123, "1.20.1", &test_datapack().to_string(), "minecraft:overworld").unwrap();
+  assert_eq!(dimension_biomes(&wasm), vec!["minecraft:ocean", "minecraft:plains"]);
+  let region = stats::Region::new(0, 0, 512, 64, 128).unwrap();
+  let stats = datapack_biome_stats("1.20", "{}", "the_end", 1, 3, &region).unwrap();
+  assert_eq!((stats.seeds(), stats.biomes()), (2, vec![0]));
+  assert_eq!(vanilla_noise_parameters("1.20", "minecraft:ridge").unwrap(), noise::NoiseParameters::new(-7, &[1.0, 2.0, 1.0, 0.0, 0.0, 0.0]));
+}
+
+#[cfg(feature = "vanilla")]
+#[test]
+fn vanilla_versions() {
+  let names = vanilla::Version::ALL.iter().map(|v| v.name()).collect::<Vec<_>>();
+  assert_eq!(names, ["1.18", "1.18.2", "1.19", "1.20"]);
+  assert_eq!(vanilla::Version::from_name("1.18.1"), Some(vanilla::Version::V1_18));
+  assert_eq!(vanilla::Version::from_name("1.19.4"), Some(vanilla::Version::V1_19));
+  let has = |version: vanilla::Version, biome: &str| version.overworld_biomes().iter().any(|b| b["biome"] == biome);
+  assert!(!has(vanilla::Version::V1_18_2, "minecraft:mangrove_swamp"));
+  assert!(!has(vanilla::Version::V1_18_2, "minecraft:deep_dark"));
+  assert!(has(vanilla::Version::V1_19, "minecraft:deep_dark"));
+  assert!(!has(vanilla::Version::V1_19, "minecraft:cherry_grove"));
+  assert!(has(vanilla::Version::V1_20, "minecraft:cherry_grove"));
+}
+
+#[cfg(feature = "vanilla-1-20")]
+#[test]
+fn world_presets() {
+  let registry = vanilla::Version::latest().registry();
+  let normal = world_preset::WorldPreset::load(&registry, "normal").unwrap();
+  assert_eq!(normal.dimension_ids(), vec!["minecraft:overworld", "minecraft:the_end", "minecraft:the_nether"]);
+  assert!(world_preset::WorldPreset::load(&registry, "minecraft:missing").is_err());
+  assert!(normal.dimension("minecraft:the_moon", &registry, 1).is_err());
+  let end = normal.dimension("the_end", &registry, 1).unwrap();
+  assert_eq!(end.biome_source().biome_id(end.biome(0, 64, 0)), "minecraft:the_end");
+
+  // Large biomes samples the climate with the large noises, whose first octaves are lower
+  let overworld = normal.dimension("overworld", &registry, 1).unwrap();
+  let large = world_preset::WorldPreset::load(&registry, "large_biomes").unwrap().dimension("overworld", &registry, 1).unwrap();
+  assert_eq!(large.settings().noise_router["continents"], "minecraft:overworld_large_biomes/continents");
+  assert_eq!(large.settings().noise_router["temperature"]["noise"], "minecraft:temperature_large");
+  assert_ne!(large.router().compute("erosion", 100, 0, 0), overworld.router().compute("erosion", 100, 0, 0));
+  assert_eq!(large.biome_source().possible_biomes(), overworld.biome_source().possible_biomes());
+
+  let single = world_preset::WorldPreset::load(&registry, "single_biome_surface").unwrap().dimension("overworld", &registry, 1).unwrap();
+  assert_eq!(single.biome_source().possible_biomes(), ["minecraft:plains"]);
+  assert_eq!(single.biome(1000, 64, -1000), 0);
+  assert!(single.biome_source().parameters().is_none());
+
+  let flat = world_preset::WorldPreset::load(&registry, "flat").unwrap().dimension("overworld", &registry, 1).unwrap();
+  match flat.generator() {
+    dimension::Generator::Flat(layers) => assert_eq!(layers.iter().map(|l| (l.block.as_str(), l.height)).collect::<Vec<_>>(), [("minecraft:bedrock", 1), ("minecraft:dirt", 2), ("minecraft:grass_block", 1)]),
+    generator => panic!("Expected a flat generator, got {:?}", generator),
+  }
+  assert_eq!(flat.router().compute("continents", 100, 0, 100), Some(0.0));
+  assert_eq!(flat.biome_source().biome_id(flat.biome(0, 0, 0)), "minecraft:plains");
+
+  let nether = preset_dimension(1, "1.20", "{}", "minecraft:amplified", "minecraft:the_nether").unwrap();
+  assert_eq!(dimension_biomes(&nether).len(), 5);
+}
+
+#[test]
+fn biome_sources() {
+  use biome_source::BiomeSource;
+  let registry = datapack::Datapack::from_json(&test_datapack()).unwrap().registry().unwrap();
+  let sampler = dimension::Dimension::load(&registry, "overworld", 1).unwrap().sampler().clone();
+
+  let fixed = biome_source::from_json(&serde_json::json!({ "type": "fixed", "biome": "desert" }), &registry).unwrap();
+  assert_eq!(fixed.possible_biomes(), ["minecraft:desert"]);
+  assert_eq!(fixed.get_noise_biome(1000, 0, -1000, &sampler), 0);
+  assert!(fixed.parameters().is_none());
+
+  // Stripes are 1 << (scale + 2) quarts wide and repeat along both axes
+  let checkerboard = biome_source::CheckerboardBiomeSource::new(vec!["minecraft:a".into(), "minecraft:b".into(), "minecraft:c".into()], 0).unwrap();
+  let row = (-6..6).map(|x| checkerboard.get_noise_biome(x, 0, 0, &sampler)).collect::<Vec<_>>();
+  assert_eq!(row, [1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1]);
+  assert_eq!(checkerboard.get_noise_biome(4, 0, 4, &sampler), 2);
+  assert!(biome_source::CheckerboardBiomeSource::new(vec![], 2).is_err());
+  assert!(biome_source::CheckerboardBiomeSource::new(vec!["minecraft:a".into()], 63).is_err());
+  let tagged = biome_source::from_json(&serde_json::json!({ "type": "minecraft:checkerboard", "biomes": "#is_ocean" }), &registry).unwrap();
+  assert_eq!(tagged.possible_biomes(), ["minecraft:ocean", "minecraft:deep_ocean"]);
+  assert_eq!(tagged.get_noise_biome(16, 0, 0, &sampler), 1);
+
+  let multi_noise = biome_source::from_json(&serde_json::json!({ "type": "multi_noise", "preset": "test" }), &registry).unwrap();
+  let parameters = multi_noise.parameters().unwrap();
+  assert_eq!(multi_noise.get_noise_biome(25, 16, -50, &sampler), parameters.find(sampler.sample(25, 16, -50)));
+  assert!(biome_source::from_json(&serde_json::json!({ "type": "minecraft:unknown" }), &registry).is_err());
+
+  // Grid exports accept any source
+  let grid = grid::Grid::flat(grid::Axis::new(0.0, 16.0, 8), 0.0, grid::Axis::new(0.0, 16.0, 1));
+  let source = biome_source::DynBiomeSource::new(std::sync::Arc::new(checkerboard));
+  assert_eq!(biome_source_noise(&source, &sampler, &grid), [0, 1, 2, 0, 1, 2, 0, 1]);
+  assert_eq!(biome_source_biomes(&source), ["minecraft:a", "minecraft:b", "minecraft:c"]);
+}
+
+#[test]
+fn end_biomes() {
+  use biome_source::BiomeSource;
+  let registry = registry::Registry::new();
+  let settings = router::NoiseGeneratorSettings::from_json(&serde_json::json!({
+    "sea_level": 0, "legacy_random_source": true,
+    "noise": { "min_y": 0, "height": 128, "size_horizontal": 2, "size_vertical": 1 },
+    "noise_router": { "erosion": { "type": "minecraft:cache_2d", "argument": { "type": "minecraft:end_islands" } } },
+  })).unwrap();
+  let router = router::NoiseRouter::new(&settings, &registry, 12345).unwrap();
+  let sampler = sampler::Sampler::new(&router);
+
+  // The main island has its full height at the origin and fades out
+  assert_eq!(router.compute("erosion", 0, 0, 0), Some((80.0 - 8.0) / 128.0));
+  assert_eq!(router.compute("erosion", 900, 0, 0), Some((-100.0 - 8.0) / 128.0));
+
+  let end = biome_source::TheEndBiomeSource::new();
+  assert_eq!(end.get_noise_biome(0, 16, 0, &sampler), 0);
+  assert_eq!(end.get_noise_biome(255, 16, 0, &sampler), 0);
+  let mut counts = [0; 5];
+  for qz in (-2000..2000).step_by(40) {
+    for qx in (-2000..2000).step_by(40) {
+      counts[end.get_noise_biome(qx, 16, qz, &sampler) as usize] += 1;
+    }
+  }
+  assert!(counts.iter().all(|&c| c > 0), "{:?}", counts);
+}
+
+#[test]
+fn explain_biomes() {
+  let parameters = climate::ParameterList::new(&[
+    (climate::parameters(0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0), 2),
+    (climate::parameters(0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0), 5),
+    (climate::parameters(0.5, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0), 5),
+    (climate::parameters(-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0), 7),
+  ]);
+  let target = || climate::target(0.1, 0.0, 0.3, 0.0, 0.0, 0.0);
+  let explanation = parameters.explain(target(), 5);
+  let chosen = explanation.chosen();
+  assert_eq!(chosen.biome, parameters.find(target()));
+  assert_eq!(chosen.biome, 2);
+  assert_eq!(chosen.distances, [1000 * 1000, 0, 3000 * 3000, 0, 0, 0, 0]);
+  assert_eq!(chosen.fitness(), 10_000_000);
+  assert_eq!(chosen.ranges[2], [0.0, 0.0]);
+
+  // One entry per other biome, the closest of its entries, closest first
+  let runners_up = explanation.runners_up();
+  assert_eq!(runners_up.iter().map(|m| m.biome).collect::<Vec<_>>(), [5, 7]);
+  assert_eq!(runners_up[0].fitness(), 1000 * 1000 + 7000 * 7000);
+  assert_eq!(runners_up[1].fitness(), 11000 * 11000 + 3000 * 3000);
+  assert_eq!(parameters.explain(target(), 1).runners_up().len(), 1);
+
+  let registry = datapack::Datapack::from_json(&test_datapack()).unwrap().registry().unwrap();
+  let dimension = dimension::Dimension::load(&registry, "overworld", 1).unwrap();
+  let explanation = dimension.explain(40, 16, -80, 3).unwrap();
+  assert_eq!(explanation.biome(), dimension.biome(40, 16, -80));
+  let parameters = dimension.biome_source().parameters().unwrap();
+  assert_eq!(explanation.chosen(), parameters.explain(dimension.sampler().sample(10, 4, -20), 3).chosen());
+  assert!(explanation.runners_up().iter().all(|m| m.fitness() >= explanation.chosen().fitness()));
+}
+
+#[test]
+fn analyze_parameters() {
+  let full = climate::Param::span(-1.0, 1.0);
+  let entry = |temperature: climate::Param, offset: f64| climate::ParamPoint::new(temperature, full, full, full, full, full, offset);
+  let parameters = climate::ParameterList::new(&[
+    (entry(full, 0.0), 0),
+    (entry(climate::Param::span(0.0, 0.5), 0.1), 1),
+    (entry(climate::Param::span(1.0, 2.0), 0.0), 2),
+    (entry(climate::Param::span(1.0, 2.0), 0.0), 3),
+  ]);
+  let analysis = parameters.analyze(2000, 0);
+  assert_eq!(analysis.biomes(), [0, 1, 2, 3]);
+  assert_eq!(analysis.counts().iter().sum::<usize>(), 2000);
+  assert!((analysis.coverage().iter().sum::<f64>() - 1.0).abs() < 1e-9);
+  assert!(analysis.unreachable().contains(&1));
+
+  // The offset makes the second entry lose everywhere inside the first one
+  let mut shadows = analysis.shadows().iter().map(|s| (s.entry.biome, s.by.biome, s.mutual)).collect::<Vec<_>>();
+  shadows.sort_unstable();
+  assert_eq!(shadows.len(), 2);
+  assert_eq!(shadows[0], (1, 0, false));
+  assert!(shadows[1] == (2, 3, true) || shadows[1] == (3, 2, true));
+  assert_eq!(analysis.shadowed_ranges().len(), 2 * 14);
+  let offset = analysis.shadows().iter().find(|s| s.entry.biome == 1).unwrap();
+  assert_eq!(offset.entry.ranges[0], [0.0, 0.5]);
+  assert_eq!(offset.entry.ranges[6], [0.1, 0.1]);
+  assert_eq!(analysis.mutual(), analysis.shadows().iter().map(|s| s.mutual as u8).collect::<Vec<_>>());
+}
+
+#[test]
+fn biome_stats() {
+  let registry = datapack::Datapack::from_json(&test_datapack()).unwrap().registry().unwrap();
+  let load = |seed| dimension::Dimension::load(&registry, "overworld", seed);
+  let dimension = load(1).unwrap();
+  let region = stats::Region::new(100, -100, 256, 64, 32).unwrap();
+  assert_eq!(region.size(), 17);
+  assert!(stats::Region::new(0, 0, 256, 64, 0).is_err());
+  assert!(stats::Region::new(0, 0, -1, 64, 16).is_err());
+
+  let stats = stats::BiomeStats::collect(dimension.biome_source(), dimension.sampler(), &region);
+  assert_eq!(stats.samples(), 17 * 17);
+  assert_eq!(stats.counts().iter().sum::<u32>(), 17 * 17);
+  assert!((stats.shares().iter().sum::<f64>() - 1.0).abs() < 1e-9);
+  let center = dimension.biome(100, 64, -100);
+  assert_eq!(stats.get(center).unwrap().nearest, 0.0);
+  assert!(stats.nearest().iter().all(|&d| (0.0..=256.0 * 2_f64.sqrt()).contains(&d)));
+
+  // Every pair of neighbouring samples with different biomes is counted once
+  let biome = |ix: i32, iz: i32| dimension.biome(100 + (ix - 8) * 32, 64, -100 + (iz - 8) * 32);
+  let mut borders = 0;
+  for iz in 0..17 {
+    for ix in 0..17 {
+      borders += (ix < 16 && biome(ix, iz) != biome(ix + 1, iz)) as u32;
+      borders += (iz < 16 && biome(ix, iz) != biome(ix, iz + 1)) as u32;
+    }
+  }
+  assert_eq!(stats.adjacent_counts().iter().sum::<u32>(), borders);
+  assert!(stats.adjacency().keys().all(|(a, b)| a < b));
+
+  let seeds = stats::BiomeStats::over_seeds(&region, 1..3, load).unwrap();
+  let mut merged = stats.clone();
+  let second = load(2).unwrap();
+  merged.merge(&stats::BiomeStats::collect(second.biome_source(), second.sampler(), &region));
+  assert_eq!(seeds, merged);
+  assert_eq!(seeds.seeds(), 2);
+  assert_eq!(seeds.samples(), 2 * 17 * 17);
+  assert!(seeds.biomes().iter().all(|&b| seeds.get(b).unwrap().mean_nearest() >= seeds.get(b).unwrap().nearest));
+
+  // Samples are snapped to quart positions, so blocks -8, -6, ..., 8 fall in
+  // stripes -2, -2, -1, -1, 0, 0, 1, 1, 2 of a checkerboard of scale 0
+  let checkerboard = biome_source::CheckerboardBiomeSource::new(vec!["minecraft:plains".to_string(), "minecraft:desert".to_string()], 0).unwrap();
+  let small = stats::Region::new(0, 0, 8, 64, 2).unwrap();
+  let stats = stats::BiomeStats::collect(&checkerboard, dimension.sampler(), &small);
+  assert_eq!(stats.counts(), vec![5 * 5 + 4 * 4, 2 * 5 * 4]);
+  let end = biome_source::TheEndBiomeSource::new();
+  assert_eq!(stats::BiomeStats::collect(&end, dimension.sampler(), &region).counts(), vec![17 * 17]);
+}
+
+#[test]
+fn rtree_parity() {
+  let point = |temperature: f64| climate::parameters(temperature, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+  let equidistant = || climate::target(0.1, 0.0, 0.0, 0.0, 0.0, 0.0);
+
+  // Small nodes are sorted by the sum of their absolute midpoints, so the
+  // entry closer to zero wins the tie even though it comes second
+  let parameters = climate::ParameterList::new(&[(point(0.6), 1), (point(-0.4), 2)]);
+  assert_eq!(parameters.find(equidistant()), 2);
+  let linear = parameters.clone().with_search_mode(climate::SearchMode::Linear);
+  assert_eq!(linear.find(equidistant()), 1);
+
+  // Vanilla starts from the last result, which wins ties
+  let mut last = climate::LastResult::default();
+  assert_eq!(parameters.find_from(climate::target(0.6, 0.0, 0.0, 0.0, 0.0, 0.0), &mut last), 1);
+  assert_eq!(parameters.find_from(equidistant(), &mut last), 1);
+  assert_eq!(parameters.find_from(climate::target(-0.4, 0.0, 0.0, 0.0, 0.0, 0.0), &mut last), 2);
+  assert_eq!(parameters.find_from(equidistant(), &mut last), 2);
+
+  // A single entry is always found, even from the last result of another list
+  let single = climate::ParameterList::new(&[(point(0.9), 3)]);
+  assert_eq!(single.find_from(equidistant(), &mut last), 3);
+  assert_eq!(single.with_search_mode(climate::SearchMode::Linear).find(equidistant()), 3);
+  let mut last = climate::LastResult::default();
+  let many = climate::ParameterList::new(&(0..50).map(|i| (point(i as f64 / 50.0), i)).collect::<Vec<_>>());
+  assert_eq!(many.find_from(climate::target(0.98, 0.0, 0.0, 0.0, 0.0, 0.0), &mut last), 49);
+  assert_eq!(parameters.find_from(equidistant(), &mut last), 2);
+
+  // Parameters are quantized as floats, and depth comes before weirdness
+  let explanation = climate::ParameterList::new(&[(climate::parameters(-0.78, 0.0, 0.0, 0.0, 0.3, 0.2, 0.0), 0)]).explain(equidistant(), 0);
+  assert_eq!(explanation.chosen().ranges[0], [-0.7799, -0.7799]);
+  assert_eq!(climate::PARAMETER_NAMES[4], "depth");
+  assert_eq!(explanation.chosen().ranges[4], [0.2, 0.2]);
+  assert_eq!(explanation.chosen().ranges[5], [0.3, 0.3]);
+}
+
+#[test]
+fn rtree_vanilla_ties() {
+  // Points on a coarse grid and targets halfway between them, so that many
+  // searches tie. The expected biomes are what vanilla's `Climate.RTree`
+  // returns for the same entries, which depends on how it splits them
+  let eighths = |i: i32, n: i32| ((i % (2 * n + 1)) - n) as f64 / 8.0;
+  let entries = (0..40).map(|i| {
+    (climate::parameters(eighths(i * 7, 4) * 2.0, eighths(i * 5, 3) * 2.0, eighths(i * 3, 2) * 2.0, 0.0, 0.0, 0.0, 0.0), i)
+  }).collect::<Vec<_>>();
+  let parameters = climate::ParameterList::new(&entries);
+  let target = |k: i32| match k % 2 {
+    0 => climate::target(eighths(k * 5, 4), eighths(k * 7, 5), eighths(k * 3, 3), 0.0, 0.0, 0.0),
+    _ => climate::target(eighths(k * 5, 4), 0.125, 0.125, 0.0, 0.0, 0.0),
+  };
+  let expected = [
+    17, 16, 3, 16, 16, 16, 39, 19, 34, 26, 34, 26, 2, 16, 15, 16, 6, 16, 26, 16, 26, 16, 21, 16,
+    34, 19, 12, 26, 25, 26, 38, 16, 6, 16, 19, 16, 39, 16, 17, 16, 30, 16, 12, 19, 3, 26, 34, 26,
+  ];
+  assert_eq!((0..48).map(|k| parameters.find(target(k))).collect::<Vec<_>>(), expected);
+  let mut last = climate::LastResult::default();
+  assert_eq!((0..48).map(|k| parameters.find_from(target(k), &mut last)).collect::<Vec<_>>(), expected);
+
+  // Starting from the last result, which is as close as the leaf that a
+  // fresh search finds
+  for (from, to, plain, biome) in [
+    ([0.0, 0.75, 0.25], [0.375, 0.625, 0.125], 29, 11),
+    ([-0.5, 0.75, -0.25], [-0.5, 0.375, -0.125], 12, 22),
+    ([-0.5, -0.25, 0.25], [-0.625, -0.25, 0.0], 17, 31),
+  ] {
+    let mut last = climate::LastResult::default();
+    assert_eq!(parameters.find_from(climate::target(from[0], from[1], from[2], 0.0, 0.0, 0.0), &mut last), biome);
+    let target = || climate::target(to[0], to[1], to[2], 0.0, 0.0, 0.0);
+    assert_eq!(parameters.find(target()), plain);
+    assert_eq!(parameters.find_from(target(), &mut last), biome);
+  }
+}
+
+#[test]
+fn rtree_matches_linear_search() {
+  // Values on a coarse grid make ties and overlapping entries common
+  let mut random = random::XoroshiroRandomSource::from(44);
+  let value = |random: &mut random::XoroshiroRandomSource| (random.next_int_max(9) - 4) as f64 / 4.0;
+  for case in 0..100 {
+    let len = 1 + random.next_int_max(if case % 2 == 0 { 12 } else { 300 }) as usize;
+    let entries = (0..len).map(|i| {
+      let mut param = || {
+        let (a, b) = (value(&mut random), value(&mut random));
+        climate::Param::span(a.min(b), a.max(b))
+      };
+      let point = climate::ParamPoint::new(param(), param(), param(), param(), param(), param(), random.next_int_max(3) as f64 / 10.0);
+      (point, (i % 7) as climate::Biome)
+    }).collect::<Vec<_>>();
+    let tree = climate::ParameterList::new(&entries);
+    let linear = tree.clone().with_search_mode(climate::SearchMode::Linear);
+    let mut last = climate::LastResult::default();
+    for _ in 0..50 {
+      let values = [0; 6].map(|_| value(&mut random) * 1.25);
+      let target = || climate::target(values[0], values[1], values[2], values[3], values[4], values[5]);
+      let expected = linear.explain(target(), 0).chosen().fitness();
+      let explanation = tree.explain(target(), 0);
+      assert_eq!(explanation.chosen().fitness(), expected, "case {} target {:?}", case, values);
+      assert_eq!(explanation.biome(), tree.find(target()));
+      let biome = tree.find_from(target(), &mut last);
+      assert!(entries.iter().any(|(point, b)| *b == biome && point.fitness(&target()) == expected), "case {} target {:?}", case, values);
+    }
+  }
+}
+
+#[cfg(all(feature = "snapshot", feature = "vanilla-1-20"))]
+#[test]
+fn snapshots() {
+  use snapshot::Snapshot;
+  let registry = vanilla::Version::latest().registry();
+  let world = dimension::Dimension::load(&registry, "overworld", 1).unwrap();
+  let sampler = sampler::Sampler::from_snapshot(&sampler_snapshot(world.sampler()).unwrap()).unwrap();
+  let biomes = serde_json::Value::Array(vanilla::Version::latest().overworld_biomes());
+  let parameters = climate::ParameterList::from_json(&biomes).unwrap().0.with_search_mode(climate::SearchMode::Linear);
+  let restored = climate::ParameterList::from_snapshot(&parameters.to_snapshot().unwrap()).unwrap();
+  assert_eq!(restored.search_mode(), climate::SearchMode::Linear);
+  for (x, z) in [(0, 0), (1000, -3000), (-12345, 678), (40000, 40000)] {
+    assert_eq!(sampler.target(x, 64, z).values(), world.sampler().target(x, 64, z).values());
+    assert_eq!(restored.find(sampler.target(x, 64, z)), parameters.find(world.sampler().target(x, 64, z)));
+  }
+
+  let normal = noise::NormalNoise::from_seed(123, -4, &[1.0, 2.0, 0.5]);
+  let snapshot = normal.to_snapshot().unwrap();
+  assert_eq!(noise::NormalNoise::from_snapshot(&snapshot).unwrap().sample(5.4, -4.0, 0.7), normal.sample(5.4, -4.0, 0.7));
+  assert!(noise::PerlinNoise::from_snapshot(&snapshot).err().unwrap().contains("normal_noise"));
+  assert!(noise::NormalNoise::from_snapshot(&snapshot[..snapshot.len() - 1]).is_err());
+  assert!(noise::NormalNoise::from_snapshot(b"DSRS").is_err());
+  assert!(noise::NormalNoise::from_snapshot(&[0; 64]).is_err());
+
+  // A single entry is a tree of one leaf. Giving it a child makes its entry unreachable.
+  let single = climate::ParameterList::new(&[(climate::parameters(0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0), 2)]);
+  let mut snapshot = single.to_snapshot().unwrap();
+  let header = 4 + 2 + 1 + "parameter_list".len();
+  let children = header + 8 + 2 * 7 * 8;
+  assert_eq!(snapshot[children..children + 16], [1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+  snapshot[children + 12] = 1;
+  assert!(climate::ParameterList::from_snapshot(&snapshot).is_err());
+
+  // Two entries are a root with two leaves. Making the root its own first child is a cycle.
+  let pair = climate::ParameterList::new(&[
+    (climate::parameters(-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0), 1),
+    (climate::parameters(0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0), 2),
+  ]);
+  let mut snapshot = pair.to_snapshot().unwrap();
+  let children = header + 8 + 3 * 2 * 7 * 8;
+  assert_eq!(snapshot[children..children + 16], [3, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0]);
+  snapshot[children + 8] = 0;
+  assert!(climate::ParameterList::from_snapshot(&snapshot).is_err());
+
+  // Splines need a value and derivative for every location
+  let spline = |derivatives: Vec<f32>| density::DensityFunction::Spline(Box::new(spline::CubicSpline::Multipoint {
+    coordinate: Box::new(density::DensityFunction::Constant(0.0)),
+    locations: vec![-1.0, 1.0],
+    values: vec![spline::CubicSpline::Constant(0.0), spline::CubicSpline::Constant(1.0)],
+    derivatives,
+  }));
+  assert!(spline(vec![0.0, 0.0]).check().is_ok());
+  assert!(spline(vec![0.0]).check().is_err());
+  assert!(spline(vec![0.0, f32::NAN]).check().is_err());
+  let clamp = density::DensityFunction::Clamp { input: Box::new(density::DensityFunction::Constant(0.0)), min: f64::NAN, max: 1.0 };
+  assert!(clamp.check().is_err());
+}
@@ -0,0 +1,79 @@
+use wasm_bindgen::prelude::*;
+use super::grid::{ Axis, AxisOrder, Grid };
+
+/// Splits a horizontal area into square tiles of `size * size` samples, so
+/// that several workers can each fill the buffer of a different tile.
+///
+/// Tiles are numbered row by row, and the samples in a tile buffer are
+/// stored row-major with `x` increasing fastest, ready for image data.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct TileLayout {
+	x_from: f64,
+	z_from: f64,
+	y: f64,
+	step: f64,
+	size: usize,
+	tiles_x: usize,
+	tiles_z: usize,
+}
+
+impl TileLayout {
+	pub fn new(x_from: f64, z_from: f64, y: f64, step: f64, size: usize, tiles_x: usize, tiles_z: usize) -> Result<Self, String> {
+		if size == 0 {
+			return Err("Tile size must be positive".to_string())
+		}
+		Ok(Self { x_from, z_from, y, step, size, tiles_x, tiles_z })
+	}
+
+	pub fn tile_count(&self) -> usize {
+		self.tiles_x * self.tiles_z
+	}
+
+	/// The number of samples in a single tile.
+	pub fn tile_len(&self) -> usize {
+		self.size * self.size
+	}
+
+	/// The sample positions of a tile, as a flat grid in buffer order.
+	pub fn grid(&self, index: usize) -> Result<Grid, String> {
+		if index >= self.tile_count() {
+			return Err(format!("Tile index {} is out of bounds", index))
+		}
+		let tile_x = (index % self.tiles_x * self.size) as f64;
+		let tile_z = (index / self.tiles_x * self.size) as f64;
+		let x = Axis::new(tile_x * self.step + self.x_from, self.step, self.size);
+		let z = Axis::new(tile_z * self.step + self.z_from, self.step, self.size);
+		Ok(Grid::flat(x, self.y, z).with_order(AxisOrder::ZYX))
+	}
+
+	/// Calls `f` for every sample position in the tile, in buffer order.
+	pub fn for_each<F: FnMut(usize, f64, f64, f64)>(&self, index: usize, mut f: F) -> Result<(), String> {
+		let mut i = 0;
+		self.grid(index)?.for_each(|x, y, z| {
+			f(i, x, y, z);
+			i += 1;
+		});
+		Ok(())
+	}
+
+	/// Fills `out` with one value per sample in the tile.
+	pub fn fill<T, F: Fn(f64, f64, f64) -> T>(&self, index: usize, out: &mut [T], f: F) -> Result<(), String> {
+		self.check_buffer(out.len(), 1)?;
+		self.for_each(index, |i, x, y, z| out[i] = f(x, y, z))
+	}
+
+	/// Fills `out` with `N` consecutive values per sample in the tile.
+	pub fn fill_chunks<T: Copy, const N: usize, F: Fn(f64, f64, f64) -> [T; N]>(&self, index: usize, out: &mut [T], f: F) -> Result<(), String> {
+		self.check_buffer(out.len(), N)?;
+		self.for_each(index, |i, x, y, z| out[i * N..(i + 1) * N].copy_from_slice(&f(x, y, z)))
+	}
+
+	fn check_buffer(&self, len: usize, values: usize) -> Result<(), String> {
+		let needed = self.tile_len() * values;
+		if len < needed {
+			return Err(format!("Tile buffer of {} values is too small, needs {}", len, needed))
+		}
+		Ok(())
+	}
+}
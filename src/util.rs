@@ -7,21 +7,120 @@ pub fn lerp2(a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> f64 {
 	lerp(b, lerp(a, c, d), lerp(a, e, f))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn lerp3(a: f64, b: f64, c: f64, d: f64, e: f64, f: f64, g: f64, h: f64, i: f64, j: f64, k: f64) -> f64 {
 	lerp(c, lerp2(a, b, d, e, f, g), lerp2(a, b, h, i, j, k))
 }
 
+/// Like vanilla's `Mth.clamp`, which gives `min` for values below it and
+/// doesn't panic when `min > max`, unlike `f64::clamp`.
+pub fn clamp(value: f64, min: f64, max: f64) -> f64 {
+	if value < min {
+		min
+	} else if value > max {
+		max
+	} else {
+		value
+	}
+}
+
+pub fn clamped_lerp(a: f64, b: f64, t: f64) -> f64 {
+	if t < 0.0 {
+		a
+	} else if t > 1.0 {
+		b
+	} else {
+		lerp(t, a, b)
+	}
+}
+
+pub fn clamped_map(x: f64, from_min: f64, from_max: f64, to_min: f64, to_max: f64) -> f64 {
+	clamped_lerp(to_min, to_max, (x - from_min) / (from_max - from_min))
+}
+
+pub fn positive_modulo(x: f64, y: f64) -> f64 {
+	(x % y + y) % y
+}
+
 pub fn smoothstep(x: f64) -> f64 {
 	x * x * x * (x * (x * 6.0 - 15.0) + 10.0)
 }
 
-const GRADIENT: [(f64, f64, f64); 16] = [(1.0, 1.0, 0.0), (-1.0, 1.0, 0.0), (1.0, -1.0, 0.0), (-1.0, -1.0, 0.0), (1.0, 0.0, 1.0), (-1.0, 0.0, 1.0), (1.0, 0.0, -1.0), (-1.0, 0.0, -1.0), (0.0, 1.0, 1.0), (0.0, -1.0, 1.0), (0.0, 1.0, -1.0), (0.0, -1.0, -1.0), (1.0, 1.0, 0.0), (0.0, -1.0, 1.0), (-1.0, 1.0, 0.0), (0.0, -1.0, -1.0)];
+pub fn smoothstep_derivative(x: f64) -> f64 {
+	30.0 * x * x * (x - 1.0) * (x - 1.0)
+}
+
+pub(crate) const GRADIENT: [(f64, f64, f64); 16] = [(1.0, 1.0, 0.0), (-1.0, 1.0, 0.0), (1.0, -1.0, 0.0), (-1.0, -1.0, 0.0), (1.0, 0.0, 1.0), (-1.0, 0.0, 1.0), (1.0, 0.0, -1.0), (-1.0, 0.0, -1.0), (0.0, 1.0, 1.0), (0.0, -1.0, 1.0), (0.0, 1.0, -1.0), (0.0, -1.0, -1.0), (1.0, 1.0, 0.0), (0.0, -1.0, 1.0), (-1.0, 1.0, 0.0), (0.0, -1.0, -1.0)];
 
 pub fn grad_dot(a: i32, b: f64, c: f64, d: f64) -> f64 {
 	let grad = GRADIENT[(a & 15) as usize];
-	return grad.0 * b + grad.1 * c + grad.2 * d
+	grad.0 * b + grad.1 * c + grad.2 * d
 }
 
 pub fn wrap(value: f64) -> f64 {
 	value - (value / 3.3554432e7 + 0.5).floor() * 3.3554432e7
 }
+
+/// Port of fdlibm's `__ieee754_log`, matching Java's `StrictMath.log` bit
+/// for bit where the platform logarithm may round differently.
+#[allow(clippy::excessive_precision)]
+pub fn strict_log(x: f64) -> f64 {
+	const LN2_HI: f64 = 6.93147180369123816490e-01;
+	const LN2_LO: f64 = 1.90821492927058770002e-10;
+	const TWO54: f64 = 1.80143985094819840000e+16;
+	const LG1: f64 = 6.666666666666735130e-01;
+	const LG2: f64 = 3.999999999940941908e-01;
+	const LG3: f64 = 2.857142874366239149e-01;
+	const LG4: f64 = 2.222219843214978396e-01;
+	const LG5: f64 = 1.818357216161805012e-01;
+	const LG6: f64 = 1.531383769920937332e-01;
+	const LG7: f64 = 1.479819860511658591e-01;
+
+	let mut x = x;
+	let mut hx = (x.to_bits() >> 32) as i32;
+	let lx = x.to_bits() as u32;
+	let mut k = 0;
+	if hx < 0x00100000 {
+		if ((hx & 0x7fffffff) as u32 | lx) == 0 {
+			return f64::NEG_INFINITY
+		}
+		if hx < 0 {
+			return f64::NAN
+		}
+		k -= 54;
+		x *= TWO54;
+		hx = (x.to_bits() >> 32) as i32;
+	}
+	if hx >= 0x7ff00000 {
+		return x + x
+	}
+	k += (hx >> 20) - 1023;
+	hx &= 0x000fffff;
+	let i = (hx + 0x95f64) & 0x100000;
+	x = f64::from_bits((((hx | (i ^ 0x3ff00000)) as u32 as u64) << 32) | (x.to_bits() & 0xffffffff));
+	k += i >> 20;
+	let f = x - 1.0;
+	let dk = k as f64;
+	if (0x000fffff & (2 + hx)) < 3 {
+		if f == 0.0 {
+			return if k == 0 { 0.0 } else { dk * LN2_HI + dk * LN2_LO }
+		}
+		let r = f * f * (0.5 - 0.33333333333333333 * f);
+		return if k == 0 { f - r } else { dk * LN2_HI - ((r - dk * LN2_LO) - f) }
+	}
+	let s = f / (2.0 + f);
+	let z = s * s;
+	let w = z * z;
+	let i = (hx - 0x6147a) | (0x6b851 - hx);
+	let t1 = w * (LG2 + w * (LG4 + w * LG6));
+	let t2 = z * (LG1 + w * (LG3 + w * (LG5 + w * LG7)));
+	let r = t2 + t1;
+	if i > 0 {
+		let hfsq = 0.5 * f * f;
+		if k == 0 { f - (hfsq - s * (hfsq + r)) } else { dk * LN2_HI - ((hfsq - (s * (hfsq + r) + dk * LN2_LO)) - f) }
+	} else if k == 0 {
+		f - s * (f - r)
+	} else {
+		dk * LN2_HI - ((s * (f - r) - dk * LN2_LO) - f)
+	}
+}
@@ -0,0 +1,500 @@
+//! Worldgen data of the vanilla game, so that callers can pick a game version
+//! instead of shipping the JSON themselves. Each version is behind its own
+//! `vanilla-*` feature.
+//!
+//! The bundled files cover the noise routers: all noises and density
+//! functions that they use, including the terrain shaping splines and caves,
+//! the `overworld`, `large_biomes`, `amplified`, `nether` and `end` noise
+//! settings without their surface rules, the three vanilla dimensions, the
+//! overworld and nether biome parameter lists, and the `normal`,
+//! `large_biomes`, `amplified`, `single_biome_surface` and `flat` world
+//! presets. Every version uses the density functions and noise settings of
+//! 1.20, only the biome parameters differ.
+//! Datapacks can be applied on top of [`Version::registry`] to replace them.
+
+use serde_json::{ json, Value };
+use super::datapack::Datapack;
+use super::registry::Registry;
+
+const WORLDGEN: &str = include_str!("../data/vanilla/worldgen.json");
+const PARAMETER_LISTS: &str = "worldgen/multi_noise_biome_source_parameter_list";
+
+/// A game version whose worldgen data is bundled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Version {
+	#[cfg(feature = "vanilla-1-18")]
+	V1_18,
+	#[cfg(feature = "vanilla-1-18-2")]
+	V1_18_2,
+	#[cfg(feature = "vanilla-1-19")]
+	V1_19,
+	#[cfg(feature = "vanilla-1-20")]
+	V1_20,
+}
+
+impl Version {
+	/// The enabled versions, from oldest to newest.
+	pub const ALL: &'static [Version] = &[
+		#[cfg(feature = "vanilla-1-18")]
+		Version::V1_18,
+		#[cfg(feature = "vanilla-1-18-2")]
+		Version::V1_18_2,
+		#[cfg(feature = "vanilla-1-19")]
+		Version::V1_19,
+		#[cfg(feature = "vanilla-1-20")]
+		Version::V1_20,
+	];
+
+	pub fn latest() -> Version {
+		*Self::ALL.last().expect("At least one vanilla version is enabled")
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			#[cfg(feature = "vanilla-1-18")]
+			Version::V1_18 => "1.18",
+			#[cfg(feature = "vanilla-1-18-2")]
+			Version::V1_18_2 => "1.18.2",
+			#[cfg(feature = "vanilla-1-19")]
+			Version::V1_19 => "1.19",
+			#[cfg(feature = "vanilla-1-20")]
+			Version::V1_20 => "1.20",
+		}
+	}
+
+	/// Finds the bundled data used by a game version like `1.19.4`, which is
+	/// the newest enabled version that is not newer than it.
+	pub fn from_name(name: &str) -> Option<Version> {
+		let mut parts = name.split('.').map(|p| p.parse::<u32>().ok());
+		let release = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+			(Some(Some(1)), Some(Some(minor)), None, None) => (minor, 0),
+			(Some(Some(1)), Some(Some(minor)), Some(Some(patch)), None) => (minor, patch),
+			_ => return None,
+		};
+		Self::ALL.iter().rev().find(|v| v.release() <= release).copied()
+	}
+
+	fn release(&self) -> (u32, u32) {
+		match self {
+			#[cfg(feature = "vanilla-1-18")]
+			Version::V1_18 => (18, 0),
+			#[cfg(feature = "vanilla-1-18-2")]
+			Version::V1_18_2 => (18, 2),
+			#[cfg(feature = "vanilla-1-19")]
+			Version::V1_19 => (19, 0),
+			#[cfg(feature = "vanilla-1-20")]
+			Version::V1_20 => (20, 0),
+		}
+	}
+
+	/// The bundled data as a datapack.
+	pub fn datapack(&self) -> Datapack {
+		let files = serde_json::from_str::<Value>(WORLDGEN).expect("Bundled worldgen data is valid JSON");
+		let mut datapack = Datapack::from_json(&files).expect("Bundled worldgen data is a valid datapack");
+		datapack.insert(&format!("data/minecraft/{}/overworld.json", PARAMETER_LISTS), json!({ "biomes": self.overworld_biomes() }));
+		datapack.insert(&format!("data/minecraft/{}/nether.json", PARAMETER_LISTS), json!({ "biomes": nether_biomes() }));
+		datapack
+	}
+
+	/// A registry with the bundled data, for datapacks to be applied on top.
+	pub fn registry(&self) -> Registry {
+		self.datapack().registry().expect("Bundled worldgen data is a valid datapack")
+	}
+
+	/// The biome parameters of the overworld preset, in the format of a
+	/// `multi_noise` biome source's `biomes`.
+	pub fn overworld_biomes(&self) -> Vec<Value> {
+		let mut builder = OverworldBuilder { release: self.release(), biomes: Vec::new() };
+		builder.add_biomes();
+		builder.biomes
+	}
+}
+
+/// The biome parameters of the nether preset, which is the same in all the
+/// bundled versions.
+pub fn nether_biomes() -> Vec<Value> {
+	let point = [0.0, 0.0];
+	[
+		("minecraft:nether_wastes", 0.0, 0.0, 0.0),
+		("minecraft:soul_sand_valley", 0.0, -0.5, 0.0),
+		("minecraft:crimson_forest", 0.4, 0.0, 0.0),
+		("minecraft:warped_forest", 0.0, 0.5, 0.375),
+		("minecraft:basalt_deltas", -0.5, 0.0, 0.175),
+	].iter().map(|&(biome, temperature, humidity, offset)| {
+		entry([temperature, temperature], [humidity, humidity], point, point, point, point, offset, biome)
+	}).collect()
+}
+
+type Range = [f64; 2];
+
+fn entry(temperature: Range, humidity: Range, continentalness: Range, erosion: Range, depth: Range, weirdness: Range, offset: f64, biome: &str) -> Value {
+	let param = |r: Range| if r[0] == r[1] { json!(r[0]) } else { json!(r) };
+	json!({
+		"biome": biome,
+		"parameters": {
+			"temperature": param(temperature),
+			"humidity": param(humidity),
+			"continentalness": param(continentalness),
+			"erosion": param(erosion),
+			"depth": param(depth),
+			"weirdness": param(weirdness),
+			"offset": offset,
+		},
+	})
+}
+
+fn span(from: Range, to: Range) -> Range {
+	[from[0], to[1]]
+}
+
+const FULL_RANGE: Range = [-1.0, 1.0];
+const TEMPERATURES: [Range; 5] = [[-1.0, -0.45], [-0.45, -0.15], [-0.15, 0.2], [0.2, 0.55], [0.55, 1.0]];
+const HUMIDITIES: [Range; 5] = [[-1.0, -0.35], [-0.35, -0.1], [-0.1, 0.1], [0.1, 0.3], [0.3, 1.0]];
+const EROSIONS: [Range; 7] = [[-1.0, -0.78], [-0.78, -0.375], [-0.375, -0.2225], [-0.2225, 0.05], [0.05, 0.45], [0.45, 0.55], [0.55, 1.0]];
+const FROZEN_RANGE: Range = TEMPERATURES[0];
+const UNFROZEN_RANGE: Range = [-0.45, 1.0];
+const MUSHROOM_FIELDS_CONTINENTALNESS: Range = [-1.2, -1.05];
+const DEEP_OCEAN_CONTINENTALNESS: Range = [-1.05, -0.455];
+const OCEAN_CONTINENTALNESS: Range = [-0.455, -0.19];
+const COAST_CONTINENTALNESS: Range = [-0.19, -0.11];
+const INLAND_CONTINENTALNESS: Range = [-0.11, 0.55];
+const NEAR_INLAND_CONTINENTALNESS: Range = [-0.11, 0.03];
+const MID_INLAND_CONTINENTALNESS: Range = [0.03, 0.3];
+const FAR_INLAND_CONTINENTALNESS: Range = [0.3, 1.0];
+
+type Table = [[Option<&'static str>; 5]; 5];
+
+const OCEANS: [[&str; 5]; 2] = [
+	["deep_frozen_ocean", "deep_cold_ocean", "deep_ocean", "deep_lukewarm_ocean", "warm_ocean"],
+	["frozen_ocean", "cold_ocean", "ocean", "lukewarm_ocean", "warm_ocean"],
+];
+const MIDDLE_BIOMES: Table = [
+	[Some("snowy_plains"), Some("snowy_plains"), Some("snowy_plains"), Some("snowy_taiga"), Some("taiga")],
+	[Some("plains"), Some("plains"), Some("forest"), Some("taiga"), Some("old_growth_spruce_taiga")],
+	[Some("flower_forest"), Some("plains"), Some("forest"), Some("birch_forest"), Some("dark_forest")],
+	[Some("savanna"), Some("savanna"), Some("forest"), Some("jungle"), Some("jungle")],
+	[Some("desert"), Some("desert"), Some("desert"), Some("desert"), Some("desert")],
+];
+const MIDDLE_BIOMES_VARIANT: Table = [
+	[Some("ice_spikes"), None, Some("snowy_taiga"), None, None],
+	[None, None, None, None, Some("old_growth_pine_taiga")],
+	[Some("sunflower_plains"), None, None, Some("old_growth_birch_forest"), None],
+	[None, None, Some("plains"), Some("sparse_jungle"), Some("bamboo_jungle")],
+	[None, None, None, None, None],
+];
+const PLATEAU_BIOMES: Table = [
+	[Some("snowy_plains"), Some("snowy_plains"), Some("snowy_plains"), Some("snowy_taiga"), Some("snowy_taiga")],
+	[Some("meadow"), Some("meadow"), Some("forest"), Some("taiga"), Some("old_growth_spruce_taiga")],
+	[Some("meadow"), Some("meadow"), Some("meadow"), Some("meadow"), Some("dark_forest")],
+	[Some("savanna_plateau"), Some("savanna_plateau"), Some("forest"), Some("forest"), Some("jungle")],
+	[Some("badlands"), Some("badlands"), Some("badlands"), Some("wooded_badlands"), Some("wooded_badlands")],
+];
+const PLATEAU_BIOMES_VARIANT: Table = [
+	[Some("ice_spikes"), None, None, None, None],
+	[None, None, Some("meadow"), Some("meadow"), Some("old_growth_pine_taiga")],
+	[None, None, Some("forest"), Some("birch_forest"), None],
+	[None, None, None, None, None],
+	[Some("eroded_badlands"), Some("eroded_badlands"), None, None, None],
+];
+/// Cherry groves replace some plateau variants since 1.20.
+const PLATEAU_BIOMES_VARIANT_1_20: Table = [
+	[Some("ice_spikes"), None, None, None, None],
+	[Some("cherry_grove"), None, Some("meadow"), Some("meadow"), Some("old_growth_pine_taiga")],
+	[Some("cherry_grove"), Some("cherry_grove"), Some("forest"), Some("birch_forest"), None],
+	[None, None, None, None, None],
+	[Some("eroded_badlands"), Some("eroded_badlands"), None, None, None],
+];
+const SHATTERED_BIOMES: Table = [
+	[Some("windswept_gravelly_hills"), Some("windswept_gravelly_hills"), Some("windswept_hills"), Some("windswept_forest"), Some("windswept_forest")],
+	[Some("windswept_gravelly_hills"), Some("windswept_gravelly_hills"), Some("windswept_hills"), Some("windswept_forest"), Some("windswept_forest")],
+	[Some("windswept_hills"), Some("windswept_hills"), Some("windswept_hills"), Some("windswept_forest"), Some("windswept_forest")],
+	[None, None, None, None, None],
+	[None, None, None, None, None],
+];
+
+/// A port of vanilla's `OverworldBiomeBuilder`.
+struct OverworldBuilder {
+	release: (u32, u32),
+	biomes: Vec<Value>,
+}
+
+impl OverworldBuilder {
+	fn add_biomes(&mut self) {
+		self.add_off_coast_biomes();
+		self.add_inland_biomes();
+		self.add_underground_biomes();
+	}
+
+	fn has_mangroves(&self) -> bool {
+		self.release >= (19, 0)
+	}
+
+	fn add_surface_biome(&mut self, t: Range, h: Range, c: Range, e: Range, w: Range, offset: f64, biome: &str) {
+		let biome = format!("minecraft:{}", biome);
+		self.biomes.push(entry(t, h, c, e, [0.0, 0.0], w, offset, &biome));
+		self.biomes.push(entry(t, h, c, e, [1.0, 1.0], w, offset, &biome));
+	}
+
+	fn add_underground_biome(&mut self, t: Range, h: Range, c: Range, e: Range, w: Range, offset: f64, biome: &str) {
+		self.biomes.push(entry(t, h, c, e, [0.2, 0.9], w, offset, &format!("minecraft:{}", biome)));
+	}
+
+	fn add_bottom_biome(&mut self, t: Range, h: Range, c: Range, e: Range, w: Range, offset: f64, biome: &str) {
+		self.biomes.push(entry(t, h, c, e, [1.1, 1.1], w, offset, &format!("minecraft:{}", biome)));
+	}
+
+	fn add_off_coast_biomes(&mut self) {
+		self.add_surface_biome(FULL_RANGE, FULL_RANGE, MUSHROOM_FIELDS_CONTINENTALNESS, FULL_RANGE, FULL_RANGE, 0.0, "mushroom_fields");
+		for (i, &t) in TEMPERATURES.iter().enumerate() {
+			self.add_surface_biome(t, FULL_RANGE, DEEP_OCEAN_CONTINENTALNESS, FULL_RANGE, FULL_RANGE, 0.0, OCEANS[0][i]);
+			self.add_surface_biome(t, FULL_RANGE, OCEAN_CONTINENTALNESS, FULL_RANGE, FULL_RANGE, 0.0, OCEANS[1][i]);
+		}
+	}
+
+	fn add_inland_biomes(&mut self) {
+		self.add_mid_slice([-1.0, -0.93333334]);
+		self.add_high_slice([-0.93333334, -0.7666667]);
+		self.add_peaks([-0.7666667, -0.56666666]);
+		self.add_high_slice([-0.56666666, -0.4]);
+		self.add_mid_slice([-0.4, -0.26666668]);
+		self.add_low_slice([-0.26666668, -0.05]);
+		self.add_valleys([-0.05, 0.05]);
+		self.add_low_slice([0.05, 0.26666668]);
+		self.add_mid_slice([0.26666668, 0.4]);
+		self.add_high_slice([0.4, 0.56666666]);
+		self.add_peaks([0.56666666, 0.7666667]);
+		self.add_high_slice([0.7666667, 0.93333334]);
+		self.add_mid_slice([0.93333334, 1.0]);
+	}
+
+	fn add_peaks(&mut self, w: Range) {
+		for (i, &t) in TEMPERATURES.iter().enumerate() {
+			for (j, &h) in HUMIDITIES.iter().enumerate() {
+				let middle = self.pick_middle_biome(i, j, w);
+				let middle_or_badlands = self.pick_middle_biome_or_badlands_if_hot(i, j, w);
+				let middle_or_badlands_or_slope = self.pick_middle_biome_or_badlands_if_hot_or_slope_if_cold(i, j, w);
+				let plateau = self.pick_plateau_biome(i, j, w);
+				let shattered = self.pick_shattered_biome(i, j, w);
+				let windswept_savanna = maybe_pick_windswept_savanna_biome(i, j, w, shattered);
+				let peak = self.pick_peak_biome(i, j, w);
+				self.add_surface_biome(t, h, span(COAST_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[0], w, 0.0, peak);
+				self.add_surface_biome(t, h, span(COAST_CONTINENTALNESS, NEAR_INLAND_CONTINENTALNESS), EROSIONS[1], w, 0.0, middle_or_badlands_or_slope);
+				self.add_surface_biome(t, h, span(MID_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[1], w, 0.0, peak);
+				self.add_surface_biome(t, h, span(COAST_CONTINENTALNESS, NEAR_INLAND_CONTINENTALNESS), span(EROSIONS[2], EROSIONS[3]), w, 0.0, middle);
+				self.add_surface_biome(t, h, span(MID_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[2], w, 0.0, plateau);
+				self.add_surface_biome(t, h, MID_INLAND_CONTINENTALNESS, EROSIONS[3], w, 0.0, middle_or_badlands);
+				self.add_surface_biome(t, h, FAR_INLAND_CONTINENTALNESS, EROSIONS[3], w, 0.0, plateau);
+				self.add_surface_biome(t, h, span(COAST_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[4], w, 0.0, middle);
+				self.add_surface_biome(t, h, span(COAST_CONTINENTALNESS, NEAR_INLAND_CONTINENTALNESS), EROSIONS[5], w, 0.0, windswept_savanna);
+				self.add_surface_biome(t, h, span(MID_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[5], w, 0.0, shattered);
+				self.add_surface_biome(t, h, span(COAST_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[6], w, 0.0, middle);
+			}
+		}
+	}
+
+	fn add_high_slice(&mut self, w: Range) {
+		for (i, &t) in TEMPERATURES.iter().enumerate() {
+			for (j, &h) in HUMIDITIES.iter().enumerate() {
+				let middle = self.pick_middle_biome(i, j, w);
+				let middle_or_badlands = self.pick_middle_biome_or_badlands_if_hot(i, j, w);
+				let middle_or_badlands_or_slope = self.pick_middle_biome_or_badlands_if_hot_or_slope_if_cold(i, j, w);
+				let plateau = self.pick_plateau_biome(i, j, w);
+				let shattered = self.pick_shattered_biome(i, j, w);
+				let windswept_savanna = maybe_pick_windswept_savanna_biome(i, j, w, middle);
+				let slope = self.pick_slope_biome(i, j, w);
+				let peak = self.pick_peak_biome(i, j, w);
+				self.add_surface_biome(t, h, COAST_CONTINENTALNESS, span(EROSIONS[0], EROSIONS[1]), w, 0.0, middle);
+				self.add_surface_biome(t, h, NEAR_INLAND_CONTINENTALNESS, EROSIONS[0], w, 0.0, slope);
+				self.add_surface_biome(t, h, span(MID_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[0], w, 0.0, peak);
+				self.add_surface_biome(t, h, NEAR_INLAND_CONTINENTALNESS, EROSIONS[1], w, 0.0, middle_or_badlands_or_slope);
+				self.add_surface_biome(t, h, span(MID_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[1], w, 0.0, slope);
+				self.add_surface_biome(t, h, span(COAST_CONTINENTALNESS, NEAR_INLAND_CONTINENTALNESS), span(EROSIONS[2], EROSIONS[3]), w, 0.0, middle);
+				self.add_surface_biome(t, h, span(MID_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[2], w, 0.0, plateau);
+				self.add_surface_biome(t, h, MID_INLAND_CONTINENTALNESS, EROSIONS[3], w, 0.0, middle_or_badlands);
+				self.add_surface_biome(t, h, FAR_INLAND_CONTINENTALNESS, EROSIONS[3], w, 0.0, plateau);
+				self.add_surface_biome(t, h, span(COAST_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[4], w, 0.0, middle);
+				self.add_surface_biome(t, h, span(COAST_CONTINENTALNESS, NEAR_INLAND_CONTINENTALNESS), EROSIONS[5], w, 0.0, windswept_savanna);
+				self.add_surface_biome(t, h, span(MID_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[5], w, 0.0, shattered);
+				self.add_surface_biome(t, h, span(COAST_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[6], w, 0.0, middle);
+			}
+		}
+	}
+
+	fn add_swamps(&mut self, c: Range, w: Range) {
+		if self.has_mangroves() {
+			self.add_surface_biome(span(TEMPERATURES[1], TEMPERATURES[2]), FULL_RANGE, c, EROSIONS[6], w, 0.0, "swamp");
+			self.add_surface_biome(span(TEMPERATURES[3], TEMPERATURES[4]), FULL_RANGE, c, EROSIONS[6], w, 0.0, "mangrove_swamp");
+		} else {
+			self.add_surface_biome(UNFROZEN_RANGE, FULL_RANGE, c, EROSIONS[6], w, 0.0, "swamp");
+		}
+	}
+
+	fn add_mid_slice(&mut self, w: Range) {
+		self.add_surface_biome(FULL_RANGE, FULL_RANGE, COAST_CONTINENTALNESS, span(EROSIONS[0], EROSIONS[2]), w, 0.0, "stony_shore");
+		self.add_swamps(span(NEAR_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), w);
+		for (i, &t) in TEMPERATURES.iter().enumerate() {
+			for (j, &h) in HUMIDITIES.iter().enumerate() {
+				let middle = self.pick_middle_biome(i, j, w);
+				let middle_or_badlands = self.pick_middle_biome_or_badlands_if_hot(i, j, w);
+				let middle_or_badlands_or_slope = self.pick_middle_biome_or_badlands_if_hot_or_slope_if_cold(i, j, w);
+				let shattered = self.pick_shattered_biome(i, j, w);
+				let plateau = self.pick_plateau_biome(i, j, w);
+				let beach = pick_beach_biome(i);
+				let windswept_savanna = maybe_pick_windswept_savanna_biome(i, j, w, middle);
+				let shattered_coast = self.pick_shattered_coast_biome(i, j, w);
+				let slope = self.pick_slope_biome(i, j, w);
+				self.add_surface_biome(t, h, span(NEAR_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[0], w, 0.0, slope);
+				self.add_surface_biome(t, h, span(NEAR_INLAND_CONTINENTALNESS, MID_INLAND_CONTINENTALNESS), EROSIONS[1], w, 0.0, middle_or_badlands_or_slope);
+				self.add_surface_biome(t, h, FAR_INLAND_CONTINENTALNESS, EROSIONS[1], w, 0.0, if i == 0 { slope } else { plateau });
+				self.add_surface_biome(t, h, NEAR_INLAND_CONTINENTALNESS, EROSIONS[2], w, 0.0, middle);
+				self.add_surface_biome(t, h, MID_INLAND_CONTINENTALNESS, EROSIONS[2], w, 0.0, middle_or_badlands);
+				self.add_surface_biome(t, h, FAR_INLAND_CONTINENTALNESS, EROSIONS[2], w, 0.0, plateau);
+				self.add_surface_biome(t, h, span(COAST_CONTINENTALNESS, NEAR_INLAND_CONTINENTALNESS), EROSIONS[3], w, 0.0, middle);
+				self.add_surface_biome(t, h, span(MID_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[3], w, 0.0, middle_or_badlands);
+				if w[1] < 0.0 {
+					self.add_surface_biome(t, h, COAST_CONTINENTALNESS, EROSIONS[4], w, 0.0, beach);
+					self.add_surface_biome(t, h, span(NEAR_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[4], w, 0.0, middle);
+				} else {
+					self.add_surface_biome(t, h, span(COAST_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[4], w, 0.0, middle);
+				}
+				self.add_surface_biome(t, h, COAST_CONTINENTALNESS, EROSIONS[5], w, 0.0, shattered_coast);
+				self.add_surface_biome(t, h, NEAR_INLAND_CONTINENTALNESS, EROSIONS[5], w, 0.0, windswept_savanna);
+				self.add_surface_biome(t, h, span(MID_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[5], w, 0.0, shattered);
+				self.add_surface_biome(t, h, COAST_CONTINENTALNESS, EROSIONS[6], w, 0.0, if w[1] < 0.0 { beach } else { middle });
+				if i == 0 {
+					self.add_surface_biome(t, h, span(NEAR_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[6], w, 0.0, middle);
+				}
+			}
+		}
+	}
+
+	fn add_low_slice(&mut self, w: Range) {
+		self.add_surface_biome(FULL_RANGE, FULL_RANGE, COAST_CONTINENTALNESS, span(EROSIONS[0], EROSIONS[2]), w, 0.0, "stony_shore");
+		self.add_swamps(span(NEAR_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), w);
+		for (i, &t) in TEMPERATURES.iter().enumerate() {
+			for (j, &h) in HUMIDITIES.iter().enumerate() {
+				let middle = self.pick_middle_biome(i, j, w);
+				let middle_or_badlands = self.pick_middle_biome_or_badlands_if_hot(i, j, w);
+				let middle_or_badlands_or_slope = self.pick_middle_biome_or_badlands_if_hot_or_slope_if_cold(i, j, w);
+				let beach = pick_beach_biome(i);
+				let windswept_savanna = maybe_pick_windswept_savanna_biome(i, j, w, middle);
+				let shattered_coast = self.pick_shattered_coast_biome(i, j, w);
+				self.add_surface_biome(t, h, NEAR_INLAND_CONTINENTALNESS, span(EROSIONS[0], EROSIONS[1]), w, 0.0, middle_or_badlands);
+				self.add_surface_biome(t, h, span(MID_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), span(EROSIONS[0], EROSIONS[1]), w, 0.0, middle_or_badlands_or_slope);
+				self.add_surface_biome(t, h, NEAR_INLAND_CONTINENTALNESS, span(EROSIONS[2], EROSIONS[3]), w, 0.0, middle);
+				self.add_surface_biome(t, h, span(MID_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), span(EROSIONS[2], EROSIONS[3]), w, 0.0, middle_or_badlands);
+				self.add_surface_biome(t, h, COAST_CONTINENTALNESS, span(EROSIONS[3], EROSIONS[4]), w, 0.0, beach);
+				self.add_surface_biome(t, h, span(NEAR_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[4], w, 0.0, middle);
+				self.add_surface_biome(t, h, COAST_CONTINENTALNESS, EROSIONS[5], w, 0.0, shattered_coast);
+				self.add_surface_biome(t, h, NEAR_INLAND_CONTINENTALNESS, EROSIONS[5], w, 0.0, windswept_savanna);
+				self.add_surface_biome(t, h, span(MID_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[5], w, 0.0, middle);
+				self.add_surface_biome(t, h, COAST_CONTINENTALNESS, EROSIONS[6], w, 0.0, beach);
+				if i == 0 {
+					self.add_surface_biome(t, h, span(NEAR_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[6], w, 0.0, middle);
+				}
+			}
+		}
+	}
+
+	fn add_valleys(&mut self, w: Range) {
+		let coast_erosion = span(EROSIONS[0], EROSIONS[1]);
+		self.add_surface_biome(FROZEN_RANGE, FULL_RANGE, COAST_CONTINENTALNESS, coast_erosion, w, 0.0, if w[1] < 0.0 { "stony_shore" } else { "frozen_river" });
+		self.add_surface_biome(UNFROZEN_RANGE, FULL_RANGE, COAST_CONTINENTALNESS, coast_erosion, w, 0.0, if w[1] < 0.0 { "stony_shore" } else { "river" });
+		self.add_surface_biome(FROZEN_RANGE, FULL_RANGE, NEAR_INLAND_CONTINENTALNESS, coast_erosion, w, 0.0, "frozen_river");
+		self.add_surface_biome(UNFROZEN_RANGE, FULL_RANGE, NEAR_INLAND_CONTINENTALNESS, coast_erosion, w, 0.0, "river");
+		self.add_surface_biome(FROZEN_RANGE, FULL_RANGE, span(COAST_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), span(EROSIONS[2], EROSIONS[5]), w, 0.0, "frozen_river");
+		self.add_surface_biome(UNFROZEN_RANGE, FULL_RANGE, span(COAST_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), span(EROSIONS[2], EROSIONS[5]), w, 0.0, "river");
+		self.add_surface_biome(FROZEN_RANGE, FULL_RANGE, COAST_CONTINENTALNESS, EROSIONS[6], w, 0.0, "frozen_river");
+		self.add_surface_biome(UNFROZEN_RANGE, FULL_RANGE, COAST_CONTINENTALNESS, EROSIONS[6], w, 0.0, "river");
+		self.add_swamps(span(INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), w);
+		self.add_surface_biome(FROZEN_RANGE, FULL_RANGE, span(INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), EROSIONS[6], w, 0.0, "frozen_river");
+		for (i, &t) in TEMPERATURES.iter().enumerate() {
+			for (j, &h) in HUMIDITIES.iter().enumerate() {
+				let middle_or_badlands = self.pick_middle_biome_or_badlands_if_hot(i, j, w);
+				self.add_surface_biome(t, h, span(MID_INLAND_CONTINENTALNESS, FAR_INLAND_CONTINENTALNESS), coast_erosion, w, 0.0, middle_or_badlands);
+			}
+		}
+	}
+
+	fn add_underground_biomes(&mut self) {
+		self.add_underground_biome(FULL_RANGE, FULL_RANGE, [0.8, 1.0], FULL_RANGE, FULL_RANGE, 0.0, "dripstone_caves");
+		self.add_underground_biome(FULL_RANGE, [0.7, 1.0], FULL_RANGE, FULL_RANGE, FULL_RANGE, 0.0, "lush_caves");
+		if self.release >= (19, 0) {
+			self.add_bottom_biome(FULL_RANGE, FULL_RANGE, FULL_RANGE, span(EROSIONS[0], EROSIONS[1]), FULL_RANGE, 0.0, "deep_dark");
+		}
+	}
+
+	fn pick_middle_biome(&self, i: usize, j: usize, w: Range) -> &'static str {
+		if w[1] < 0.0 {
+			return MIDDLE_BIOMES[i][j].unwrap()
+		}
+		MIDDLE_BIOMES_VARIANT[i][j].or(MIDDLE_BIOMES[i][j]).unwrap()
+	}
+
+	fn pick_middle_biome_or_badlands_if_hot(&self, i: usize, j: usize, w: Range) -> &'static str {
+		if i == 4 { pick_badlands_biome(j, w) } else { self.pick_middle_biome(i, j, w) }
+	}
+
+	fn pick_middle_biome_or_badlands_if_hot_or_slope_if_cold(&self, i: usize, j: usize, w: Range) -> &'static str {
+		if i == 0 { self.pick_slope_biome(i, j, w) } else { self.pick_middle_biome_or_badlands_if_hot(i, j, w) }
+	}
+
+	fn pick_shattered_coast_biome(&self, i: usize, j: usize, w: Range) -> &'static str {
+		let biome = if w[1] >= 0.0 { self.pick_middle_biome(i, j, w) } else { pick_beach_biome(i) };
+		maybe_pick_windswept_savanna_biome(i, j, w, biome)
+	}
+
+	fn pick_plateau_biome(&self, i: usize, j: usize, w: Range) -> &'static str {
+		if w[1] < 0.0 {
+			return PLATEAU_BIOMES[i][j].unwrap()
+		}
+		let variants = if self.release >= (20, 0) { &PLATEAU_BIOMES_VARIANT_1_20 } else { &PLATEAU_BIOMES_VARIANT };
+		variants[i][j].or(PLATEAU_BIOMES[i][j]).unwrap()
+	}
+
+	fn pick_peak_biome(&self, i: usize, j: usize, w: Range) -> &'static str {
+		if i <= 2 {
+			if w[1] < 0.0 { "jagged_peaks" } else { "frozen_peaks" }
+		} else if i == 3 {
+			"stony_peaks"
+		} else {
+			pick_badlands_biome(j, w)
+		}
+	}
+
+	fn pick_slope_biome(&self, i: usize, j: usize, w: Range) -> &'static str {
+		if i >= 3 {
+			self.pick_plateau_biome(i, j, w)
+		} else if j <= 1 {
+			"snowy_slopes"
+		} else {
+			"grove"
+		}
+	}
+
+	fn pick_shattered_biome(&self, i: usize, j: usize, w: Range) -> &'static str {
+		SHATTERED_BIOMES[i][j].unwrap_or_else(|| self.pick_middle_biome(i, j, w))
+	}
+}
+
+fn maybe_pick_windswept_savanna_biome(i: usize, j: usize, w: Range, fallback: &'static str) -> &'static str {
+	if i > 1 && j < 4 && w[1] >= 0.0 { "windswept_savanna" } else { fallback }
+}
+
+fn pick_beach_biome(i: usize) -> &'static str {
+	match i {
+		0 => "snowy_beach",
+		4 => "desert",
+		_ => "beach",
+	}
+}
+
+fn pick_badlands_biome(j: usize, w: Range) -> &'static str {
+	if j < 2 {
+		if w[1] < 0.0 { "eroded_badlands" } else { "badlands" }
+	} else if j < 3 {
+		"badlands"
+	} else {
+		"wooded_badlands"
+	}
+}
@@ -0,0 +1,37 @@
+use std::collections::BTreeMap;
+use serde_json::Value;
+use super::dimension::Dimension;
+use super::registry::{ normalize_id, Registry };
+
+/// A world type from the registry's `worldgen/world_preset`, like `normal`,
+/// `amplified` or `flat`, defining the dimensions of a world.
+#[derive(Clone, Debug)]
+pub struct WorldPreset {
+	dimensions: BTreeMap<String, Value>,
+}
+
+impl WorldPreset {
+	pub fn load(registry: &Registry, id: &str) -> Result<Self, String> {
+		let json = registry.resource("worldgen/world_preset", id).ok_or_else(|| format!("Unknown world preset {}", normalize_id(id)))?;
+		Self::from_json(json)
+	}
+
+	pub fn from_json(json: &Value) -> Result<Self, String> {
+		let dimensions = json.get("dimensions").and_then(Value::as_object).ok_or("World preset needs dimensions")?;
+		Ok(Self {
+			dimensions: dimensions.iter().map(|(id, json)| (normalize_id(id), json.clone())).collect(),
+		})
+	}
+
+	/// The IDs of the dimensions, sorted.
+	pub fn dimension_ids(&self) -> Vec<&str> {
+		self.dimensions.keys().map(String::as_str).collect()
+	}
+
+	/// Builds one of the dimensions for a seed. Dimensions are only built
+	/// when needed, since each one seeds its own noises.
+	pub fn dimension(&self, id: &str, registry: &Registry, seed: i64) -> Result<Dimension, String> {
+		let json = self.dimensions.get(&normalize_id(id)).ok_or_else(|| format!("World preset has no dimension {}", normalize_id(id)))?;
+		Dimension::from_json(json, registry, seed)
+	}
+}